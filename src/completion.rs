@@ -0,0 +1,268 @@
+//! The completion popup's model: candidates gathered from pluggable sources,
+//! fuzzy filtered by the text typed since the popup opened.
+
+use std::{collections::HashSet, path::{Path, PathBuf}};
+
+use crate::{editor::{Context, location::Location}, fuzzy};
+
+/// at most this many candidates come from a single source.
+const MAX_CANDIDATES: usize = 200;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Candidate {
+  pub label: String,
+  pub insert_text: String,
+  pub detail: Option<String>,
+}
+
+impl Candidate {
+  pub fn new(label: &str) -> Self {
+    Self { label: label.to_string(), insert_text: label.to_string(), detail: None }
+  }
+}
+
+/// what is being completed: plain words, or a path when the text before the
+/// cursor contains a '/'.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Token {
+  Word,
+  Path,
+}
+
+pub trait Source {
+  /// candidates for `prefix`. filtering and ranking happen afterwards.
+  fn candidates(&self, editor: &Context, token: Token, prefix: &str) -> Vec<Candidate>;
+}
+
+/// words that already appear in the document.
+pub struct DocumentWords;
+
+impl Source for DocumentWords {
+  fn candidates(&self, editor: &Context, token: Token, prefix: &str) -> Vec<Candidate> {
+    if token != Token::Word {
+      return Vec::new();
+    }
+    let text = editor.document().text();
+    let mut seen = HashSet::new();
+    text.split(|c: char| !is_word_char(c))
+      .filter(|word| !word.is_empty() && *word != prefix && !word.chars().all(|c| c.is_ascii_digit()))
+      .filter(|word| seen.insert(*word))
+      .take(MAX_CANDIDATES)
+      .map(Candidate::new)
+      .collect()
+  }
+}
+
+/// entries of the directory named before the last '/', relative to the
+/// document's directory.
+pub struct FilePaths;
+
+impl Source for FilePaths {
+  fn candidates(&self, editor: &Context, token: Token, prefix: &str) -> Vec<Candidate> {
+    if token != Token::Path {
+      return Vec::new();
+    }
+    let (dir, _) = prefix.rsplit_once('/').unwrap_or(("", prefix));
+    let base = editor.document().path()
+      .and_then(Path::parent)
+      .map(Path::to_path_buf)
+      .unwrap_or_else(|| PathBuf::from("."));
+    let lookup = if dir.is_empty() && prefix.starts_with('/') {
+      PathBuf::from("/")
+    } else if let Some(home) = dir.strip_prefix('~').and_then(|rest| Some(PathBuf::from(std::env::var_os("HOME")?).join(rest.trim_start_matches('/')))) {
+      home
+    } else {
+      base.join(dir)
+    };
+    let Ok(entries) = std::fs::read_dir(lookup) else { return Vec::new() };
+    let mut candidates: Vec<Candidate> = entries
+      .filter_map(|entry| entry.ok())
+      .take(MAX_CANDIDATES)
+      .map(|entry| {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+        let label = if is_dir { format!("{}/", name) } else { name };
+        Candidate {
+          insert_text: format!("{}/{}", dir, label),
+          label,
+          detail: Some(if is_dir { "dir" } else { "file" }.to_string()),
+        }
+      })
+      .collect();
+    candidates.sort_by(|a, b| a.label.cmp(&b.label));
+    candidates
+  }
+}
+
+pub fn default_sources() -> Vec<Box<dyn Source>> {
+  vec![Box::new(DocumentWords), Box::new(FilePaths)]
+}
+
+pub fn is_word_char(char: char) -> bool {
+  char.is_alphanumeric() || char == '_'
+}
+
+fn is_path_char(char: char) -> bool {
+  is_word_char(char) || matches!(char, '/' | '.' | '-' | '~')
+}
+
+/// an open completion popup.
+pub struct Completion {
+  /// where the text being completed starts. accepting replaces from here to the cursor.
+  pub start: Location,
+  pub token: Token,
+  candidates: Vec<Candidate>,
+  /// indices into `candidates`, best match first.
+  filtered: Vec<usize>,
+  pub selected: usize,
+  query: String,
+}
+
+impl Completion {
+  /// open at the cursor, collecting candidates from `sources`.
+  pub fn open(editor: &Context, sources: &[Box<dyn Source>]) -> Self {
+    let (start, token, query) = token_before_cursor(editor);
+    let mut completion = Self {
+      start,
+      token,
+      candidates: Vec::new(),
+      filtered: Vec::new(),
+      selected: 0,
+      query: query.clone(),
+    };
+    for source in sources {
+      completion.add(source.candidates(editor, token, &query));
+    }
+    completion
+  }
+
+  /// add candidates, e.g. when a language server answers.
+  pub fn add(&mut self, candidates: Vec<Candidate>) {
+    for candidate in candidates {
+      if !self.candidates.iter().any(|c| c.label == candidate.label) {
+        self.candidates.push(candidate);
+      }
+    }
+    self.refilter();
+  }
+
+  /// follow the text typed since opening. returns false once the cursor has
+  /// left the completed text and the popup should close.
+  pub fn update(&mut self, editor: &Context) -> bool {
+    let cursor = editor.cursor_location();
+    if cursor.ln != self.start.ln || cursor < self.start {
+      return false;
+    }
+    let line = editor.document().line_text(cursor.ln);
+    let query: String = line.chars().skip(self.start.col as usize).take((cursor.col - self.start.col) as usize).collect();
+    let allowed = match self.token {
+      Token::Word => query.chars().all(is_word_char),
+      Token::Path => query.chars().all(is_path_char),
+    };
+    if !allowed {
+      return false;
+    }
+    if query != self.query {
+      self.query = query;
+      self.refilter();
+    }
+    true
+  }
+
+  fn refilter(&mut self) {
+    let mut scored: Vec<(i64, usize)> = self.candidates.iter()
+      .enumerate()
+      .filter_map(|(i, candidate)| {
+        let text = match self.token {
+          Token::Word => &candidate.label,
+          Token::Path => &candidate.insert_text,
+        };
+        fuzzy::score(&self.query, text).map(|score| (score, i))
+      })
+      .collect();
+    // stable, so equal scores keep their source order
+    scored.sort_by_key(|(score, _)| -score);
+    self.filtered = scored.into_iter().map(|(_, i)| i).collect();
+    self.selected = self.selected.min(self.filtered.len().saturating_sub(1));
+  }
+
+  /// the visible candidates, best first.
+  pub fn items(&self) -> Vec<&Candidate> {
+    self.filtered.iter().map(|i| &self.candidates[*i]).collect()
+  }
+
+  pub fn selected_item(&self) -> Option<&Candidate> {
+    self.filtered.get(self.selected).map(|i| &self.candidates[*i])
+  }
+
+  pub fn next(&mut self) {
+    if !self.filtered.is_empty() {
+      self.selected = (self.selected + 1) % self.filtered.len();
+    }
+  }
+
+  pub fn previous(&mut self) {
+    if !self.filtered.is_empty() {
+      self.selected = (self.selected + self.filtered.len() - 1) % self.filtered.len();
+    }
+  }
+
+  /// replace the completed text with the selected candidate.
+  pub fn accept(&self, editor: &mut Context) {
+    if let Some(candidate) = self.selected_item() {
+      let text = candidate.insert_text.clone();
+      editor.select(self.start, editor.cursor_location());
+      editor.input(&text);
+    }
+  }
+}
+
+/// the start, kind and text of whatever is being typed right before the cursor.
+fn token_before_cursor(editor: &Context) -> (Location, Token, String) {
+  let cursor = editor.cursor_location();
+  let before: Vec<char> = editor.document().line_text(cursor.ln).chars().take(cursor.col as usize).collect();
+  let path_len = before.iter().rev().take_while(|c| is_path_char(**c)).count();
+  let path: String = before[before.len() - path_len..].iter().collect();
+  let (token, len) = if path.contains('/') {
+    (Token::Path, path_len)
+  } else {
+    (Token::Word, before.iter().rev().take_while(|c| is_word_char(**c)).count())
+  };
+  let text: String = before[before.len() - len..].iter().collect();
+  (Location { ln: cursor.ln, col: cursor.col - len as u32 }, token, text)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::document::Document;
+
+  #[test]
+  fn test_word_completion() {
+    let mut editor = Context::with_document(Document::from("get_line line_count\nlet x = gl"), 40, 10);
+    editor.set_cursor(Location { ln: 1, col: 10 });
+    let mut completion = Completion::open(&editor, &default_sources());
+    assert_eq!(completion.start, Location { ln: 1, col: 8 });
+    assert_eq!(completion.items()[0].label, "get_line");
+    editor.insert_char('n');
+    assert!(completion.update(&editor));
+    assert_eq!(completion.items().len(), 1);
+    completion.accept(&mut editor);
+    assert_eq!(editor.document().line_text(1), "let x = get_line");
+    editor.insert_char(' ');
+    assert!(!completion.update(&editor));
+  }
+
+  #[test]
+  fn test_navigation() {
+    let mut editor = Context::with_document(Document::from("aa ab ac a"), 40, 10);
+    editor.set_cursor(Location { ln: 0, col: 10 });
+    let mut completion = Completion::open(&editor, &default_sources());
+    completion.add(vec![Candidate::new("ad"), Candidate::new("aa")]);
+    assert_eq!(completion.items().len(), 4);
+    completion.previous();
+    assert_eq!(completion.selected, 3);
+    completion.next();
+    assert_eq!(completion.selected, 0);
+  }
+}
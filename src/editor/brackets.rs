@@ -0,0 +1,130 @@
+use super::{
+  character::Character,
+  document::Document,
+  indent::{CLOSERS, OPENERS},
+  language::Language,
+  location::Location,
+};
+
+/// pairs that are closed automatically when the opening half is typed.
+pub const AUTO_PAIRS: [(char, char); 5] = [('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')];
+
+/// how many lines a match is searched across before giving up.
+const MAX_SCAN_LINES: u32 = 5000;
+
+/// the closing half of an auto pair.
+pub fn partner(opener: char) -> Option<char> {
+  AUTO_PAIRS.iter().find(|(open, _)| *open == opener).map(|(_, close)| *close)
+}
+
+/// whether typing `char` in front of the same char should step over it.
+pub fn is_closing(char: char) -> bool {
+  AUTO_PAIRS.iter().any(|(_, close)| *close == char)
+}
+
+/// which characters of a line are code, as opposed to the inside of a
+/// string literal or a line comment.
+pub fn code_mask(line: &[Character], language: Language) -> Vec<bool> {
+  let comment: Vec<char> = language.line_comment().unwrap_or_default().chars().collect();
+  let mut mask = Vec::with_capacity(line.len());
+  let mut string: Option<char> = None;
+  let mut escaped = false;
+  for (i, c) in line.iter().enumerate() {
+    match string {
+      Some(quote) => {
+        mask.push(false);
+        if escaped {
+          escaped = false;
+        } else if c.char == '\\' {
+          escaped = true;
+        } else if c.char == quote {
+          string = None;
+        }
+      },
+      None => {
+        let starts_comment = !comment.is_empty()
+          && line[i..].iter().map(|c| c.char).take(comment.len()).eq(comment.iter().copied());
+        if starts_comment {
+          mask.resize(line.len(), false);
+          break;
+        }
+        if language.quotes().contains(&c.char) {
+          string = Some(c.char);
+          mask.push(false);
+        } else {
+          mask.push(true);
+        }
+      },
+    }
+  }
+  mask
+}
+
+/// the location of the bracket matching the one at `location`.
+/// brackets inside strings and comments are skipped.
+pub fn find_match(document: &Document, location: Location, language: Language) -> Option<Location> {
+  let char = document.get_character(location)?.char;
+  let (open, close, forward) = if let Some(i) = OPENERS.iter().position(|c| *c == char) {
+    (char, CLOSERS[i], true)
+  } else if let Some(i) = CLOSERS.iter().position(|c| *c == char) {
+    (OPENERS[i], char, false)
+  } else {
+    return None;
+  };
+  let line = document.line(location.ln)?;
+  let start = line.iter().position(|c| c.location == location)?;
+  if !code_mask(&line, language)[start] {
+    return None;
+  }
+  let mut depth = 0;
+  let mut visit = |c: &Character| {
+    if c.char == open {
+      depth += 1;
+    } else if c.char == close {
+      depth -= 1;
+    }
+    depth == 0
+  };
+  if forward {
+    let last_ln = (location.ln + MAX_SCAN_LINES).min(document.line_count() as u32);
+    for ln in location.ln..last_ln {
+      let line = document.line(ln)?;
+      let mask = code_mask(&line, language);
+      let skip = if ln == location.ln { start } else { 0 };
+      let found = line.iter().zip(mask).skip(skip).find(|(c, code)| *code && visit(c));
+      if let Some((c, _)) = found {
+        return Some(c.location);
+      }
+    }
+  } else {
+    let first_ln = location.ln.saturating_sub(MAX_SCAN_LINES);
+    for ln in (first_ln..=location.ln).rev() {
+      let line = document.line(ln)?;
+      let mask = code_mask(&line, language);
+      let take = if ln == location.ln { start + 1 } else { line.len() };
+      let found = line.iter().zip(mask).take(take).rev().find(|(c, code)| *code && visit(c));
+      if let Some((c, _)) = found {
+        return Some(c.location);
+      }
+    }
+  }
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_find_match() {
+    let doc = Document::from("fn a() {\n  b(\"}\"); // }\n  [c]\n}");
+    let open = Location { ln: 0, col: 7 };
+    let close = Location { ln: 3, col: 0 };
+    assert_eq!(find_match(&doc, open, Language::Rust), Some(close));
+    assert_eq!(find_match(&doc, close, Language::Rust), Some(open));
+    assert_eq!(find_match(&doc, Location { ln: 2, col: 2 }, Language::Rust), Some(Location { ln: 2, col: 4 }));
+    // without language information the brace in the string counts
+    assert_eq!(find_match(&doc, open, Language::PlainText), Some(Location { ln: 1, col: 5 }));
+    assert_eq!(find_match(&doc, Location { ln: 0, col: 0 }, Language::Rust), None);
+  }
+}
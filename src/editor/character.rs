@@ -3,6 +3,8 @@ use super::location::Location;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Character {
+  /// position among the document's characters. in large-file mode it is the
+  /// byte offset into the file instead.
   pub index: usize,
   pub char: char,
   pub width: u32,
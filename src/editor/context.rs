@@ -1,11 +1,22 @@
-use super::{location::Location, document::Document, range::Range, position::Position};
-use crate::macros::log;
+use super::{location::Location, document::{Document, Change}, range::Range, position::Position, indent, language::Language, brackets, cursor::Cursor, diagnostic::Diagnostic, fold::{self, Fold}, scroll::{self, HorizontalScroll, ScrollOff}, whitespace::SaveActions};
 
 pub struct Context {
   viewport_size: ViewportSize,
   cursor_location: Location,
+  selection: Option<Range>,
+  /// cursors besides the primary one at `cursor_location`.
+  extra_cursors: Vec<Cursor>,
+  language: Language,
+  indent_unit: String,
+  save_actions: SaveActions,
+  /// columns to draw a guide line at.
+  rulers: Vec<u32>,
+  scroll_off: ScrollOff,
   offset_top: u32,
   offset_left: u32,
+  /// the cursor and document version when the viewport was last scrolled by
+  /// hand. the viewport stays put until either of them changes.
+  scrolled: Option<(Location, u64)>,
   document: Document,
 }
 
@@ -15,18 +26,153 @@ impl Context {
       viewport_size: ViewportSize { width, height },
       document: Document::default(),
       cursor_location: Location { ln: 0, col: 0 },
+      selection: None,
+      extra_cursors: Vec::new(),
+      language: Language::default(),
+      indent_unit: Language::default().indent_unit().to_string(),
+      save_actions: SaveActions::default(),
+      rulers: Vec::new(),
+      scroll_off: ScrollOff::default(),
       offset_left: 0,
       offset_top: 0,
+      scrolled: None,
     }
   }
 
+  pub fn with_document(document: Document, width: u32, height: u32) -> Self {
+    let language = document.path().map(Language::from_path).unwrap_or_default();
+    Self {
+      document,
+      language,
+      indent_unit: language.indent_unit().to_string(),
+      ..Self::with_size(width, height)
+    }
+  }
+
+  pub fn document(&self) -> &Document {
+    &self.document
+  }
+
+  pub fn language(&self) -> Language {
+    self.language
+  }
+
+  /// tidy whitespace per the save actions and write the document.
+  pub fn save(&mut self) -> anyhow::Result<()> {
+    self.clean_up_whitespace();
+    self.document.save()
+  }
+
+  /// what one indent level is, a tab or some spaces.
+  pub fn set_indent_unit(&mut self, unit: &str) {
+    self.indent_unit = unit.to_string();
+  }
+
+  pub fn set_tab_width(&mut self, tab_width: Option<u32>) {
+    self.document.set_tab_width(tab_width);
+  }
+
+  pub fn set_line_ending(&mut self, line_ending: &'static str) {
+    self.document.set_line_ending(line_ending);
+  }
+
+  pub fn set_bom(&mut self, bom: bool) {
+    self.document.set_bom(bom);
+  }
+
+  pub fn rulers(&self) -> &[u32] {
+    &self.rulers
+  }
+
+  pub fn set_rulers(&mut self, rulers: Vec<u32>) {
+    self.rulers = rulers;
+  }
+
+  /// how close the cursor gets to the edges before the viewport follows it.
+  pub fn set_scroll_off(&mut self, scroll_off: ScrollOff) {
+    self.scroll_off = scroll_off;
+  }
+
+  /// what to tidy up on every save.
+  pub fn set_save_actions(&mut self, actions: SaveActions) {
+    self.save_actions = actions;
+  }
+
+  pub fn save_actions(&self) -> SaveActions {
+    self.save_actions
+  }
+
+  /// apply the save actions, keeping cursors on what is left of their lines.
+  fn clean_up_whitespace(&mut self) {
+    if !self.document.apply_save_actions(self.save_actions) {
+      return;
+    }
+    let clamp = |document: &Document, location: Location| {
+      let last_ln = document.line_count().saturating_sub(1) as u32;
+      let ln = location.ln.min(last_ln);
+      let len = document.line(ln).map_or(0, |line| line.len()) as u32;
+      Location { ln, col: location.col.min(len) }
+    };
+    self.cursor_location = clamp(&self.document, self.cursor_location);
+    self.selection = None;
+    self.extra_cursors = self.extra_cursors.iter().map(|cursor| Cursor::new(clamp(&self.document, cursor.location))).collect();
+    self.dedup_cursors();
+  }
+
+  /// save to `path`, picking the language from the new name.
+  pub fn save_as(&mut self, path: &std::path::Path) -> anyhow::Result<()> {
+    self.clean_up_whitespace();
+    self.document.save_as(path)?;
+    self.language = Language::from_path(path);
+    self.indent_unit = self.language.indent_unit().to_string();
+    Ok(())
+  }
+
+  /// replace the text with `text` as read from disk, keeping the cursor on
+  /// the same line where possible. the reload can be undone.
+  pub fn reload(&mut self, text: &str) {
+    let cursor = self.cursor_location;
+    let line_count = self.document.line_count() as u32;
+    self.replace_lines(0, line_count, text);
+    self.document.mark_saved();
+    self.set_cursor(cursor);
+  }
+
+  /// queue every edit from now on, see `Document::take_changes`.
+  pub fn track_changes(&mut self) {
+    self.document.track_changes();
+  }
+
+  pub fn take_changes(&mut self) -> Vec<Change> {
+    self.document.take_changes()
+  }
+
+  pub fn cursor_location(&self) -> Location {
+    self.cursor_location
+  }
+
+  /// every cursor, the primary one included, in document order.
+  pub fn cursors(&self) -> Vec<Cursor> {
+    let mut cursors = self.extra_cursors.clone();
+    cursors.push(Cursor { location: self.cursor_location, selection: self.selection });
+    cursors.sort_by(|a, b| a.location.partial_cmp(&b.location).unwrap());
+    cursors
+  }
+
+  /// insert `content` at every cursor, replacing their selections.
   pub fn input(&mut self, content: &str) {
+    self.edit_all(|ctx| {
+      ctx.delete_selection();
+      ctx.insert_at_cursor(content);
+    });
+  }
+
+  fn insert_at_cursor(&mut self, content: &str) {
     if let Ok(chars) = self.document.insert(self.cursor_location, content) {
       if let Some(end_char) = chars.last() {
-        let new_cursor_location = {
-          let mut loc = end_char.location.clone();
-          loc.col += 1;
-          loc
+        let new_cursor_location = match end_char.char {
+          '\n' => Location { ln: end_char.location.ln + 1, col: 0 },
+          _ => Location { ln: end_char.location.ln, col: end_char.location.col + 1 },
         };
         self.cursor_location = new_cursor_location;
         self.auto_center_cursor();
@@ -34,8 +180,163 @@ impl Context {
     }
   }
 
+  /// insert a typed character. brackets and quotes get their closing partner,
+  /// typing a closer in front of the same closer steps over it, and a closing
+  /// bracket typed on an otherwise blank line removes one indent level first.
+  pub fn insert_char(&mut self, char: char) {
+    self.edit_all(|ctx| {
+      ctx.delete_selection();
+      ctx.insert_char_at_cursor(char);
+    });
+  }
+
+  fn insert_char_at_cursor(&mut self, char: char) {
+    let next = self.document.get_character(self.cursor_location).map(|c| c.char);
+    if next == Some(char) && brackets::is_closing(char) {
+      return self.step_right();
+    }
+    if let Some(closer) = brackets::partner(char) {
+      if self.should_auto_close(char, next) {
+        self.insert_at_cursor(&format!("{}{}", char, closer));
+        return self.step_left();
+      }
+    }
+    if indent::CLOSERS.contains(&char) {
+      let line = self.document.line_text(self.cursor_location.ln);
+      let before_cursor: String = line.chars().take(self.cursor_location.col as usize).collect();
+      if !before_cursor.is_empty() && before_cursor.trim().is_empty() {
+        let dedented = indent::dedent(&before_cursor, &self.indent_unit).to_string();
+        self.replace_leading_whitespace(self.cursor_location.ln, &dedented);
+      }
+    }
+    self.insert_at_cursor(&char.to_string());
+  }
+
+  fn should_auto_close(&self, opener: char, next: Option<char>) -> bool {
+    let next_allows = match next {
+      None => true,
+      Some(next) => next.is_whitespace() || indent::CLOSERS.contains(&next),
+    };
+    if !next_allows {
+      return false;
+    }
+    if opener == '"' || opener == '\'' {
+      if opener == '\'' && self.language == Language::Rust {
+        return false;
+      }
+      let previous = self.document.before(self.cursor_location).map(|c| c.char);
+      return !matches!(previous, Some(p) if p.is_alphanumeric() || p == opener);
+    }
+    true
+  }
+
+  /// Tab: insert one indent level at every cursor.
+  pub fn insert_indent(&mut self) {
+    let unit = self.indent_unit.clone();
+    self.input(&unit);
+  }
+
+  /// Enter: carry over the current line's indentation, one level more after a
+  /// line that opens a block. between a bracket pair the closer moves to its own line.
+  pub fn newline(&mut self) {
+    self.edit_all(|ctx| {
+      ctx.delete_selection();
+      ctx.newline_at_cursor();
+    });
+  }
+
+  fn newline_at_cursor(&mut self) {
+    let Location { ln, col } = self.cursor_location;
+    let line = self.document.line_text(ln);
+    let before_cursor: String = line.chars().take(col as usize).collect();
+    let after_cursor: String = line.chars().skip(before_cursor.chars().count()).collect();
+    let base = indent::leading_whitespace(&before_cursor).to_string();
+    if !indent::opens_block(&before_cursor, self.language()) {
+      return self.insert_at_cursor(&format!("{}{}", self.document.line_ending(), base));
+    }
+    let inner = format!("{}{}", base, self.indent_unit);
+    let pair_closed = matches!(
+      (before_cursor.trim_end().chars().last().and_then(indent::closer_of), after_cursor.trim_start().chars().next()),
+      (Some(closer), Some(next)) if closer == next
+    );
+    if pair_closed {
+      let newline = self.document.line_ending();
+      self.insert_at_cursor(&format!("{}{}{}{}", newline, inner, newline, base));
+      self.cursor_location = Location { ln: ln + 1, col: inner.chars().count() as u32 };
+      self.auto_center_cursor();
+    } else {
+      self.insert_at_cursor(&format!("{}{}", self.document.line_ending(), inner));
+    }
+  }
+
+  /// reindent the selected lines, or the cursor line without a selection.
+  pub fn reindent_selection(&mut self) {
+    let (start, end) = match self.selection {
+      Some(range) => (range.start.ln, range.end.ln),
+      None => (self.cursor_location.ln, self.cursor_location.ln),
+    };
+    let language = self.language();
+    self.document.begin_edit();
+    for ln in start..=end {
+      let line = self.document.line_text(ln);
+      let previous = (0..ln).rev()
+        .map(|ln| self.document.line_text(ln))
+        .find(|line| !line.trim().is_empty());
+      let wanted = if line.trim().is_empty() {
+        String::new()
+      } else {
+        indent::indent_for(previous.as_deref(), &line, &self.indent_unit, language)
+      };
+      let current = indent::leading_whitespace(&line);
+      if current != wanted {
+        let delta = wanted.chars().count() as i64 - current.chars().count() as i64;
+        let cursor = self.cursor_location;
+        self.replace_leading_whitespace(ln, &wanted);
+        self.cursor_location = if cursor.ln == ln {
+          Location { ln, col: (cursor.col as i64 + delta).max(0) as u32 }
+        } else {
+          cursor
+        };
+      }
+    }
+    self.document.end_edit();
+    self.auto_center_cursor();
+  }
+
+  /// swap the leading whitespace of line `ln` for `whitespace` and leave the
+  /// cursor right after it.
+  fn replace_leading_whitespace(&mut self, ln: u32, whitespace: &str) {
+    let line = self.document.line_text(ln);
+    let current_len = indent::leading_whitespace(&line).chars().count() as u32;
+    let start = Location { ln, col: 0 };
+    let _ = self.document.remove(Range::new(start, Location { ln, col: current_len }));
+    self.cursor_location = start;
+    if !whitespace.is_empty() {
+      self.insert_at_cursor(whitespace);
+    }
+  }
+
+  /// delete the character before the cursor. between an empty auto pair,
+  /// like `(|)`, both halves go.
   pub fn backspace(&mut self) {
+    self.edit_all(|ctx| {
+      if !ctx.delete_selection() {
+        ctx.backspace_at_cursor();
+      }
+    });
+  }
+
+  fn backspace_at_cursor(&mut self) {
     if let Some(char) = self.document.before(self.cursor_location) {
+      let next = self.document.get_character(self.cursor_location).map(|c| c.char);
+      if next.is_some() && brackets::partner(char.char) == next {
+        let end = Location { ln: self.cursor_location.ln, col: self.cursor_location.col + 1 };
+        if self.document.remove(Range::new(char.location, end)).is_ok() {
+          self.cursor_location = char.location;
+          self.auto_center_cursor();
+        }
+        return;
+      }
       if self.document.remove(Range::new(char.location, self.cursor_location)).is_ok() {
         self.cursor_location = char.location;
         self.auto_center_cursor();
@@ -46,9 +347,13 @@ impl Context {
   pub fn visual_area(&self) -> Vec<String> {
     let width = self.viewport_size.width;
     let offset_left = self.offset_left;
-    let cuted_lines: Vec<String> = self.document.lines().iter()
-      .skip(self.offset_top as usize)
-      .take(self.viewport_size.height as usize)
+    let lines = if self.document.folds().is_empty() {
+      self.document.lines_in(self.offset_top as usize, self.viewport_size.height as usize)
+    } else {
+      self.visible_lines().into_iter().filter_map(|ln| self.document.line(ln)).collect()
+    };
+    let cuted_lines: Vec<String> = lines
+      .iter()
       .map(|line| {
         let mut str = String::new();
         let mut cut_used: u32 = 0;
@@ -59,8 +364,7 @@ impl Context {
             if cut_used + char.width <= offset_left {
               cut_used += char.width;
             } else {
-              let slot: String = std::iter::repeat("<").take((cut_used + char.width - offset_left) as usize).collect();
-              str = format!("{}{}", str, slot);
+              str.push_str(&"<".repeat((cut_used + char.width - offset_left) as usize));
               cut_used = offset_left;
             }
           } else {
@@ -69,10 +373,12 @@ impl Context {
               // stage 3
               if lenght_used + char.width <= width {
                 lenght_used += char.width;
-                str.push(char.char);
+                match char.char {
+                  '\t' => str.push_str(&" ".repeat(char.width as usize)),
+                  _ => str.push(char.char),
+                }
               } else {
-                let slot: String = std::iter::repeat(">").take((char.width - (lenght_used + char.width - width)) as usize).collect();
-                str = format!("{}{}", str, slot);
+                str.push_str(&">".repeat((width - lenght_used) as usize));
                 lenght_used = width;
               }
             } else {
@@ -87,32 +393,752 @@ impl Context {
     cuted_lines
   }
 
-  pub fn delete(&mut self) { todo!() }
-  pub fn up(&mut self) { todo!() }
-  pub fn down(&mut self) { todo!() }
-  pub fn left(&mut self) { todo!() }
-  pub fn right(&mut self) { todo!() }
-  pub fn set_cursor(&mut self, location: Location) { todo!() }
-  pub fn select(&mut self, start: Location, end: Location) { todo!() }
+  /// delete the character after the cursor, joining lines at the end of a line.
+  pub fn delete(&mut self) {
+    self.edit_all(|ctx| {
+      if !ctx.delete_selection() {
+        let end = ctx.next_location(ctx.cursor_location);
+        ctx.remove_to(end);
+      }
+    });
+  }
+
+  /// delete from the cursor back to the start of the previous word.
+  pub fn delete_word_backward(&mut self) {
+    self.edit_all(|ctx| {
+      if !ctx.delete_selection() {
+        let start = ctx.word_start_before(ctx.cursor_location);
+        ctx.remove_to(start);
+      }
+    });
+  }
+
+  /// delete from the cursor to the end of the next word.
+  pub fn delete_word_forward(&mut self) {
+    self.edit_all(|ctx| {
+      if !ctx.delete_selection() {
+        let end = ctx.word_end_after(ctx.cursor_location);
+        ctx.remove_to(end);
+      }
+    });
+  }
+
+  /// delete the whole line of every cursor, line break included.
+  pub fn delete_line(&mut self) {
+    self.edit_all(|ctx| {
+      ctx.selection = None;
+      let Location { ln, col } = ctx.cursor_location;
+      let last_ln = ctx.document.line_count().saturating_sub(1) as u32;
+      let range = if ln < last_ln {
+        Range::new(Location { ln, col: 0 }, Location { ln: ln + 1, col: 0 })
+      } else {
+        // the last line has no line break of its own, take the one before it
+        let start = match ln {
+          0 => Location { ln, col: 0 },
+          _ => ctx.line_end(ln - 1),
+        };
+        Range::new(start, ctx.document.end_location())
+      };
+      if ctx.document.remove(range).is_ok() {
+        let ln = ln.min(ctx.document.line_count().saturating_sub(1) as u32);
+        let x = ctx.document.line(ln).unwrap_or_default().len() as u32;
+        ctx.cursor_location = Location { ln, col: col.min(x) };
+        ctx.keep_cursor_in_document();
+      }
+    });
+  }
+
+  /// delete from the cursor to the end of the line. at the end of a line the
+  /// line break goes instead, joining the next line.
+  pub fn kill_to_end_of_line(&mut self) {
+    self.edit_all(|ctx| {
+      ctx.selection = None;
+      let ln = ctx.cursor_location.ln;
+      let line_end = ctx.line_end(ln);
+      let end = if line_end == ctx.cursor_location {
+        // the whole "\r\n", not just its '\r'
+        match ctx.document.line_count() > ln as usize + 1 {
+          true => Location { ln: ln + 1, col: 0 },
+          false => line_end,
+        }
+      } else {
+        line_end
+      };
+      ctx.remove_to(end);
+    });
+  }
+
+  /// remove the text between the cursor and `other`, in either direction.
+  fn remove_to(&mut self, other: Location) {
+    let cursor = self.cursor_location;
+    if other == cursor {
+      return;
+    }
+    let range = if other < cursor { Range::new(other, cursor) } else { Range::new(cursor, other) };
+    if self.document.remove(range).is_ok() {
+      self.cursor_location = range.start;
+      self.keep_cursor_in_document();
+    }
+  }
+
+  fn keep_cursor_in_document(&mut self) {
+    if self.document.is_out_of_document(self.cursor_location) {
+      self.cursor_location = self.document.end_location();
+    }
+  }
+
+  /// the location right after the character at `location`.
+  fn next_location(&self, location: Location) -> Location {
+    match self.document.after(location) {
+      Some(char) => char.location,
+      None => self.document.end_location(),
+    }
+  }
+
+  /// the location of the line break ending line `ln`, or the document end.
+  fn line_end(&self, ln: u32) -> Location {
+    self.document.line_break(ln)
+  }
+
+  /// where a word deleted backwards from `location` starts: whitespace
+  /// first, then a run of word characters or of punctuation.
+  fn word_start_before(&self, location: Location) -> Location {
+    let mut start = location;
+    let mut class = None;
+    while let Some(char) = self.document.before(start) {
+      let char_class = CharClass::of(char.char);
+      match class {
+        None if char_class == CharClass::Space && char.char != '\n' => {},
+        None if char.char == '\n' => {
+          if start == location {
+            start = char.location;
+          }
+          break;
+        },
+        None => class = Some(char_class),
+        Some(class) if class != char_class => break,
+        _ => {},
+      }
+      start = char.location;
+    }
+    start
+  }
+
+  /// where a word deleted forwards from `location` ends.
+  fn word_end_after(&self, location: Location) -> Location {
+    let mut end = location;
+    let mut class = None;
+    while let Some(char) = self.document.get_character(end) {
+      let char_class = CharClass::of(char.char);
+      match class {
+        None if char_class == CharClass::Space && char.char != '\n' => {},
+        None if char.char == '\n' => {
+          if end == location {
+            end = self.next_location(end);
+          }
+          break;
+        },
+        None => class = Some(char_class),
+        Some(class) if class != char_class => break,
+        _ => {},
+      }
+      end = self.next_location(end);
+    }
+    end
+  }
+
+  pub fn up(&mut self) {
+    self.move_all(|ctx| {
+      if let Some(ln) = ctx.line_above(ctx.cursor_location.ln) {
+        ctx.move_to_line(ln);
+      }
+    });
+  }
+
+  pub fn down(&mut self) {
+    self.move_all(|ctx| {
+      if let Some(ln) = ctx.line_below(ctx.cursor_location.ln) {
+        ctx.move_to_line(ln);
+      }
+    });
+  }
+
+  pub fn left(&mut self) {
+    self.move_all(Self::step_left);
+  }
+
+  pub fn right(&mut self) {
+    self.move_all(Self::step_right);
+  }
+
+  /// move the cursor being worked on one character back, leaving the other
+  /// cursors alone.
+  fn step_left(&mut self) {
+    if let Some(char) = self.document.before(self.cursor_location) {
+      self.cursor_location = char.location;
+    }
+    // stepping back over a fold lands at the end of its first line
+    if self.document.is_hidden(self.cursor_location.ln) {
+      let ln = fold::line_at_row(self.document.folds(), fold::row_of(self.document.folds(), self.cursor_location.ln));
+      self.cursor_location = self.line_end(ln);
+    }
+  }
+
+  /// move the cursor being worked on one character forward, leaving the
+  /// other cursors alone.
+  fn step_right(&mut self) {
+    if let Some(char) = self.document.character_at(self.cursor_location) {
+      let location = match char.char {
+        '\n' => Location { ln: char.location.ln + 1, col: 0 },
+        _ => Location { ln: char.location.ln, col: char.location.col + 1 },
+      };
+      // a fold is stepped over as if it were the line break it ends with
+      self.cursor_location = match self.document.is_hidden(location.ln) {
+        true => match self.line_below(char.location.ln) {
+          Some(ln) => Location { ln, col: 0 },
+          None => char.location,
+        },
+        false => location,
+      };
+    }
+  }
+
+  /// the visible line right above line `ln`.
+  fn line_above(&self, ln: u32) -> Option<u32> {
+    let folds = self.document.folds();
+    let row = fold::row_of(folds, ln).checked_sub(1)?;
+    Some(fold::line_at_row(folds, row))
+  }
+
+  /// the visible line right below line `ln`, skipping folded lines.
+  fn line_below(&self, ln: u32) -> Option<u32> {
+    let folds = self.document.folds();
+    let below = fold::line_at_row(folds, fold::row_of(folds, ln) + 1);
+    ((below as usize) < self.document.line_count()).then_some(below)
+  }
+
+  /// move the cursor to line `ln`, keeping its display column where possible.
+  fn move_to_line(&mut self, ln: u32) {
+    let x = self.position_of(self.cursor_location).x;
+    self.cursor_location = self.location_on_line(ln, x);
+  }
+
+  /// the location on line `ln` closest to display column `x`.
+  fn location_on_line(&self, ln: u32, x: u32) -> Location {
+    let line = self.document.line(ln).unwrap_or_default();
+    let col = line.iter()
+      .find(|c| c.position.x >= x)
+      .or(line.last())
+      .map(|c| if c.position.x >= x { c.location.col } else { c.location.col + 1 })
+      .unwrap_or(0);
+    Location { ln, col }
+  }
+
+  /// the location drawn at `position` inside the viewport.
+  pub fn location_at_visible(&self, position: Position) -> Location {
+    let last_ln = self.document.line_count().saturating_sub(1) as u32;
+    let ln = fold::line_at_row(self.document.folds(), position.y + self.offset_top).min(last_ln);
+    self.location_on_line(ln, position.x + self.offset_left)
+  }
+
+  /// run a single-cursor edit at every cursor as one undo step. cursors are
+  /// visited in document order, each one shifted by what the edits before it
+  /// inserted or removed.
+  fn edit_all(&mut self, op: impl Fn(&mut Self)) {
+    let (offset_top, offset_left) = (self.offset_top, self.offset_left);
+    let primary = self.cursor_location;
+    let marks: Vec<(IndexedCursor, bool)> = self.cursors().iter()
+      .map(|cursor| (self.index_cursor(cursor), cursor.location == primary))
+      .collect();
+    self.document.begin_edit();
+    let mut delta: isize = 0;
+    let mut edited = Vec::with_capacity(marks.len());
+    for (mark, is_primary) in marks {
+      let cursor = self.locate_cursor(&mark, delta);
+      self.cursor_location = cursor.location;
+      self.selection = cursor.selection;
+      let len = self.document.len();
+      op(self);
+      delta += self.document.len() as isize - len as isize;
+      let cursor = Cursor { location: self.cursor_location, selection: self.selection };
+      edited.push((self.index_cursor(&cursor), is_primary));
+    }
+    self.document.end_edit();
+    self.extra_cursors.clear();
+    for (mark, is_primary) in edited {
+      let cursor = self.locate_cursor(&mark, 0);
+      if is_primary {
+        self.cursor_location = cursor.location;
+        self.selection = cursor.selection;
+      } else {
+        self.extra_cursors.push(cursor);
+      }
+    }
+    self.dedup_cursors();
+    self.offset_top = offset_top;
+    self.offset_left = offset_left;
+    self.auto_center_cursor();
+  }
+
+  fn index_cursor(&self, cursor: &Cursor) -> IndexedCursor {
+    let index = |location: Location| self.document.index_of(location);
+    IndexedCursor {
+      index: index(cursor.location),
+      selection: cursor.selection.map(|range| (index(range.start), index(range.end))),
+    }
+  }
+
+  fn locate_cursor(&self, cursor: &IndexedCursor, delta: isize) -> Cursor {
+    let location = |index: usize| self.document.location_at((index as isize + delta).max(0) as usize);
+    Cursor {
+      location: location(cursor.index),
+      selection: cursor.selection.map(|(start, end)| Range::new(location(start), location(end))),
+    }
+  }
+
+  /// run a cursor movement for every cursor. moving drops selections.
+  fn move_all(&mut self, op: impl Fn(&mut Self)) {
+    let primary = self.cursor_location;
+    let mut moved_primary = primary;
+    let mut extra = Vec::with_capacity(self.extra_cursors.len());
+    for cursor in self.cursors() {
+      self.cursor_location = cursor.location;
+      op(self);
+      if cursor.location == primary {
+        moved_primary = self.cursor_location;
+      } else {
+        extra.push(Cursor::new(self.cursor_location));
+      }
+    }
+    self.cursor_location = moved_primary;
+    self.selection = None;
+    self.extra_cursors = extra;
+    self.dedup_cursors();
+    self.auto_center_cursor();
+  }
+
+  fn dedup_cursors(&mut self) {
+    let primary = self.cursor_location;
+    let mut seen = vec![primary];
+    self.extra_cursors.retain(|cursor| {
+      if seen.contains(&cursor.location) {
+        return false;
+      }
+      seen.push(cursor.location);
+      true
+    });
+  }
+
+  /// remove the selected text, leaving the cursor where it started.
+  /// returns false when nothing was selected.
+  fn delete_selection(&mut self) -> bool {
+    match self.selection.take() {
+      Some(range) if range.start != range.end => {
+        if self.document.remove(range).is_ok() {
+          self.cursor_location = range.start;
+        }
+        true
+      },
+      _ => false,
+    }
+  }
+
+  /// add a cursor one line above the topmost cursor.
+  pub fn add_cursor_above(&mut self) {
+    let top = self.cursors()[0].location;
+    if let Some(ln) = self.line_above(top.ln) {
+      let x = self.position_of(top).x;
+      let location = self.location_on_line(ln, x);
+      self.add_cursor(location);
+    }
+  }
+
+  /// add a cursor one line below the bottommost cursor.
+  pub fn add_cursor_below(&mut self) {
+    let bottom = self.cursors().last().unwrap().location;
+    if let Some(ln) = self.line_below(bottom.ln) {
+      let x = self.position_of(bottom).x;
+      let location = self.location_on_line(ln, x);
+      self.add_cursor(location);
+    }
+  }
+
+  /// add a cursor at `location` and make it the primary one.
+  pub fn add_cursor(&mut self, location: Location) {
+    self.reveal(location.ln);
+    self.extra_cursors.push(Cursor { location: self.cursor_location, selection: self.selection });
+    self.cursor_location = location;
+    self.selection = None;
+    self.dedup_cursors();
+    self.auto_center_cursor();
+  }
+
+  /// drop every cursor but the primary one, and its selection.
+  pub fn collapse_cursors(&mut self) {
+    self.extra_cursors.clear();
+    self.selection = None;
+  }
+
+  /// select the word under the cursor, or, with a selection, add a cursor
+  /// selecting the next occurrence of the selected text.
+  pub fn select_next_occurrence(&mut self) {
+    let Some(selection) = self.selection else {
+      if let Some(word) = self.word_at(self.cursor_location) {
+        self.selection = Some(word);
+        self.cursor_location = word.end;
+      }
+      return;
+    };
+    let text = self.document.text_in(selection);
+    let cursors = self.cursors();
+    let selected_starts: Vec<Location> = cursors.iter().filter_map(|c| c.selection).map(|r| r.start).collect();
+    let from = cursors.last().map(|c| c.selection.map(|r| r.end).unwrap_or(c.location)).unwrap();
+    let found = self.document.find(&text, from)
+      .or_else(|| self.document.find(&text, Location { ln: 0, col: 0 }))
+      .filter(|found| !selected_starts.contains(found));
+    if let Some(start) = found {
+      let end = self.document.location_at(self.document.index_of(start) + text.chars().count());
+      self.add_cursor(end);
+      self.selection = Some(Range::new(start, end));
+    }
+  }
+
+  /// the range of the word containing `location`.
+  fn word_at(&self, location: Location) -> Option<Range> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let line = self.document.line(location.ln)?;
+    let col = location.col as usize;
+    let start = line[..col.min(line.len())].iter().rev()
+      .take_while(|c| is_word(c.char))
+      .count();
+    let end = line[col.min(line.len())..].iter()
+      .take_while(|c| is_word(c.char))
+      .count();
+    if start + end == 0 {
+      return None;
+    }
+    Some(Range::new(
+      Location { ln: location.ln, col: (col - start) as u32 },
+      Location { ln: location.ln, col: (col + end) as u32 },
+    ))
+  }
+
+  pub fn undo(&mut self) {
+    if let Some(location) = self.document.undo() {
+      self.collapse_cursors();
+      self.cursor_location = location;
+      self.auto_center_cursor();
+    }
+  }
+
+  pub fn redo(&mut self) {
+    if let Some(location) = self.document.redo() {
+      self.collapse_cursors();
+      self.cursor_location = location;
+      self.auto_center_cursor();
+    }
+  }
+
+  /// the bracket at the cursor, or right before it, and the bracket matching it.
+  pub fn matching_bracket(&self) -> Option<(Location, Location)> {
+    let at_cursor = Some(self.cursor_location);
+    let before_cursor = self.document.before(self.cursor_location)
+      .filter(|c| c.location.ln == self.cursor_location.ln)
+      .map(|c| c.location);
+    [at_cursor, before_cursor].into_iter()
+      .flatten()
+      .find_map(|location| {
+        brackets::find_match(&self.document, location, self.language).map(|found| (location, found))
+      })
+  }
+
+  pub fn jump_to_matching_bracket(&mut self) {
+    if let Some((_, found)) = self.matching_bracket() {
+      self.cursor_location = found;
+      self.auto_center_cursor();
+    }
+  }
+
+  /// replace `len` whole lines starting at line `start` with `text`, as one undo step.
+  pub fn replace_lines(&mut self, start: u32, len: u32, text: &str) {
+    self.collapse_cursors();
+    self.select(Location { ln: start, col: 0 }, Location { ln: start + len, col: 0 });
+    self.input(text);
+    self.set_cursor(Location { ln: start, col: 0 });
+  }
+
+  /// replace each range with its text, all as one undo step. the ranges are
+  /// replaced in the order given, so later ones should come first.
+  pub fn replace_ranges(&mut self, replacements: &[(Range, String)]) {
+    self.collapse_cursors();
+    self.document.begin_edit();
+    for (range, text) in replacements {
+      self.select(range.start, range.end);
+      self.input(text);
+    }
+    self.document.end_edit();
+  }
+
+  pub fn set_diagnostics(&mut self, diagnostics: Vec<Diagnostic>) {
+    self.document.set_diagnostics(diagnostics);
+  }
+
+  /// the diagnostic covering the cursor, or else the first one on its line.
+  pub fn diagnostic_at_cursor(&self) -> Option<&Diagnostic> {
+    let cursor = self.cursor_location;
+    let diagnostics = self.document.diagnostics();
+    diagnostics.iter()
+      .find(|d| d.range.start <= cursor && cursor < d.range.end)
+      .or_else(|| diagnostics.iter().find(|d| d.range.start.ln <= cursor.ln && cursor.ln <= d.range.end.ln))
+  }
+
+  /// move to the start of the next diagnostic, wrapping around at the end.
+  pub fn next_diagnostic(&mut self) {
+    let cursor = self.cursor_location;
+    let diagnostics = self.document.diagnostics();
+    let next = diagnostics.iter().find(|d| d.range.start > cursor).or(diagnostics.first());
+    if let Some(location) = next.map(|d| d.range.start) {
+      self.set_cursor(location);
+    }
+  }
+
+  /// move to the start of the previous diagnostic, wrapping around at the start.
+  pub fn previous_diagnostic(&mut self) {
+    let cursor = self.cursor_location;
+    let diagnostics = self.document.diagnostics();
+    let previous = diagnostics.iter().rev().find(|d| d.range.start < cursor).or(diagnostics.last());
+    if let Some(location) = previous.map(|d| d.range.start) {
+      self.set_cursor(location);
+    }
+  }
+
+  /// where `location` is drawn inside the viewport, if it is visible.
+  pub fn visible_position(&self, location: Location) -> Option<Position> {
+    if self.document.is_hidden(location.ln) {
+      return None;
+    }
+    let width = self.document.character_at(location).map(|c| c.width).unwrap_or(1);
+    let position = self.position_of(location);
+    let x = position.x.checked_sub(self.offset_left)?;
+    let y = position.y.checked_sub(self.offset_top)?;
+    if x + width > self.viewport_size.width || y >= self.viewport_size.height {
+      return None;
+    }
+    Some(Position { x, y })
+  }
+
+  /// move the only cursor to `location`, clamped into the document.
+  pub fn set_cursor(&mut self, location: Location) {
+    let last_ln = self.document.line_count().saturating_sub(1) as u32;
+    let ln = location.ln.min(last_ln);
+    let line_len = self.document.line(ln).map(|line| line.len()).unwrap_or(0) as u32;
+    self.collapse_cursors();
+    self.reveal(ln);
+    self.cursor_location = Location { ln, col: location.col.min(line_len) };
+    self.auto_center_cursor();
+  }
+
+  /// move the cursor to `location` and scroll so it ends up in the middle of
+  /// the viewport, however far away it is.
+  pub fn goto(&mut self, location: Location) {
+    self.set_cursor(location);
+    let position = self.absolute_cursor_position();
+    let width = self.viewport_size.width;
+    self.center_cursor_line();
+    self.offset_left = if position.x < width { 0 } else { scroll::center(position.x, width) };
+  }
+
+  /// scroll so the cursor's line is in the middle of the viewport.
+  pub fn center_cursor_line(&mut self) {
+    let row = self.absolute_cursor_position().y;
+    self.offset_top = scroll::center(row, self.viewport_size.height);
+  }
+
+  /// scroll so the cursor's line is at the top, below the scroll-off margin.
+  pub fn cursor_line_to_top(&mut self) {
+    let row = self.absolute_cursor_position().y;
+    self.offset_top = row.saturating_sub(scroll::margin(self.scroll_off.vertical, self.viewport_size.height));
+  }
+
+  /// scroll so the cursor's line is at the bottom, above the scroll-off margin.
+  pub fn cursor_line_to_bottom(&mut self) {
+    let row = self.absolute_cursor_position().y;
+    let height = self.viewport_size.height;
+    self.offset_top = (row + scroll::margin(self.scroll_off.vertical, height) + 1).saturating_sub(height);
+  }
+
+  pub fn select(&mut self, start: Location, end: Location) {
+    self.reveal(start.ln);
+    self.reveal(end.ln);
+    self.selection = Some(if start <= end { Range::new(start, end) } else { Range::new(end, start) });
+    self.cursor_location = end;
+  }
   
+  /// the lines on screen, top to bottom.
+  pub fn visible_lines(&self) -> Vec<u32> {
+    let line_count = self.document.line_count() as u32;
+    (0..self.viewport_size.height)
+      .map(|row| fold::line_at_row(self.document.folds(), self.offset_top + row))
+      .take_while(|ln| *ln < line_count)
+      .collect()
+  }
+
+  /// close the block at the cursor, or the innermost one around it.
+  pub fn fold(&mut self) {
+    if self.document.is_large() {
+      return;
+    }
+    let ln = self.cursor_location.ln;
+    let Some(new) = fold::range_at(&self.document, ln, self.language)
+      .filter(|new| !self.document.folds().contains(new))
+      .or_else(|| fold::enclosing(&self.document, ln, self.language))
+    else { return };
+    let mut folds = self.document.folds().to_vec();
+    folds.push(new);
+    self.document.set_folds(folds);
+    self.keep_cursors_visible();
+    self.auto_center_cursor();
+  }
+
+  /// open the folds starting on the cursor's line.
+  pub fn unfold(&mut self) {
+    let ln = self.cursor_location.ln;
+    let folds = self.document.folds().iter().filter(|fold| fold.start != ln).copied().collect();
+    self.document.set_folds(folds);
+  }
+
+  /// close or open the fold starting on line `ln`, e.g. from a gutter click.
+  pub fn toggle_fold(&mut self, ln: u32) {
+    if self.is_folded(ln) {
+      self.cursor_location = Location { ln, col: 0 };
+      return self.unfold();
+    }
+    self.set_cursor(Location { ln, col: 0 });
+    if fold::range_at(&self.document, ln, self.language).is_some() {
+      self.fold();
+    }
+  }
+
+  /// close every block in the document.
+  pub fn fold_all(&mut self) {
+    if self.document.is_large() {
+      return;
+    }
+    let folds: Vec<Fold> = (0..self.document.line_count() as u32)
+      .filter_map(|ln| fold::range_at(&self.document, ln, self.language))
+      .collect();
+    self.document.set_folds(folds);
+    self.keep_cursors_visible();
+    self.auto_center_cursor();
+  }
+
+  pub fn unfold_all(&mut self) {
+    self.document.set_folds(Vec::new());
+  }
+
+  /// whether a closed fold starts on line `ln`.
+  pub fn is_folded(&self, ln: u32) -> bool {
+    self.document.folds().iter().any(|fold| fold.start == ln)
+  }
+
+  /// open whatever folds hide line `ln`.
+  fn reveal(&mut self, ln: u32) {
+    if self.document.is_hidden(ln) {
+      let folds = self.document.folds().iter().filter(|fold| !fold.hides(ln)).copied().collect();
+      self.document.set_folds(folds);
+    }
+  }
+
+  /// move cursors that a new fold hid to the end of the line it starts on.
+  fn keep_cursors_visible(&mut self) {
+    let folds = self.document.folds().to_vec();
+    let visible = |location: Location| match self.document.is_hidden(location.ln) {
+      true => self.line_end(fold::line_at_row(&folds, fold::row_of(&folds, location.ln))),
+      false => location,
+    };
+    let primary = visible(self.cursor_location);
+    let extra: Vec<Cursor> = self.extra_cursors.iter().map(|cursor| Cursor::new(visible(cursor.location))).collect();
+    if primary != self.cursor_location {
+      self.selection = None;
+    }
+    self.cursor_location = primary;
+    self.extra_cursors = extra;
+    self.dedup_cursors();
+  }
+
+  /// the first visible line and column.
+  pub fn offsets(&self) -> (u32, u32) {
+    (self.offset_top, self.offset_left)
+  }
+
+  pub fn set_offsets(&mut self, offset_top: u32, offset_left: u32) {
+    self.offset_top = offset_top;
+    self.offset_left = offset_left;
+  }
+
+  /// rows the document takes on screen, a closed fold counting as one.
+  pub fn row_count(&self) -> u32 {
+    fold::row_of(self.document.folds(), self.document.line_count().saturating_sub(1) as u32) + 1
+  }
+
+  /// scroll so visual row `row` is at the top. the cursor moves along when it
+  /// would end up outside the scroll-off margins.
+  pub fn scroll_to(&mut self, row: u32) {
+    let height = self.viewport_size.height;
+    let margin = scroll::margin(self.scroll_off.vertical, height);
+    self.offset_top = row.min((self.row_count() + margin).saturating_sub(height));
+    let top = if self.offset_top == 0 { 0 } else { self.offset_top + margin };
+    let bottom = (self.offset_top + height).saturating_sub(margin + 1).max(top);
+    let position = self.absolute_cursor_position();
+    let cursor_row = position.y.clamp(top, bottom);
+    if cursor_row != position.y {
+      let ln = fold::line_at_row(self.document.folds(), cursor_row);
+      let location = self.location_on_line(ln, position.x);
+      self.set_cursor(location);
+    }
+  }
+
+  /// move the viewport a line down, leaving the cursor where it is.
   pub fn scroll_up(&mut self) {
-    self.offset_top += 1;
+    self.offset_top = (self.offset_top + 1).min(self.row_count().saturating_sub(1));
+    self.keep_scroll();
   }
 
+  /// move the viewport a line up, leaving the cursor where it is.
   pub fn scroll_down(&mut self) {
     self.offset_top = self.offset_top.saturating_sub(1);
+    self.keep_scroll();
   }
 
+  /// move the viewport a column right, as far as the widest line on screen.
   pub fn scroll_left(&mut self) {
-    self.offset_left += 1;
+    let widest = self.visible_lines().into_iter()
+      .filter_map(|ln| self.document.line(ln)?.last().map(|c| c.position.x + c.width))
+      .max()
+      .unwrap_or(0);
+    if self.offset_left + 1 < widest {
+      self.offset_left += 1;
+    }
+    self.keep_scroll();
   }
 
+  /// move the viewport a column left.
   pub fn scroll_right(&mut self) {
     self.offset_left = self.offset_left.saturating_sub(1);
+    self.keep_scroll();
+  }
+
+  fn keep_scroll(&mut self) {
+    self.scrolled = Some((self.cursor_location, self.document.version()));
   }
 
   pub fn set_size(&mut self, width: u32, height: u32) {
+    let resized = (self.viewport_size.width, self.viewport_size.height) != (width, height);
     self.viewport_size = ViewportSize { width, height };
+    if !resized && self.scrolled == Some((self.cursor_location, self.document.version())) {
+      return;
+    }
+    self.scrolled = None;
     self.auto_center_cursor();
   }
 
@@ -124,12 +1150,25 @@ impl Context {
   }
 
   fn absolute_cursor_position(&self) -> Position {
-    match self.document.get_character(self.cursor_location) {
-      Some(c) => c.position.clone(),
+    self.position_of(self.cursor_location)
+  }
+
+  /// where `location` is drawn in the whole document, past-the-end included.
+  fn position_of(&self, location: Location) -> Position {
+    let mut position = self.document_position_of(location);
+    position.y = fold::row_of(self.document.folds(), position.y);
+    position
+  }
+
+  /// like `position_of`, ignoring folds.
+  fn document_position_of(&self, location: Location) -> Position {
+    match self.document.character_at(location) {
+      Some(c) => c.position,
       None => {
         match self.document.last_character() {
+          Some(lc) if lc.char == '\n' => Position { x: 0, y: lc.position.y + 1 },
           Some(lc) => {
-            let mut pos = lc.position.clone();
+            let mut pos = lc.position;
             pos.x += lc.width;
             pos
           },
@@ -139,23 +1178,45 @@ impl Context {
     }
   }
 
+  /// scroll just enough to keep the cursor inside the scroll-off margins,
+  /// however far it moved.
   fn auto_center_cursor(&mut self) {
-    let cursor_pos = self.cursor_position();
-    let abs_pos = self.absolute_cursor_position();
-    if cursor_pos.x > self.viewport_size.width {
-      self.offset_left += self.viewport_size.width / 2;
-    }
-    if cursor_pos.y > self.viewport_size.height - 1 {
-      self.offset_top += 1;
-    }
-    if abs_pos.x < self.offset_left {
-      self.offset_left = self.offset_left.saturating_sub(self.viewport_size.width / 2);
-    }
-    if abs_pos.y < self.offset_top {
-      self.offset_top = self.offset_top.saturating_sub(1);
+    let position = self.absolute_cursor_position();
+    let ViewportSize { width, height } = self.viewport_size;
+    let ScrollOff { vertical, horizontal, horizontal_scroll } = self.scroll_off;
+    self.offset_top = scroll::follow(self.offset_top, position.y, height, vertical);
+    self.offset_left = match horizontal_scroll {
+      HorizontalScroll::Smooth => scroll::follow(self.offset_left, position.x, width, horizontal),
+      HorizontalScroll::Page => scroll::page(self.offset_left, position.x, width, horizontal),
+    };
+  }
+
+}
+
+/// what kind of characters make up a word, for word-wise deletion.
+#[derive(Clone, Copy, PartialEq)]
+enum CharClass {
+  Word,
+  Space,
+  Punctuation,
+}
+
+impl CharClass {
+  fn of(char: char) -> Self {
+    if char.is_alphanumeric() || char == '_' {
+      Self::Word
+    } else if char.is_whitespace() {
+      Self::Space
+    } else {
+      Self::Punctuation
     }
   }
+}
 
+/// a cursor addressed by character index, which survives edits elsewhere.
+struct IndexedCursor {
+  index: usize,
+  selection: Option<(usize, usize)>,
 }
 
 struct ViewportSize {
@@ -180,7 +1241,8 @@ mod tests {
         }
         editor.input("\n");
       }
-      for _ in 0..3 {
+      // the last newline already pushed the viewport down by one line
+      for _ in 0..2 {
         editor.scroll_up();
       }
       for _ in 0..4 {
@@ -189,4 +1251,319 @@ mod tests {
       let s = &editor.visual_area()[0];
       assert_eq!(s, "<33333");
     }
+
+    #[test]
+    fn test_scroll_by_hand() {
+      let mut editor = Context::with_size(9, 3);
+      editor.input("0\n1\n2\n3\n4\n5");
+      editor.set_cursor(Location { ln: 0, col: 0 });
+      editor.scroll_up();
+      editor.scroll_up();
+      // redrawing keeps the viewport where it was scrolled to
+      editor.set_size(9, 3);
+      assert_eq!(editor.visible_lines(), vec![2, 3, 4]);
+      // until the cursor moves
+      editor.right();
+      editor.set_size(9, 3);
+      assert_eq!(editor.visible_lines(), vec![0, 1, 2]);
+    }
+
+    fn text(editor: Context) -> String {
+      editor.document.into()
+    }
+
+    #[test]
+    fn test_smart_enter() {
+      let mut editor = Context::with_size(20, 20);
+      editor.language = Language::Rust;
+      editor.indent_unit = "  ".to_string();
+      editor.input("if a {");
+      editor.newline();
+      editor.input("b");
+      editor.newline();
+      editor.insert_char('}');
+      assert_eq!(text(editor), "if a {\n  b\n}");
+    }
+
+    #[test]
+    fn test_smart_enter_between_pair() {
+      let mut editor = Context::with_size(20, 20);
+      editor.language = Language::Rust;
+      editor.indent_unit = "  ".to_string();
+      editor.input("  f()");
+      editor.cursor_location = Location { ln: 0, col: 4 };
+      editor.newline();
+      assert_eq!(editor.cursor_location, Location { ln: 1, col: 4 });
+      editor.input("x");
+      assert_eq!(text(editor), "  f(\n    x\n  )");
+    }
+
+    #[test]
+    fn test_reindent_selection() {
+      let mut editor = Context::with_document(Document::from("a {\nb {\n      c\n}\n   }"), 20, 20);
+      editor.language = Language::Rust;
+      editor.indent_unit = "  ".to_string();
+      editor.select(Location { ln: 0, col: 0 }, Location { ln: 4, col: 0 });
+      editor.reindent_selection();
+      assert_eq!(text(editor), "a {\n  b {\n    c\n  }\n}");
+    }
+
+    #[test]
+    fn test_auto_pairs() {
+      let mut editor = Context::with_size(20, 20);
+      editor.language = Language::Rust;
+      editor.insert_char('f');
+      editor.insert_char('(');
+      editor.insert_char('"');
+      editor.insert_char('x');
+      editor.insert_char('"');
+      editor.insert_char(')');
+      editor.insert_char('[');
+      editor.backspace();
+      assert_eq!(editor.cursor_location, Location { ln: 0, col: 6 });
+      assert_eq!(text(editor), "f(\"x\")");
+    }
+
+    #[test]
+    fn test_jump_to_matching_bracket() {
+      let mut editor = Context::with_document(Document::from("a(b[c]\n)"), 20, 20);
+      editor.cursor_location = Location { ln: 0, col: 1 };
+      editor.jump_to_matching_bracket();
+      assert_eq!(editor.cursor_location, Location { ln: 1, col: 0 });
+      editor.cursor_location = Location { ln: 0, col: 6 };
+      assert_eq!(editor.matching_bracket(), Some((Location { ln: 0, col: 5 }, Location { ln: 0, col: 3 })));
+    }
+
+    #[test]
+    fn test_up_down() {
+      let mut editor = Context::with_document(Document::from("汉字ab\nabcdef\nx"), 20, 20);
+      editor.cursor_location = Location { ln: 1, col: 4 };
+      editor.up();
+      assert_eq!(editor.cursor_location, Location { ln: 0, col: 2 });
+      editor.down();
+      editor.down();
+      assert_eq!(editor.cursor_location, Location { ln: 2, col: 1 });
+    }
+
+    #[test]
+    fn test_multiple_cursors() {
+      let mut editor = Context::with_document(Document::from("ab\ncd\nef"), 20, 20);
+      editor.cursor_location = Location { ln: 0, col: 1 };
+      editor.add_cursor_below();
+      editor.add_cursor_below();
+      editor.input("X\n");
+      assert_eq!(editor.cursors().len(), 3);
+      editor.backspace();
+      editor.left();
+      editor.backspace();
+      assert_eq!(editor.cursor_location, Location { ln: 2, col: 0 });
+      editor.undo();
+      assert_eq!(text(editor), "aXb\ncXd\neXf");
+      // pairs close at every cursor, each stepping back only its own cursor
+      let mut editor = Context::with_document(Document::from("a \nb "), 20, 20);
+      editor.cursor_location = Location { ln: 0, col: 1 };
+      editor.add_cursor_below();
+      editor.insert_char('(');
+      assert_eq!(editor.document().text(), "a() \nb() ");
+      let locations: Vec<_> = editor.cursors().iter().map(|cursor| cursor.location).collect();
+      assert_eq!(locations, vec![Location { ln: 0, col: 2 }, Location { ln: 1, col: 2 }]);
+      editor.right();
+      assert_eq!(editor.cursor_location, Location { ln: 1, col: 3 });
+    }
+
+    #[test]
+    fn test_select_next_occurrence() {
+      let mut editor = Context::with_document(Document::from("foo bar\nfoo foobar"), 20, 20);
+      editor.cursor_location = Location { ln: 0, col: 1 };
+      editor.select_next_occurrence();
+      editor.select_next_occurrence();
+      editor.select_next_occurrence();
+      editor.select_next_occurrence();
+      assert_eq!(editor.cursors().len(), 3);
+      editor.input("baz");
+      assert_eq!(editor.cursors().len(), 3);
+      editor.undo();
+      editor.redo();
+      assert_eq!(text(editor), "baz bar\nbaz bazbar");
+    }
+
+    #[test]
+    fn test_delete() {
+      let mut editor = Context::with_document(Document::from("ab\ncd"), 20, 20);
+      editor.cursor_location = Location { ln: 0, col: 1 };
+      editor.delete();
+      editor.delete();
+      assert_eq!(editor.cursor_location, Location { ln: 0, col: 1 });
+      editor.cursor_location = Location { ln: 0, col: 3 };
+      editor.delete();
+      assert_eq!(text(editor), "acd");
+    }
+
+    #[test]
+    fn test_delete_words() {
+      let mut editor = Context::with_document(Document::from("let foo.bar  = 1;"), 30, 20);
+      editor.cursor_location = Location { ln: 0, col: 13 };
+      editor.delete_word_backward();
+      assert_eq!(editor.cursor_location, Location { ln: 0, col: 8 });
+      editor.delete_word_backward();
+      editor.delete_word_forward();
+      assert_eq!(text(editor), "let foo 1;");
+    }
+
+    #[test]
+    fn test_delete_line_and_kill() {
+      let mut editor = Context::with_document(Document::from("one\ntwo\nthree"), 20, 20);
+      editor.cursor_location = Location { ln: 2, col: 4 };
+      editor.delete_line();
+      assert_eq!(editor.cursor_location, Location { ln: 1, col: 3 });
+      editor.cursor_location = Location { ln: 0, col: 1 };
+      editor.kill_to_end_of_line();
+      editor.kill_to_end_of_line();
+      assert_eq!(text(editor), "otwo");
+    }
+
+    #[test]
+    fn test_delete_line_and_kill_crlf() {
+      let mut editor = Context::with_document(Document::from("one\r\ntwo\r\nthree"), 20, 20);
+      editor.cursor_location = Location { ln: 0, col: 3 };
+      editor.kill_to_end_of_line();
+      assert_eq!(editor.document().text(), "onetwo\r\nthree");
+      editor.cursor_location = Location { ln: 1, col: 2 };
+      editor.delete_line();
+      assert_eq!(editor.document().text(), "onetwo");
+      editor.cursor_location = Location { ln: 0, col: 3 };
+      editor.kill_to_end_of_line();
+      assert_eq!(text(editor), "one");
+    }
+
+    #[test]
+    fn test_diagnostics() {
+      use crate::editor::diagnostic::Severity;
+      let mut ctx = Context::with_document(Document::from("a\nb\nc"), 10, 10);
+      let diagnostic = |ln: u32| Diagnostic {
+        range: Range::new(Location { ln, col: 0 }, Location { ln, col: 1 }),
+        severity: Severity::Error,
+        message: format!("line {}", ln),
+        source: None,
+      };
+      ctx.set_diagnostics(vec![diagnostic(2), diagnostic(1)]);
+      ctx.next_diagnostic();
+      assert_eq!(ctx.cursor_location(), Location { ln: 1, col: 0 });
+      assert_eq!(ctx.diagnostic_at_cursor().unwrap().message, "line 1");
+      ctx.next_diagnostic();
+      ctx.next_diagnostic();
+      assert_eq!(ctx.cursor_location(), Location { ln: 1, col: 0 });
+      ctx.previous_diagnostic();
+      assert_eq!(ctx.cursor_location(), Location { ln: 2, col: 0 });
+      ctx.set_cursor(Location { ln: 0, col: 0 });
+      assert!(ctx.diagnostic_at_cursor().is_none());
+    }
+
+    #[test]
+    fn test_replace_lines() {
+      let mut ctx = Context::with_document(Document::from("a\nb\nc"), 10, 10);
+      ctx.replace_lines(1, 1, "x\ny\n");
+      assert_eq!(ctx.document().text(), "a\nx\ny\nc");
+      ctx.replace_lines(3, 1, "");
+      assert_eq!(ctx.document().text(), "a\nx\ny\n");
+      ctx.replace_lines(1, 0, "b\n");
+      assert_eq!(ctx.document().text(), "a\nb\nx\ny\n");
+      ctx.undo();
+      assert_eq!(ctx.document().text(), "a\nx\ny\n");
+      let range = |ln, col| Range::new(Location { ln, col }, Location { ln, col: col + 1 });
+      ctx.replace_ranges(&[(range(2, 0), "yy".to_string()), (range(0, 0), "aa".to_string())]);
+      assert_eq!(ctx.document().text(), "aa\nx\nyy\n");
+      ctx.undo();
+      assert_eq!(ctx.document().text(), "a\nx\ny\n");
+    }
+
+    #[test]
+    fn test_reload() {
+      let mut ctx = Context::with_document(Document::from("one\ntwo\nthree"), 10, 10);
+      ctx.set_cursor(Location { ln: 2, col: 4 });
+      ctx.reload("one\n2\nthree\n");
+      assert_eq!(ctx.document().text(), "one\n2\nthree\n");
+      assert_eq!(ctx.cursor_location(), Location { ln: 2, col: 4 });
+      assert!(!ctx.document().is_dirty());
+      ctx.reload("1\n");
+      assert_eq!(ctx.cursor_location(), Location { ln: 1, col: 0 });
+    }
+
+    #[test]
+    fn test_goto() {
+      let text: String = (0..100).map(|ln| format!("line {}\n", ln)).collect();
+      let mut ctx = Context::with_document(Document::from(text.as_str()), 20, 10);
+      ctx.goto(Location { ln: 50, col: 2 });
+      assert_eq!(ctx.cursor_location(), Location { ln: 50, col: 2 });
+      assert_eq!(ctx.offsets(), (45, 0));
+      assert_eq!(ctx.cursor_position(), Position { x: 2, y: 5 });
+      ctx.goto(Location { ln: 1, col: 0 });
+      assert_eq!(ctx.offsets(), (0, 0));
+    }
+
+    #[test]
+    fn test_folds() {
+      let doc = Document::from("x\nfn a() {\n  b();\n  c();\n}\nd");
+      let mut ctx = Context::with_document(doc, 20, 10);
+      ctx.set_cursor(Location { ln: 2, col: 2 });
+      ctx.fold();
+      assert_eq!(ctx.cursor_location(), Location { ln: 1, col: 8 });
+      assert_eq!(ctx.visual_area(), vec!["x", "fn a() {", "}", "d"]);
+      assert_eq!(ctx.visible_lines(), vec![0, 1, 4, 5]);
+      ctx.down();
+      assert_eq!(ctx.cursor_position(), Position { x: 1, y: 2 });
+      ctx.left();
+      assert_eq!(ctx.cursor_location(), Location { ln: 4, col: 0 });
+      ctx.left();
+      assert_eq!(ctx.cursor_location(), Location { ln: 1, col: 8 });
+      ctx.right();
+      assert_eq!(ctx.cursor_location(), Location { ln: 4, col: 0 });
+      assert_eq!(ctx.location_at_visible(Position { x: 0, y: 3 }), Location { ln: 5, col: 0 });
+      // edits above the fold move it along, going inside opens it
+      ctx.set_cursor(Location { ln: 0, col: 0 });
+      ctx.newline();
+      assert!(ctx.is_folded(2));
+      ctx.set_cursor(Location { ln: 4, col: 0 });
+      assert!(!ctx.is_folded(2));
+      ctx.fold_all();
+      assert_eq!(ctx.visible_lines(), vec![0, 1, 2, 5, 6]);
+      assert_eq!(ctx.cursor_location(), Location { ln: 2, col: 8 });
+      ctx.unfold_all();
+      assert_eq!(ctx.visible_lines().len(), 7);
+    }
+
+    #[test]
+    fn test_scroll() {
+      let doc = Document::from("x\n".repeat(100).as_str());
+      let mut ctx = Context::with_document(doc, 20, 10);
+      ctx.set_scroll_off(ScrollOff { vertical: 2, ..ScrollOff::default() });
+      ctx.set_cursor(Location { ln: 8, col: 0 });
+      assert_eq!(ctx.offsets(), (1, 0));
+      // a long paste keeps the cursor on screen
+      ctx.input(&"y\n".repeat(30));
+      assert_eq!(ctx.offsets(), (33, 0));
+      ctx.cursor_line_to_top();
+      assert_eq!(ctx.offsets(), (36, 0));
+      ctx.cursor_line_to_bottom();
+      assert_eq!(ctx.offsets(), (31, 0));
+      ctx.center_cursor_line();
+      assert_eq!(ctx.offsets(), (33, 0));
+      let doc = Document::from("z".repeat(50).as_str());
+      let mut ctx = Context::with_document(doc, 20, 10);
+      ctx.set_cursor(Location { ln: 0, col: 25 });
+      assert_eq!(ctx.offsets(), (0, 6));
+      ctx.set_scroll_off(ScrollOff { horizontal_scroll: HorizontalScroll::Page, ..ScrollOff::default() });
+      ctx.set_cursor(Location { ln: 0, col: 30 });
+      assert_eq!(ctx.offsets(), (0, 20));
+      // dragging the scrollbar takes the cursor along
+      let doc = Document::from("x\n".repeat(100).as_str());
+      let mut ctx = Context::with_document(doc, 20, 10);
+      ctx.set_scroll_off(ScrollOff { vertical: 2, ..ScrollOff::default() });
+      ctx.scroll_to(50);
+      assert_eq!((ctx.offsets(), ctx.cursor_location().ln), ((50, 0), 52));
+      ctx.scroll_to(500);
+      assert_eq!((ctx.offsets(), ctx.cursor_location().ln), ((93, 0), 95));
+      ctx.scroll_to(0);
+      assert_eq!((ctx.offsets(), ctx.cursor_location().ln), ((0, 0), 7));
+    }
 }
@@ -0,0 +1,14 @@
+use super::{location::Location, range::Range};
+
+/// one of possibly many cursors, with the text it has selected.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cursor {
+  pub location: Location,
+  pub selection: Option<Range>,
+}
+
+impl Cursor {
+  pub fn new(location: Location) -> Self {
+    Self { location, selection: None }
+  }
+}
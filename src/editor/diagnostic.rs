@@ -0,0 +1,31 @@
+use super::range::Range;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+  Error,
+  Warning,
+  Information,
+  Hint,
+}
+
+/// a problem reported for part of a document, e.g. by a language server.
+/// the document keeps `range` in step with later edits.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+  pub range: Range,
+  pub severity: Severity,
+  pub message: String,
+  pub source: Option<String>,
+}
+
+/// where char `index` ends up after `remove` chars at `at` were replaced by
+/// `insert` chars. indices inside the replaced text move to its start.
+pub fn shift_index(index: usize, at: usize, remove: usize, insert: usize) -> usize {
+  if index >= at + remove {
+    index + insert - remove
+  } else if index > at {
+    at
+  } else {
+    index
+  }
+}
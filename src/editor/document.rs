@@ -1,112 +1,544 @@
-use anyhow::{Result, Ok};
-use super::location::{Location, self};
+use std::path::{Path, PathBuf};
+use anyhow::{Result, Ok, bail, Context};
+use super::history::{Edit, History};
+use super::large_file::LargeFile;
+use super::location::Location;
 use super::position::Position;
 use super::range::Range;
 use crate::editor::measure::char_width;
 use crate::macros::log;
 use super::character::Character;
+use super::diagnostic::{self, Diagnostic};
+use super::fold::{self, Fold};
+use super::whitespace::{self, SaveActions};
 
-#[derive(Default, Debug)]
+/// a change to the text, queued for whoever syncs the document elsewhere
+/// (like a language server). `start_utf16` is the start column counted in
+/// UTF-16 code units, taken before the change was applied.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+  pub start: Location,
+  pub start_utf16: u32,
+  pub removed: String,
+  pub inserted: String,
+}
+
+/// files bigger than this are opened in large-file mode.
+pub const LARGE_FILE_THRESHOLD: u64 = 32 * 1024 * 1024;
+
+/// read the file at `path` the way a `Document` holds it: without the UTF-8
+/// byte order mark, if any. also says whether there was one.
+pub fn read_text(path: &Path) -> Result<(String, bool)> {
+  let content = std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+  match content.strip_prefix('\u{feff}') {
+    Some(text) => Ok((text.to_string(), true)),
+    None => Ok((content, false)),
+  }
+}
+
+#[derive(Debug)]
 pub struct Document {
   raw: Vec<char>,
-  parsed: Vec<Character>
+  parsed: Vec<Character>,
+  /// index into `parsed` where each line starts.
+  line_starts: Vec<usize>,
+  large: Option<LargeFile>,
+  path: Option<PathBuf>,
+  history: History,
+  /// `None` unless change tracking was turned on.
+  changes: Option<Vec<Change>>,
+  /// sorted by start.
+  diagnostics: Vec<Diagnostic>,
+  /// closed folds, sorted by start. nested ones may overlap.
+  folds: Vec<Fold>,
+  /// bumped on every edit, so others can tell whether the text changed.
+  version: u64,
+  /// the version that was last read from or written to `path`.
+  saved_version: u64,
+  /// what Enter and the on-save actions insert, "\n" or "\r\n".
+  line_ending: &'static str,
+  /// columns between tab stops. `None` draws a tab as one column.
+  tab_width: Option<u32>,
+  /// whether the file starts with a UTF-8 byte order mark, kept out of the text.
+  bom: bool,
 }
 
 impl Document {
+  /// open the file at `path`. files above `LARGE_FILE_THRESHOLD` are memory-mapped
+  /// and opened read-only, see `LargeFile`.
+  pub fn open(path: &Path) -> Result<Self> {
+    let metadata = std::fs::metadata(path).with_context(|| format!("failed to read {}", path.display()))?;
+    if metadata.len() > LARGE_FILE_THRESHOLD {
+      log!("{} is {} bytes, opening in large-file mode", path.display(), metadata.len());
+      return Ok(Self {
+        large: Some(LargeFile::open(path)?),
+        path: Some(path.to_path_buf()),
+        ..Self::default()
+      });
+    }
+    let (text, bom) = read_text(path)?;
+    let mut doc = Self::from(text.as_str());
+    doc.path = Some(path.to_path_buf());
+    doc.bom = bom;
+    Ok(doc)
+  }
+
+  pub fn path(&self) -> Option<&Path> {
+    self.path.as_deref()
+  }
+
+  /// whether the document is backed by a memory-mapped large file.
+  /// expensive features (highlighting, full reparse) should check this and stay off.
+  pub fn is_large(&self) -> bool {
+    self.large.is_some()
+  }
+
   pub fn insert(&mut self, location: Location, str: &str) -> Result<Vec<&Character>> {
+    if self.is_large() {
+      bail!("large files are read-only");
+    }
     let len = str.chars().count();
-    let start_index: usize;
-    match self.get_character(location) {
-      Some(pchar) => {
-        let index = pchar.index;
-        start_index = index;
-        self.raw.splice(index..index, str.chars());
-      },
-      None => {
-        start_index = self.raw.len();
-        self.raw.extend(str.chars());
-      },
+    let start_index = self.index_of(location);
+    if len > 0 {
+      self.splice(start_index, 0, str);
+      self.history.record(Edit::Insert { index: start_index, text: str.to_string() });
     }
-    self.parse();
     Ok(self.parsed.iter().skip(start_index).take(len).collect())
   }
 
   pub fn remove(&mut self, range: Range) -> Result<()> {
-    let mut offset = 0;
-    for pchar in self.parsed.iter() {
-      let location = pchar.location;
-      if location >= range.start && location < range.end && pchar.index < self.raw.len() {
-        self.raw.remove(pchar.index - offset);
-        offset += 1;
+    if self.is_large() {
+      bail!("large files are read-only");
+    }
+    let removed: Vec<usize> = self.parsed.iter()
+      .filter(|pchar| pchar.location >= range.start && pchar.location < range.end)
+      .map(|pchar| pchar.index)
+      .collect();
+    if let (Some(first), Some(last)) = (removed.first(), removed.last()) {
+      let text = self.splice(*first, last - first + 1, "");
+      self.history.record(Edit::Remove { index: *first, text });
+    }
+    Ok(())
+  }
+
+  /// replace `remove` characters at `index` with `insert`, returning what was removed.
+  fn splice(&mut self, index: usize, remove: usize, insert: &str) -> String {
+    if self.changes.is_some() {
+      let start = self.location_at(index);
+      let change = Change {
+        start,
+        start_utf16: self.utf16_column(start),
+        removed: self.raw[index..index + remove].iter().collect(),
+        inserted: insert.to_string(),
+      };
+      if let Some(changes) = self.changes.as_mut() {
+        changes.push(change);
       }
     }
+    let diagnostics: Vec<(usize, usize)> = self.diagnostics.iter()
+      .map(|d| (self.index_of(d.range.start), self.index_of(d.range.end)))
+      .collect();
+    let first = self.location_at(index).ln;
+    let last = if remove > 0 { self.location_at(index + remove - 1).ln } else { first };
+    let line_count = self.line_count() as i64;
+    let removed = self.raw.splice(index..index + remove, insert.chars()).collect();
     self.parse();
+    let delta = self.line_count() as i64 - line_count;
+    fold::shift(&mut self.folds, first, last, delta);
+    self.version += 1;
+    let inserted = insert.chars().count();
+    for (i, (start, end)) in diagnostics.into_iter().enumerate() {
+      let start = diagnostic::shift_index(start, index, remove, inserted);
+      let end = diagnostic::shift_index(end, index, remove, inserted);
+      self.diagnostics[i].range = Range::new(self.location_at(start), self.location_at(end));
+    }
+    removed
+  }
+
+  pub fn version(&self) -> u64 {
+    self.version
+  }
+
+  /// whether there are edits that are not written to `path` yet.
+  pub fn is_dirty(&self) -> bool {
+    self.version != self.saved_version
+  }
+
+  /// treat the current text as what is on disk, e.g. after reloading it.
+  pub fn mark_saved(&mut self) {
+    self.saved_version = self.version;
+  }
+
+  /// write the text back to `path`.
+  pub fn save(&mut self) -> Result<()> {
+    if self.is_large() {
+      bail!("large files are read-only");
+    }
+    let Some(path) = self.path.as_ref() else { bail!("the document has no file name") };
+    std::fs::write(path, self.contents()).with_context(|| format!("failed to write {}", path.display()))?;
+    self.saved_version = self.version;
     Ok(())
   }
 
+  /// the text as written to disk.
+  fn contents(&self) -> String {
+    match self.bom {
+      true => format!("\u{feff}{}", self.text()),
+      false => self.text(),
+    }
+  }
+
+  pub fn line_ending(&self) -> &'static str {
+    self.line_ending
+  }
+
+  pub fn set_line_ending(&mut self, line_ending: &'static str) {
+    self.line_ending = line_ending;
+  }
+
+  pub fn set_tab_width(&mut self, tab_width: Option<u32>) {
+    if self.tab_width != tab_width {
+      self.tab_width = tab_width;
+      self.parse();
+    }
+  }
+
+  pub fn set_bom(&mut self, bom: bool) {
+    self.bom = bom;
+  }
+
+  /// clean up whitespace as `actions` say, as a single undo step. returns
+  /// whether anything changed.
+  pub fn apply_save_actions(&mut self, actions: SaveActions) -> bool {
+    if self.is_large() || !actions.any() {
+      return false;
+    }
+    let version = self.version;
+    self.begin_edit();
+    if actions.trim_trailing_whitespace {
+      for ln in (0..self.line_count() as u32).rev() {
+        let line = self.line(ln).unwrap_or_default();
+        let len = whitespace::trailing_len(&line);
+        if len > 0 {
+          let start = line[line.len() - len].location;
+          let end = Location { ln, col: line[line.len() - 1].location.col + 1 };
+          let _ = self.remove(Range::new(start, end));
+        }
+      }
+    }
+    let content_end = self.raw.iter().rposition(|c| !c.is_whitespace()).map_or(0, |i| i + 1);
+    if actions.trim_final_newlines {
+      // keep the line break right after the last text, "\r\n" included
+      if let Some(newline) = self.raw[content_end..].iter().position(|c| *c == '\n') {
+        let keep = content_end + newline + 1;
+        if keep < self.raw.len() {
+          let _ = self.remove(Range::new(self.location_at(keep), self.end_location()));
+        }
+      }
+    }
+    if actions.insert_final_newline && self.raw.last().is_some_and(|c| *c != '\n') {
+      let _ = self.insert(self.end_location(), self.line_ending);
+    }
+    self.end_edit();
+    self.version != version
+  }
+
+  /// write the text to `path` and keep saving there from now on.
+  pub fn save_as(&mut self, path: &Path) -> Result<()> {
+    if self.is_large() {
+      bail!("large files are read-only");
+    }
+    std::fs::write(path, self.contents()).with_context(|| format!("failed to write {}", path.display()))?;
+    self.path = Some(path.to_path_buf());
+    self.saved_version = self.version;
+    Ok(())
+  }
+
+  pub fn diagnostics(&self) -> &[Diagnostic] {
+    &self.diagnostics
+  }
+
+  /// replace the diagnostics, e.g. when a language server publishes new ones.
+  pub fn set_diagnostics(&mut self, mut diagnostics: Vec<Diagnostic>) {
+    diagnostics.sort_by(|a, b| a.range.start.partial_cmp(&b.range.start).unwrap());
+    self.diagnostics = diagnostics;
+  }
+
+  pub fn folds(&self) -> &[Fold] {
+    &self.folds
+  }
+
+  pub fn set_folds(&mut self, mut folds: Vec<Fold>) {
+    folds.sort_by_key(|fold| (fold.start, fold.end));
+    folds.dedup();
+    self.folds = folds;
+  }
+
+  /// whether a closed fold hides line `ln`.
+  pub fn is_hidden(&self, ln: u32) -> bool {
+    self.folds.iter().any(|fold| fold.hides(ln))
+  }
+
+  /// start queueing `Change`s for `take_changes`.
+  pub fn track_changes(&mut self) {
+    self.changes.get_or_insert_with(Vec::new);
+  }
+
+  /// the changes made since the last call.
+  pub fn take_changes(&mut self) -> Vec<Change> {
+    self.changes.as_mut().map(std::mem::take).unwrap_or_default()
+  }
+
+  /// column of `location` counted in UTF-16 code units.
+  pub fn utf16_column(&self, location: Location) -> u32 {
+    let Some(line) = self.line_slice(location.ln) else { return 0 };
+    line.iter()
+      .take(location.col as usize)
+      .map(|c| c.char.len_utf16() as u32)
+      .sum()
+  }
+
+  /// the character column of a UTF-16 column on line `ln`.
+  pub fn column_from_utf16(&self, ln: u32, utf16: u32) -> u32 {
+    let Some(line) = self.line_slice(ln) else { return 0 };
+    let mut units = 0;
+    let mut col = 0;
+    for char in line.iter().filter(|c| c.char != '\n') {
+      if units >= utf16 {
+        break;
+      }
+      units += char.char.len_utf16() as u32;
+      col += 1;
+    }
+    col
+  }
+
+  /// the whole text. empty in large-file mode.
+  pub fn text(&self) -> String {
+    self.raw.iter().collect()
+  }
+
+  /// number of characters. in large-file mode the size of the file in bytes,
+  /// since counting its characters would mean reading all of it.
+  pub fn len(&self) -> usize {
+    match &self.large {
+      Some(large) => large.len(),
+      None => self.raw.len(),
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// character index of `location`. locations past the end map to the end.
+  pub fn index_of(&self, location: Location) -> usize {
+    match self.get_character(location) {
+      Some(char) => char.index,
+      None => self.raw.len(),
+    }
+  }
+
+  /// location of the character at `index`, or the end location past the end.
+  pub fn location_at(&self, index: usize) -> Location {
+    match self.parsed.get(index) {
+      Some(char) => char.location,
+      None => self.end_location(),
+    }
+  }
+
+  /// the text between two locations.
+  pub fn text_in(&self, range: Range) -> String {
+    let start = self.index_of(range.start);
+    let end = self.index_of(range.end).max(start);
+    self.raw[start..end].iter().collect()
+  }
+
+  /// start grouping edits into one undo step, until the matching `end_edit`.
+  pub fn begin_edit(&mut self) {
+    self.history.begin();
+  }
+
+  pub fn end_edit(&mut self) {
+    self.history.end();
+  }
+
+  /// revert the last undo step. returns where the change happened.
+  pub fn undo(&mut self) -> Option<Location> {
+    let edits = self.history.undo()?;
+    Some(self.apply(edits))
+  }
+
+  /// reapply the last undone step. returns where the change happened.
+  pub fn redo(&mut self) -> Option<Location> {
+    let edits = self.history.redo()?;
+    Some(self.apply(edits))
+  }
+
+  /// apply edits without recording them, returning the location after the last one.
+  fn apply(&mut self, edits: Vec<Edit>) -> Location {
+    let mut cursor = 0;
+    for edit in edits {
+      match edit {
+        Edit::Insert { index, text } => {
+          self.splice(index, 0, &text);
+          cursor = index + text.chars().count();
+        },
+        Edit::Remove { index, text } => {
+          self.splice(index, text.chars().count(), "");
+          cursor = index;
+        },
+      }
+    }
+    self.location_at(cursor)
+  }
+
+  /// the character at `location`. large files build their characters on
+  /// demand, so they have none to lend, see `character_at`.
   pub fn get_character(&self, location: Location) -> Option<&Character> {
-    self.parsed.iter().find(|c| c.location == location)
+    self.line_slice(location.ln)?.get(location.col as usize)
+  }
+
+  /// like `get_character`, but reading large files too.
+  pub fn character_at(&self, location: Location) -> Option<Character> {
+    match &self.large {
+      Some(large) => large.get_character(location),
+      None => self.get_character(location).cloned(),
+    }
+  }
+
+  /// every parsed character of line `ln`, including its '\n'.
+  fn line_slice(&self, ln: u32) -> Option<&[Character]> {
+    let start = *self.line_starts.get(ln as usize)?;
+    let end = self.line_starts.get(ln as usize + 1).copied().unwrap_or(self.parsed.len());
+    Some(&self.parsed[start..end])
   }
 
   fn parse(&mut self) {
     self.parsed.clear();
+    self.line_starts.clear();
+    self.line_starts.push(0);
     let mut location = Location { ln: 0, col: 0 };
     let mut position = Position { x: 0, y: 0 };
     for (index, char) in self.raw.iter().enumerate() {
-      let char = char.clone();
-      let width = char_width(char);
-      if char == '\n' {
-        location.ln += 1;
-        location.col = 0;
-        position.x = 0;
-        position.y += 1;
-      }
+      let char = *char;
+      let width = match (char, self.tab_width) {
+        ('\t', Some(tab_width)) => tab_width - position.x % tab_width,
+        _ => char_width(char),
+      };
       self.parsed.push(
         Character {
-          char: char.clone(),
+          char,
           index,
-          location: location.clone(),
-          position: position.clone(),
+          location,
+          position,
           width,
         }
       );
-      location.col += 1;
-      position.x += width;
+      if char == '\n' {
+        self.line_starts.push(index + 1);
+        location.ln += 1;
+        location.col = 0;
+        position.x = 0;
+        position.y += 1;
+      } else {
+        location.col += 1;
+        position.x += width;
+      }
     }
   }
 
+  /// visible characters of every line. a line's '\n' is its last character,
+  /// so a document ending with '\n' has an empty last line.
   pub fn lines(&self) -> Vec<Vec<Character>> {
-    let mut lines: Vec<Vec<Character>> = Vec::new();
-    let mut current_ln: u32 = 0;
-    let mut current_line: Vec<Character> = Vec::new();
+    let mut lines: Vec<Vec<Character>> = vec![Vec::new()];
     for char in self.parsed.iter() {
       if char.char == '\n' {
-        lines.push(current_line.clone());
-        current_ln += 1;
-        current_line = Vec::new();
+        lines.push(Vec::new());
+        continue;
       }
       if char.width == 0 {
         continue;
       }
-      if char.location.ln == current_ln {
-        current_line.push(char.clone());
+      if let Some(line) = lines.last_mut() {
+        line.push(char.clone());
       }
-      if char.location.ln > current_ln {
-        lines.push(current_line.clone());
-        current_ln = char.location.ln;
-        current_line = vec![char.clone()];
-      }
-      if char.location.ln < current_ln {
-        panic!("document model error.");
-      }
-    }
-    if !current_line.is_empty() {
-      lines.push(current_line);
     }
     lines
   }
 
+  /// visible characters of line `ln`.
+  pub fn line(&self, ln: u32) -> Option<Vec<Character>> {
+    if self.large.is_some() {
+      return self.lines_in(ln as usize, 1).pop();
+    }
+    Some(self.line_slice(ln)?.iter().filter(|c| c.width > 0).cloned().collect())
+  }
+
+  /// where the line break ending line `ln` starts: the '\r' of a "\r\n",
+  /// else the '\n', or the end of the last line.
+  pub fn line_break(&self, ln: u32) -> Location {
+    let col = match &self.large {
+      Some(large) => large.line(ln as usize).map(|line| break_col(&line)),
+      None => self.line_slice(ln).map(break_col),
+    };
+    Location { ln, col: col.unwrap_or(0) as u32 }
+  }
+
+  /// text of line `ln`, without the line break.
+  pub fn line_text(&self, ln: u32) -> String {
+    self.line(ln).unwrap_or_default().iter().map(|c| c.char).collect()
+  }
+
+  /// the location right after the last character.
+  pub fn end_location(&self) -> Location {
+    match self.last_character() {
+      Some(char) if char.char == '\n' => Location { ln: char.location.ln + 1, col: 0 },
+      Some(char) => Location { ln: char.location.ln, col: char.location.col + 1 },
+      None => Location { ln: 0, col: 0 },
+    }
+  }
+
+  /// visible characters of `count` lines starting at line `start`.
+  /// in large-file mode only these lines are materialized.
+  pub fn lines_in(&self, start: usize, count: usize) -> Vec<Vec<Character>> {
+    match &self.large {
+      Some(large) => (start..start + count)
+        .map_while(|ln| large.line(ln))
+        .map(|line| line.into_iter().filter(|c| c.width > 0).collect())
+        .collect(),
+      None => self.lines().into_iter().skip(start).take(count).collect(),
+    }
+  }
+
+  /// number of lines. in large-file mode this grows while the file is being indexed.
+  pub fn line_count(&self) -> usize {
+    match &self.large {
+      Some(large) => large.line_count(),
+      None => self.line_starts.len(),
+    }
+  }
+
+  /// find the next occurrence of `pattern` at or after `from`.
+  pub fn find(&self, pattern: &str, from: Location) -> Option<Location> {
+    if let Some(large) = &self.large {
+      return large.find(pattern, from);
+    }
+    let needle: Vec<char> = pattern.chars().collect();
+    if needle.is_empty() {
+      return None;
+    }
+    let start = self.parsed.iter().position(|c| c.location >= from)?;
+    self.raw[start..]
+      .windows(needle.len())
+      .position(|window| window == needle.as_slice())
+      .map(|index| self.parsed[start + index].location)
+  }
+
   pub fn before(&self, location: Location) -> Option<Character> {
+    if let Some(large) = &self.large {
+      return large.before(location);
+    }
     match self.get_character(location) {
       Some(pchar) => {
         if pchar.index > 0 {
@@ -120,6 +552,9 @@ impl Document {
   }
 
   pub fn after(&self, location: Location) -> Option<Character> {
+    if let Some(large) = &self.large {
+      return large.after(location);
+    }
     match self.get_character(location) {
       Some(pchar) => {
         if pchar.index + 1 < self.parsed.len() {
@@ -133,7 +568,10 @@ impl Document {
   }
 
   pub fn last_character(&self) -> Option<Character> {
-    self.parsed.last().cloned()
+    match &self.large {
+      Some(large) => large.last_character(),
+      None => self.parsed.last().cloned(),
+    }
   }
 
   pub fn is_out_of_document(&self, location: Location) -> bool {
@@ -144,20 +582,53 @@ impl Document {
   }
 }
 
+impl Default for Document {
+  fn default() -> Self {
+    Self {
+      raw: Vec::new(),
+      parsed: Vec::new(),
+      line_starts: vec![0],
+      large: None,
+      path: None,
+      history: History::default(),
+      changes: None,
+      diagnostics: Vec::new(),
+      folds: Vec::new(),
+      version: 0,
+      saved_version: 0,
+      line_ending: "\n",
+      tab_width: None,
+      bom: false,
+    }
+  }
+}
+
 impl From<&str> for Document {
   fn from(value: &str) -> Self {
+    // new lines follow the first line break
+    let crlf = value.find('\n').is_some_and(|i| value[..i].ends_with('\r'));
     let mut doc = Self {
       raw: value.chars().collect(),
-      parsed: Vec::new()
+      line_ending: if crlf { "\r\n" } else { "\n" },
+      ..Self::default()
     };
     doc.parse();
     doc
   }
 }
 
-impl Into<String> for Document {
-  fn into(self) -> String {
-    self.raw.into_iter().collect()
+impl From<Document> for String {
+  fn from(value: Document) -> Self {
+    value.raw.into_iter().collect()
+  }
+}
+
+/// column of the line break in the characters of a whole line.
+fn break_col(line: &[Character]) -> usize {
+  match line {
+    [.., cr, lf] if cr.char == '\r' && lf.char == '\n' => line.len() - 2,
+    [.., lf] if lf.char == '\n' => line.len() - 1,
+    _ => line.len(),
   }
 }
 
@@ -212,12 +683,12 @@ use super::*;
   #[test]
   fn test4() {
     let doc = Document::from("rust\n铁锈");
-    let before_char = doc.before(Location { ln: 1, col: 1 }).unwrap();
+    let before_char = doc.before(Location { ln: 1, col: 0 }).unwrap();
     assert_eq!(before_char.char, '\n');
-    assert_eq!(before_char.location, Location { ln: 1, col: 0 });
-    let after_char = doc.after(Location { ln: 1, col: 0 }).unwrap();
+    assert_eq!(before_char.location, Location { ln: 0, col: 4 });
+    let after_char = doc.after(Location { ln: 0, col: 4 }).unwrap();
     assert_eq!(after_char.char, '铁');
-    assert_eq!(after_char.location, Location { ln: 1, col: 1 });
+    assert_eq!(after_char.location, Location { ln: 1, col: 0 });
   }
 
   #[test]
@@ -232,4 +703,69 @@ use super::*;
       println!("{:?}", line.iter().map(|c| c.char).collect::<Vec<char>>());
     }
   }
+
+  #[test]
+  fn test6() {
+    let doc = Document::from("one\ntwo\none");
+    assert_eq!(doc.line_count(), 3);
+    assert_eq!(doc.find("one", Location { ln: 0, col: 1 }), Some(Location { ln: 2, col: 0 }));
+    assert_eq!(doc.find("three", Location { ln: 0, col: 0 }), None);
+    let lines = doc.lines_in(1, 5);
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].iter().map(|c| c.char).collect::<String>(), "two");
+  }
+
+  #[test]
+  fn test7() {
+    let mut doc = Document::from("a😀b\ncd");
+    doc.track_changes();
+    doc.insert(Location { ln: 0, col: 2 }, "x").unwrap();
+    doc.remove(Range::new(Location { ln: 0, col: 3 }, Location { ln: 1, col: 1 })).unwrap();
+    doc.undo();
+    assert_eq!(doc.take_changes(), vec![
+      Change { start: Location { ln: 0, col: 2 }, start_utf16: 3, removed: String::new(), inserted: "x".to_string() },
+      Change { start: Location { ln: 0, col: 3 }, start_utf16: 4, removed: "b\nc".to_string(), inserted: String::new() },
+      Change { start: Location { ln: 0, col: 3 }, start_utf16: 4, removed: String::new(), inserted: "b\nc".to_string() },
+    ]);
+    assert!(doc.take_changes().is_empty());
+    assert_eq!(doc.column_from_utf16(0, 3), 2);
+  }
+
+  #[test]
+  fn test8() {
+    let mut doc = Document::from("let a = 1;\nlet b = a;");
+    let diagnostic = |start: Location, end: Location| Diagnostic {
+      range: Range::new(start, end),
+      severity: diagnostic::Severity::Warning,
+      message: "unused".to_string(),
+      source: None,
+    };
+    doc.set_diagnostics(vec![
+      diagnostic(Location { ln: 1, col: 4 }, Location { ln: 1, col: 5 }),
+      diagnostic(Location { ln: 0, col: 4 }, Location { ln: 0, col: 5 }),
+    ]);
+    assert_eq!(doc.diagnostics()[0].range.start, Location { ln: 0, col: 4 });
+    doc.insert(Location { ln: 0, col: 0 }, "\n  ").unwrap();
+    assert_eq!(doc.diagnostics()[0].range, Range::new(Location { ln: 1, col: 6 }, Location { ln: 1, col: 7 }));
+    assert_eq!(doc.diagnostics()[1].range, Range::new(Location { ln: 2, col: 4 }, Location { ln: 2, col: 5 }));
+    doc.remove(Range::new(Location { ln: 1, col: 5 }, Location { ln: 1, col: 7 })).unwrap();
+    assert_eq!(doc.diagnostics()[0].range, Range::new(Location { ln: 1, col: 5 }, Location { ln: 1, col: 5 }));
+    doc.undo();
+    doc.undo();
+    assert_eq!(doc.diagnostics()[1].range, Range::new(Location { ln: 1, col: 4 }, Location { ln: 1, col: 5 }));
+  }
+
+  #[test]
+  fn test9() {
+    let mut doc = Document::from("a  \r\nb\t\r\n\r\n  \r\n");
+    let all = SaveActions { trim_trailing_whitespace: true, insert_final_newline: true, trim_final_newlines: true };
+    assert!(doc.apply_save_actions(all));
+    assert_eq!(doc.text(), "a\r\nb\r\n");
+    assert!(!doc.apply_save_actions(all));
+    doc.undo();
+    assert_eq!(doc.text(), "a  \r\nb\t\r\n\r\n  \r\n");
+    let mut doc = Document::from("x");
+    assert!(doc.apply_save_actions(SaveActions { insert_final_newline: true, ..SaveActions::default() }));
+    assert_eq!(doc.text(), "x\n");
+  }
 }
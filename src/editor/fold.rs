@@ -0,0 +1,204 @@
+//! Folds: runs of lines hidden behind the line that opens them.
+
+use super::{brackets, document::Document, indent::OPENERS, language::Language};
+
+/// how far `enclosing` looks upwards for a block around a line.
+const MAX_SCAN_LINES: u32 = 1000;
+
+/// line `start` stays visible, lines `start + 1..=end` are hidden.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fold {
+  pub start: u32,
+  pub end: u32,
+}
+
+impl Fold {
+  pub fn hides(&self, ln: u32) -> bool {
+    ln > self.start && ln <= self.end
+  }
+
+  pub fn hidden_count(&self) -> u32 {
+    self.end - self.start
+  }
+}
+
+/// the block opened on line `ln`: up to the matching bracket when the line
+/// ends with an opener, under a Markdown heading until the next heading of the
+/// same level, and otherwise the lines indented deeper than `ln`.
+pub fn range_at(document: &Document, ln: u32, language: Language) -> Option<Fold> {
+  let line = document.line(ln)?;
+  let text = document.line_text(ln);
+  if text.trim().is_empty() {
+    return None;
+  }
+  if language == Language::Markdown {
+    return heading_range(document, ln);
+  }
+  let mask = brackets::code_mask(&line, language);
+  let last = line.iter().zip(mask).rev().find(|(c, code)| *code && !c.char.is_whitespace());
+  if let Some((opener, _)) = last.filter(|(c, _)| OPENERS.contains(&c.char)) {
+    let close = brackets::find_match(document, opener.location, language)?;
+    // the closing line stays visible, so `} else {` still reads right
+    return (close.ln > ln + 1).then(|| Fold { start: ln, end: close.ln - 1 });
+  }
+  indent_range(document, ln)
+}
+
+/// whether line `ln` looks like it opens a block. cheaper than `range_at`,
+/// for marking every visible line.
+pub fn is_foldable(document: &Document, ln: u32, language: Language) -> bool {
+  let text = document.line_text(ln);
+  if language == Language::Markdown {
+    return heading_level(&text).is_some();
+  }
+  if text.trim_end().ends_with(OPENERS) {
+    return true;
+  }
+  let next = (ln + 1..document.line_count() as u32)
+    .map(|ln| document.line_text(ln))
+    .find(|line| !line.trim().is_empty());
+  !text.trim().is_empty() && next.is_some_and(|next| indent_width(&next) > indent_width(&text))
+}
+
+/// the innermost block that line `ln` is inside of.
+pub fn enclosing(document: &Document, ln: u32, language: Language) -> Option<Fold> {
+  (ln.saturating_sub(MAX_SCAN_LINES)..ln).rev()
+    .filter_map(|start| range_at(document, start, language))
+    .find(|fold| fold.hides(ln))
+}
+
+fn indent_range(document: &Document, ln: u32) -> Option<Fold> {
+  let base = indent_width(&document.line_text(ln));
+  let mut end = None;
+  for next in ln + 1..document.line_count() as u32 {
+    let text = document.line_text(next);
+    if text.trim().is_empty() {
+      continue;
+    }
+    if indent_width(&text) <= base {
+      break;
+    }
+    end = Some(next);
+  }
+  end.map(|end| Fold { start: ln, end })
+}
+
+fn heading_range(document: &Document, ln: u32) -> Option<Fold> {
+  let level = heading_level(&document.line_text(ln))?;
+  let mut end = ln;
+  for next in ln + 1..document.line_count() as u32 {
+    if heading_level(&document.line_text(next)).is_some_and(|other| other <= level) {
+      break;
+    }
+    end = next;
+  }
+  // trailing blank lines stay visible as the gap before the next heading
+  while end > ln && document.line_text(end).trim().is_empty() {
+    end -= 1;
+  }
+  (end > ln).then_some(Fold { start: ln, end })
+}
+
+fn heading_level(line: &str) -> Option<usize> {
+  let level = line.chars().take_while(|c| *c == '#').count();
+  (level > 0 && line[level..].starts_with(' ')).then_some(level)
+}
+
+/// leading whitespace in columns, a tab counting as four.
+fn indent_width(line: &str) -> usize {
+  line.chars()
+    .take_while(|c| c.is_whitespace())
+    .map(|c| if c == '\t' { 4 } else { 1 })
+    .sum()
+}
+
+/// the hidden lines of `folds` as merged, sorted `(first, last)` pairs.
+fn hidden_ranges(folds: &[Fold]) -> Vec<(u32, u32)> {
+  let mut ranges: Vec<(u32, u32)> = folds.iter().map(|fold| (fold.start + 1, fold.end)).collect();
+  ranges.sort();
+  let mut merged: Vec<(u32, u32)> = Vec::with_capacity(ranges.len());
+  for (first, last) in ranges {
+    match merged.last_mut() {
+      Some(previous) if first <= previous.1 + 1 => previous.1 = previous.1.max(last),
+      _ => merged.push((first, last)),
+    }
+  }
+  merged
+}
+
+/// the screen row of line `ln` with `folds` closed. a hidden line is on the
+/// row of the line that hides it.
+pub fn row_of(folds: &[Fold], ln: u32) -> u32 {
+  let mut hidden = 0;
+  for (first, last) in hidden_ranges(folds) {
+    if first > ln {
+      break;
+    }
+    if ln <= last {
+      return first - 1 - hidden;
+    }
+    hidden += last - first + 1;
+  }
+  ln - hidden
+}
+
+/// the line on screen row `row` with `folds` closed.
+pub fn line_at_row(folds: &[Fold], row: u32) -> u32 {
+  let mut ln = row;
+  for (first, last) in hidden_ranges(folds) {
+    if first > ln {
+      break;
+    }
+    ln += last - first + 1;
+  }
+  ln
+}
+
+/// keep `folds` in step with an edit that replaced lines `first..=last`,
+/// changing the line count by `delta`. edits reaching into hidden lines, or
+/// splitting or joining a fold's first line, open the fold.
+pub fn shift(folds: &mut Vec<Fold>, first: u32, last: u32, delta: i64) {
+  folds.retain(|fold| {
+    let touches_hidden = first <= fold.end && last > fold.start;
+    let changes_start = delta != 0 && (first..=last).contains(&fold.start);
+    !touches_hidden && !changes_start
+  });
+  for fold in folds.iter_mut().filter(|fold| fold.start > last) {
+    fold.start = (fold.start as i64 + delta) as u32;
+    fold.end = (fold.end as i64 + delta) as u32;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_ranges() {
+    let doc = Document::from("fn a() {\n  b();\n  c();\n} else {\n}\ndef d():\n    e\n\n    f\ng");
+    assert_eq!(range_at(&doc, 0, Language::Rust), Some(Fold { start: 0, end: 2 }));
+    assert_eq!(range_at(&doc, 3, Language::Rust), None);
+    assert_eq!(range_at(&doc, 5, Language::Python), Some(Fold { start: 5, end: 8 }));
+    assert_eq!(enclosing(&doc, 2, Language::Rust), Some(Fold { start: 0, end: 2 }));
+    assert!(is_foldable(&doc, 5, Language::Python));
+    let doc = Document::from("# a\ntext\n## b\nmore\n\n# c\n");
+    assert_eq!(range_at(&doc, 0, Language::Markdown), Some(Fold { start: 0, end: 3 }));
+    assert_eq!(range_at(&doc, 2, Language::Markdown), Some(Fold { start: 2, end: 3 }));
+  }
+
+  #[test]
+  fn test_rows() {
+    let folds = [Fold { start: 1, end: 3 }, Fold { start: 2, end: 3 }, Fold { start: 5, end: 6 }];
+    assert_eq!(row_of(&folds, 1), 1);
+    assert_eq!(row_of(&folds, 3), 1);
+    assert_eq!(row_of(&folds, 4), 2);
+    assert_eq!(row_of(&folds, 7), 4);
+    assert_eq!(line_at_row(&folds, 2), 4);
+    assert_eq!(line_at_row(&folds, 4), 7);
+    let mut folds = folds.to_vec();
+    shift(&mut folds, 0, 0, 2);
+    assert_eq!(folds[0], Fold { start: 3, end: 5 });
+    shift(&mut folds, 4, 4, 0);
+    assert_eq!(folds, vec![Fold { start: 4, end: 5 }, Fold { start: 7, end: 8 }]);
+  }
+}
@@ -0,0 +1,67 @@
+/// a single change to a document's text, addressed by character index.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Edit {
+  Insert { index: usize, text: String },
+  Remove { index: usize, text: String },
+}
+
+impl Edit {
+  /// the edit that undoes this one.
+  pub fn inverse(&self) -> Self {
+    match self {
+      Self::Insert { index, text } => Self::Remove { index: *index, text: text.clone() },
+      Self::Remove { index, text } => Self::Insert { index: *index, text: text.clone() },
+    }
+  }
+}
+
+/// undo and redo stacks. edits made between `begin` and `end` are undone as
+/// one step, everything else is one step per edit.
+#[derive(Debug, Default)]
+pub struct History {
+  undo: Vec<Vec<Edit>>,
+  redo: Vec<Vec<Edit>>,
+  group: Option<Vec<Edit>>,
+  depth: usize,
+}
+
+impl History {
+  pub fn record(&mut self, edit: Edit) {
+    self.redo.clear();
+    match self.group.as_mut() {
+      Some(group) => group.push(edit),
+      None => self.undo.push(vec![edit]),
+    }
+  }
+
+  pub fn begin(&mut self) {
+    if self.depth == 0 {
+      self.group = Some(Vec::new());
+    }
+    self.depth += 1;
+  }
+
+  pub fn end(&mut self) {
+    self.depth = self.depth.saturating_sub(1);
+    if self.depth == 0 {
+      if let Some(group) = self.group.take().filter(|group| !group.is_empty()) {
+        self.undo.push(group);
+      }
+    }
+  }
+
+  /// the edits of the last step, most recent first, already inverted.
+  pub fn undo(&mut self) -> Option<Vec<Edit>> {
+    let step = self.undo.pop()?;
+    let inverse = step.iter().rev().map(Edit::inverse).collect();
+    self.redo.push(step);
+    Some(inverse)
+  }
+
+  /// the edits of the last undone step, in their original order.
+  pub fn redo(&mut self) -> Option<Vec<Edit>> {
+    let step = self.redo.pop()?;
+    self.undo.push(step.clone());
+    Some(step)
+  }
+}
@@ -0,0 +1,72 @@
+use super::language::Language;
+
+pub const OPENERS: [char; 3] = ['{', '(', '['];
+pub const CLOSERS: [char; 3] = ['}', ')', ']'];
+
+/// the whitespace a line starts with.
+pub fn leading_whitespace(line: &str) -> &str {
+  let end = line.len() - line.trim_start_matches([' ', '\t']).len();
+  &line[..end]
+}
+
+/// whether the next line after `line` should be indented one more level.
+pub fn opens_block(line: &str, language: Language) -> bool {
+  match line.trim_end().chars().last() {
+    Some(last) => language.indent_triggers().contains(&last),
+    None => false,
+  }
+}
+
+/// the closing partner of an opening bracket.
+pub fn closer_of(opener: char) -> Option<char> {
+  OPENERS.iter().position(|c| *c == opener).map(|i| CLOSERS[i])
+}
+
+/// remove one indent level from the end of `indent`.
+pub fn dedent<'a>(indent: &'a str, unit: &str) -> &'a str {
+  if let Some(rest) = indent.strip_suffix(unit) {
+    return rest;
+  }
+  if let Some(rest) = indent.strip_suffix('\t') {
+    return rest;
+  }
+  // a partial level, e.g. 3 spaces with a 4 space unit
+  indent.trim_end_matches(' ')
+}
+
+/// the indentation `line` should have, given the closest non-blank line above it.
+pub fn indent_for(previous: Option<&str>, line: &str, unit: &str, language: Language) -> String {
+  let Some(previous) = previous else { return String::new() };
+  let mut indent = leading_whitespace(previous).to_string();
+  if opens_block(previous, language) {
+    indent.push_str(unit);
+  }
+  if line.trim_start().starts_with(CLOSERS) {
+    indent = dedent(&indent, unit).to_string();
+  }
+  indent
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_indent_for() {
+    let unit = "  ";
+    assert_eq!(indent_for(None, "fn main() {", unit, Language::Rust), "");
+    assert_eq!(indent_for(Some("fn main() {"), "let a = 1;", unit, Language::Rust), "  ");
+    assert_eq!(indent_for(Some("  let a = 1;"), "}", unit, Language::Rust), "");
+    assert_eq!(indent_for(Some("  foo(["), "])", unit, Language::Rust), "  ");
+    assert_eq!(indent_for(Some("def main():"), "pass", unit, Language::Python), "  ");
+    assert_eq!(indent_for(Some("note:"), "text", unit, Language::PlainText), "");
+  }
+
+  #[test]
+  fn test_dedent() {
+    assert_eq!(dedent("        ", "    "), "    ");
+    assert_eq!(dedent("\t\t", "    "), "\t");
+    assert_eq!(dedent("   ", "    "), "");
+    assert_eq!(leading_whitespace(" \t x "), " \t ");
+  }
+}
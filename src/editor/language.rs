@@ -0,0 +1,106 @@
+use std::path::Path;
+
+/// languages pound knows something about. detected from the file extension.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Language {
+  #[default]
+  PlainText,
+  Rust,
+  Python,
+  JavaScript,
+  TypeScript,
+  Json,
+  Toml,
+  Yaml,
+  Markdown,
+  C,
+  Go,
+}
+
+impl Language {
+  pub fn from_path(path: &Path) -> Self {
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+    match extension {
+      "rs" => Self::Rust,
+      "py" | "pyi" => Self::Python,
+      "js" | "jsx" | "mjs" | "cjs" => Self::JavaScript,
+      "ts" | "tsx" => Self::TypeScript,
+      "json" => Self::Json,
+      "toml" => Self::Toml,
+      "yml" | "yaml" => Self::Yaml,
+      "md" | "markdown" => Self::Markdown,
+      "c" | "h" | "cc" | "cpp" | "hpp" => Self::C,
+      "go" => Self::Go,
+      _ => Self::PlainText,
+    }
+  }
+
+  /// a line ending with one of these opens a block, so the next line gets one
+  /// more indent level.
+  pub fn indent_triggers(&self) -> &'static [char] {
+    match self {
+      Self::Python | Self::Yaml => &['{', '(', '[', ':'],
+      Self::Markdown | Self::PlainText => &[],
+      _ => &['{', '(', '['],
+    }
+  }
+
+  /// default indent unit, used until settings say otherwise.
+  pub fn indent_unit(&self) -> &'static str {
+    match self {
+      Self::Go => "\t",
+      Self::Python | Self::Rust | Self::C => "    ",
+      _ => "  ",
+    }
+  }
+
+  /// prefix that starts a comment running to the end of the line.
+  pub fn line_comment(&self) -> Option<&'static str> {
+    match self {
+      Self::Rust | Self::JavaScript | Self::TypeScript | Self::C | Self::Go | Self::Json => Some("//"),
+      Self::Python | Self::Toml | Self::Yaml => Some("#"),
+      Self::Markdown | Self::PlainText => None,
+    }
+  }
+
+  /// characters that delimit string literals.
+  pub fn quotes(&self) -> &'static [char] {
+    match self {
+      Self::Rust | Self::C => &['"'],
+      Self::Go => &['"', '`'],
+      Self::JavaScript | Self::TypeScript => &['"', '\'', '`'],
+      Self::Python | Self::Toml | Self::Yaml => &['"', '\''],
+      Self::Json => &['"'],
+      Self::Markdown | Self::PlainText => &[],
+    }
+  }
+
+  /// the `languageId` the language server protocol uses.
+  pub fn language_id(&self) -> &'static str {
+    match self {
+      Self::PlainText => "plaintext",
+      Self::Rust => "rust",
+      Self::Python => "python",
+      Self::JavaScript => "javascript",
+      Self::TypeScript => "typescript",
+      Self::Json => "json",
+      Self::Toml => "toml",
+      Self::Yaml => "yaml",
+      Self::Markdown => "markdown",
+      Self::C => "c",
+      Self::Go => "go",
+    }
+  }
+
+  /// the language server started for documents of this language.
+  pub fn language_server(&self) -> Option<(&'static str, &'static [&'static str])> {
+    match self {
+      Self::Rust => Some(("rust-analyzer", &[])),
+      Self::Python => Some(("pylsp", &[])),
+      Self::JavaScript | Self::TypeScript => Some(("typescript-language-server", &["--stdio"])),
+      Self::C => Some(("clangd", &[])),
+      Self::Go => Some(("gopls", &[])),
+      _ => None,
+    }
+  }
+}
@@ -0,0 +1,262 @@
+use std::{
+  fs::File,
+  path::Path,
+  sync::{Arc, RwLock, atomic::{AtomicBool, Ordering}},
+  thread,
+};
+
+use anyhow::{Context, Result};
+use memmap2::Mmap;
+
+use super::{character::Character, location::Location, position::Position};
+use crate::editor::measure::char_width;
+
+/// how many line starts the indexer collects before publishing them.
+const INDEX_BATCH: usize = 4096;
+/// how much of a line is turned into `Character`s. anything past it is cut
+/// off, so a file that is one huge line doesn't end up in memory whole.
+const MAX_LINE_BYTES: usize = 64 * 1024;
+
+/// a read-only view over a memory-mapped file.
+/// line starts are indexed by a background thread, and `Character`s are only
+/// materialized for the lines that someone actually asks for.
+#[derive(Debug)]
+pub struct LargeFile {
+  mmap: Arc<Mmap>,
+  line_starts: Arc<RwLock<Vec<usize>>>,
+  indexed: Arc<AtomicBool>,
+}
+
+impl LargeFile {
+  pub fn open(path: &Path) -> Result<Self> {
+    let file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    // SAFETY: the mapping is only ever read. if another process truncates the
+    // file while it is mapped we may read garbage, which is the same trade-off
+    // every mmap based viewer makes.
+    let mmap = unsafe { Mmap::map(&file) }.with_context(|| format!("failed to map {}", path.display()))?;
+    let large_file = Self {
+      mmap: Arc::new(mmap),
+      line_starts: Arc::new(RwLock::new(vec![0])),
+      indexed: Arc::new(AtomicBool::new(false)),
+    };
+    large_file.spawn_indexer();
+    Ok(large_file)
+  }
+
+  fn spawn_indexer(&self) {
+    let mmap = self.mmap.clone();
+    let line_starts = self.line_starts.clone();
+    let indexed = self.indexed.clone();
+    thread::spawn(move || {
+      let mut batch = Vec::with_capacity(INDEX_BATCH);
+      for (offset, byte) in mmap.iter().enumerate() {
+        if *byte == b'\n' {
+          batch.push(offset + 1);
+          if batch.len() == INDEX_BATCH {
+            line_starts.write().unwrap().append(&mut batch);
+          }
+        }
+      }
+      line_starts.write().unwrap().append(&mut batch);
+      indexed.store(true, Ordering::Release);
+    });
+  }
+
+  /// whether the background indexer has seen the whole file.
+  pub fn is_indexed(&self) -> bool {
+    self.indexed.load(Ordering::Acquire)
+  }
+
+  /// number of lines known so far. only final once `is_indexed` returns true.
+  pub fn line_count(&self) -> usize {
+    self.line_starts.read().unwrap().len()
+  }
+
+  /// size of the file in bytes.
+  pub fn len(&self) -> usize {
+    self.mmap.len()
+  }
+
+  /// byte offset where line `ln` starts, right after the previous '\n'.
+  /// lines the indexer has not reached yet are found by scanning forward.
+  fn line_start(&self, ln: usize) -> Option<usize> {
+    let indexed = self.is_indexed();
+    let (mut current, mut start) = {
+      let line_starts = self.line_starts.read().unwrap();
+      if let Some(start) = line_starts.get(ln) {
+        return Some(*start);
+      }
+      (line_starts.len() - 1, *line_starts.last().unwrap())
+    };
+    if indexed {
+      return None;
+    }
+    while current < ln {
+      let next = self.mmap[start..].iter().position(|b| *b == b'\n')?;
+      start += next + 1;
+      current += 1;
+    }
+    Some(start)
+  }
+
+  fn line_end(&self, ln: usize) -> usize {
+    self.line_start(ln + 1).unwrap_or(self.mmap.len())
+  }
+
+  /// every character of line `ln`, including the '\n' that ends it.
+  /// lines longer than `MAX_LINE_BYTES` stop at the last whole character
+  /// before it.
+  pub fn line(&self, ln: usize) -> Option<Vec<Character>> {
+    let start = self.line_start(ln)?;
+    let mut end = self.line_end(ln);
+    if end - start > MAX_LINE_BYTES {
+      end = start + MAX_LINE_BYTES;
+      // back off to the start of the character the cut went through
+      while end > start && self.mmap[end] & 0xc0 == 0x80 {
+        end -= 1;
+      }
+    }
+    let text = String::from_utf8_lossy(&self.mmap[start..end]);
+    let mut position = Position { x: 0, y: ln as u32 };
+    let mut byte_index = start;
+    let chars = text.chars()
+      .enumerate()
+      .map(|(col, char)| {
+        let width = char_width(char);
+        let character = Character {
+          index: byte_index,
+          char,
+          width,
+          position,
+          location: Location { ln: ln as u32, col: col as u32 },
+        };
+        byte_index += char.len_utf8();
+        position.x += width;
+        character
+      })
+      .collect();
+    Some(chars)
+  }
+
+  pub fn get_character(&self, location: Location) -> Option<Character> {
+    self.line(location.ln as usize)?.get(location.col as usize).cloned()
+  }
+
+  pub fn before(&self, location: Location) -> Option<Character> {
+    if location.col > 0 {
+      return self.get_character(Location { ln: location.ln, col: location.col - 1 });
+    }
+    if location.ln == 0 {
+      return None;
+    }
+    self.line(location.ln as usize - 1)?.last().cloned()
+  }
+
+  pub fn after(&self, location: Location) -> Option<Character> {
+    let line = self.line(location.ln as usize)?;
+    match line.get(location.col as usize + 1) {
+      Some(char) => Some(char.clone()),
+      None => self.line(location.ln as usize + 1)?.first().cloned(),
+    }
+  }
+
+  /// the last character of the file. while indexing it is the last one
+  /// indexed so far, the line break ending the last complete line.
+  pub fn last_character(&self) -> Option<Character> {
+    let line_count = self.line_count();
+    // the last line found so far may still be growing
+    let complete = if self.is_indexed() { line_count } else { line_count - 1 };
+    (0..complete).rev()
+      .find_map(|ln| self.line(ln).and_then(|line| line.last().cloned()))
+  }
+
+  /// find the next occurrence of `pattern` at or after `from`.
+  pub fn find(&self, pattern: &str, from: Location) -> Option<Location> {
+    let needle = pattern.as_bytes();
+    if needle.is_empty() {
+      return None;
+    }
+    let start = match self.get_character(from) {
+      Some(char) => char.index,
+      None => self.line_start(from.ln as usize + 1)?,
+    };
+    let found = self.mmap[start..]
+      .windows(needle.len())
+      .position(|window| window == needle)? + start;
+    Some(self.location_of(found))
+  }
+
+  /// convert a byte offset into the `Location` of the character there.
+  fn location_of(&self, offset: usize) -> Location {
+    let mut ln = {
+      let line_starts = self.line_starts.read().unwrap();
+      line_starts.partition_point(|start| *start <= offset) - 1
+    };
+    while let Some(next) = self.line_start(ln + 1) {
+      if next > offset {
+        break;
+      }
+      ln += 1;
+    }
+    let start = self.line_start(ln).unwrap_or(0);
+    let col = String::from_utf8_lossy(&self.mmap[start..offset]).chars().count();
+    Location { ln: ln as u32, col: col as u32 }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{io::Write, time::Duration};
+  use super::*;
+
+  fn temp_file(name: &str, content: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("pound-{}-{}", std::process::id(), name));
+    File::create(&path).unwrap().write_all(content.as_bytes()).unwrap();
+    path
+  }
+
+  #[test]
+  fn test_lines() {
+    let path = temp_file("large-lines", "rust\n铁锈\nend");
+    let file = LargeFile::open(&path).unwrap();
+    let line: String = file.line(1).unwrap().iter().map(|c| c.char).collect();
+    assert_eq!(line, "铁锈\n");
+    let char = file.get_character(Location { ln: 1, col: 1 }).unwrap();
+    assert_eq!(char.char, '锈');
+    assert_eq!(char.position, Position { x: 2, y: 1 });
+    assert_eq!(file.before(Location { ln: 1, col: 0 }).unwrap().char, '\n');
+    assert_eq!(file.after(Location { ln: 1, col: 2 }).unwrap().char, 'e');
+    while !file.is_indexed() {
+      std::thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(file.line_count(), 3);
+    assert_eq!(file.last_character().unwrap().char, 'd');
+    std::fs::remove_file(path).unwrap();
+  }
+
+  #[test]
+  fn test_find() {
+    let content: String = (0..10000).map(|ln| format!("line {}\n", ln)).collect();
+    let path = temp_file("large-find", &content);
+    let file = LargeFile::open(&path).unwrap();
+    let found = file.find("line 9999", Location { ln: 0, col: 0 }).unwrap();
+    assert_eq!(found, Location { ln: 9999, col: 0 });
+    let first = file.find("line", Location { ln: 0, col: 1 }).unwrap();
+    assert_eq!(first, Location { ln: 1, col: 0 });
+    assert_eq!(file.find("missing", Location { ln: 0, col: 0 }), None);
+    std::fs::remove_file(path).unwrap();
+  }
+
+  #[test]
+  fn test_long_line() {
+    let content = format!("a{}\nend", "铁".repeat(MAX_LINE_BYTES));
+    let path = temp_file("large-long-line", &content);
+    let file = LargeFile::open(&path).unwrap();
+    let line = file.line(0).unwrap();
+    // 'a' and then as many whole 3 byte characters as fit
+    assert_eq!(line.len(), 1 + (MAX_LINE_BYTES - 1) / 3);
+    assert_eq!(line.last().unwrap().char, '铁');
+    assert_eq!(file.line(1).unwrap().len(), 3);
+    std::fs::remove_file(path).unwrap();
+  }
+}
@@ -1,9 +1,19 @@
 mod context;
 pub mod measure;
 pub mod character;
+pub mod cursor;
+pub mod diagnostic;
+pub mod brackets;
 pub mod document;
+pub mod fold;
+pub mod history;
+pub mod indent;
+pub mod language;
+pub mod large_file;
 pub mod location;
 pub mod position;
 pub mod range;
+pub mod scroll;
+pub mod whitespace;
 
 pub use context::Context;
@@ -1,6 +1,6 @@
 use super::location::Location;
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Range {
   pub start: Location,
   pub end: Location,
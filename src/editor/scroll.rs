@@ -0,0 +1,94 @@
+//! Keeping the cursor in view: how far the viewport moves when the cursor
+//! gets close to one of its edges.
+
+use serde::{Deserialize, Serialize};
+
+/// how the viewport follows the cursor sideways.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HorizontalScroll {
+  /// just enough to keep the margin.
+  #[default]
+  Smooth,
+  /// half a screen at a time, putting the cursor in the middle.
+  Page,
+}
+
+/// what the cursor keeps between itself and the edges of the viewport.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ScrollOff {
+  /// lines above and below.
+  pub vertical: u32,
+  /// columns left and right.
+  pub horizontal: u32,
+  pub horizontal_scroll: HorizontalScroll,
+}
+
+/// a margin that fits a viewport `size` long, leaving room for the cursor.
+pub fn margin(margin: u32, size: u32) -> u32 {
+  margin.min(size.saturating_sub(1) / 2)
+}
+
+/// the offset that keeps `position` at least `margin` away from the edges of
+/// a viewport `size` long, moving as little as possible from `offset`.
+/// positions a whole viewport or more away are centered instead, so a big
+/// jump doesn't leave the cursor at the very edge.
+pub fn follow(offset: u32, position: u32, size: u32, margin: u32) -> u32 {
+  if size == 0 {
+    return offset;
+  }
+  let margin = self::margin(margin, size);
+  if position + size < offset || position >= offset + 2 * size {
+    return center(position, size);
+  }
+  if position < offset + margin {
+    position.saturating_sub(margin)
+  } else if position + margin >= offset + size {
+    position + margin + 1 - size
+  } else {
+    offset
+  }
+}
+
+/// like `follow`, but jumping so `position` ends up in the middle whenever
+/// it gets too close to an edge.
+pub fn page(offset: u32, position: u32, size: u32, margin: u32) -> u32 {
+  if size == 0 {
+    return offset;
+  }
+  let margin = self::margin(margin, size);
+  if position < offset + margin || position + margin >= offset + size {
+    center(position, size)
+  } else {
+    offset
+  }
+}
+
+/// the offset that puts `position` in the middle of a viewport `size` long.
+pub fn center(position: u32, size: u32) -> u32 {
+  position.saturating_sub(size / 2)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_follow() {
+    // inside the margins nothing moves
+    assert_eq!(follow(10, 15, 10, 2), 10);
+    // one past the bottom margin scrolls one line
+    assert_eq!(follow(10, 18, 10, 2), 11);
+    assert_eq!(follow(10, 11, 10, 2), 9);
+    assert_eq!(follow(0, 1, 10, 2), 0);
+    // a paste that lands a screen and a half further down
+    assert_eq!(follow(10, 25, 10, 2), 18);
+    // two screens away gets centered
+    assert_eq!(follow(10, 100, 10, 2), 95);
+    assert_eq!(follow(100, 3, 10, 2), 0);
+    // the margin shrinks to fit
+    assert_eq!(follow(0, 5, 4, 10), 3);
+    assert_eq!(page(0, 79, 80, 4), 39);
+    assert_eq!(page(40, 50, 80, 4), 40);
+  }
+}
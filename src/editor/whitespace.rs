@@ -0,0 +1,41 @@
+//! Tidying up whitespace when saving.
+
+use anyhow::{Result, bail};
+
+use super::character::Character;
+
+/// what to clean up before writing a document. all off by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SaveActions {
+  pub trim_trailing_whitespace: bool,
+  pub insert_final_newline: bool,
+  /// drop blank lines at the end, keeping a single line break.
+  pub trim_final_newlines: bool,
+}
+
+impl SaveActions {
+  pub fn any(&self) -> bool {
+    self.trim_trailing_whitespace || self.insert_final_newline || self.trim_final_newlines
+  }
+
+  /// turn on the actions named in a comma separated list like
+  /// "trim_trailing_whitespace,insert_final_newline".
+  pub fn from_names(names: &str) -> Result<Self> {
+    let mut actions = Self::default();
+    for name in names.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+      match name {
+        "trim_trailing_whitespace" => actions.trim_trailing_whitespace = true,
+        "insert_final_newline" => actions.insert_final_newline = true,
+        "trim_final_newlines" => actions.trim_final_newlines = true,
+        _ => bail!("unknown save action {:?}", name),
+      }
+    }
+    Ok(actions)
+  }
+}
+
+/// how many characters at the end of `line` are spaces or tabs. `line` is
+/// what `Document::line` returns, without the line break.
+pub fn trailing_len(line: &[Character]) -> usize {
+  line.iter().rev().take_while(|c| c.char == ' ' || c.char == '\t').count()
+}
@@ -0,0 +1,279 @@
+//! `.editorconfig` support: the files above a path are read from the
+//! outermost (or the one marked `root = true`) inwards, and matching sections
+//! override what came before.
+
+use std::{collections::HashMap, path::Path};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::{editor::Context, macros::log};
+
+/// numeric ranges like `{1..20}` with more values than this match nothing.
+const MAX_RANGE: i64 = 1000;
+
+lazy_static! {
+  static ref NUMERIC_RANGE: Regex = Regex::new(r"^\{(-?\d+)\.\.(-?\d+)\}").unwrap();
+}
+
+struct Section {
+  /// matched against the path relative to the file's directory.
+  pattern: Option<Regex>,
+  properties: Vec<(String, String)>,
+}
+
+struct Config {
+  root: bool,
+  sections: Vec<Section>,
+}
+
+fn parse(text: &str) -> Config {
+  let mut config = Config { root: false, sections: Vec::new() };
+  for line in text.lines().map(str::trim) {
+    if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+      continue;
+    }
+    if let Some(header) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+      let pattern = Regex::new(&pattern_regex(header)).ok();
+      config.sections.push(Section { pattern, properties: Vec::new() });
+      continue;
+    }
+    let Some((key, value)) = line.split_once(['=', ':']) else { continue };
+    let (key, value) = (key.trim().to_lowercase(), value.trim().to_string());
+    match config.sections.last_mut() {
+      Some(section) => section.properties.push((key, value)),
+      None if key == "root" => config.root = value.eq_ignore_ascii_case("true"),
+      None => {},
+    }
+  }
+  config
+}
+
+/// a regex for a section header. headers without a '/' match file names in
+/// any directory.
+fn pattern_regex(header: &str) -> String {
+  let glob = header.strip_prefix('/').unwrap_or(header);
+  let prefix = if header.contains('/') { "^" } else { "^(?:.*/)?" };
+  format!("{}{}$", prefix, glob_regex(glob))
+}
+
+/// translate `*`, `**`, `?`, `[abc]`, `[!abc]`, `{a,b}` and `{1..3}` into a regex.
+fn glob_regex(glob: &str) -> String {
+  let chars: Vec<char> = glob.chars().collect();
+  let mut regex = String::new();
+  let mut braces = 0;
+  let mut i = 0;
+  while i < chars.len() {
+    match chars[i] {
+      '*' if chars.get(i + 1) == Some(&'*') => {
+        regex.push_str(".*");
+        i += 1;
+      },
+      '*' => regex.push_str("[^/]*"),
+      '?' => regex.push_str("[^/]"),
+      '[' => match chars[i..].iter().position(|c| *c == ']') {
+        Some(len) => {
+          let class: String = chars[i + 1..i + len].iter().collect();
+          match class.strip_prefix('!') {
+            Some(class) => regex.push_str(&format!("[^{}]", class.replace('\\', "\\\\"))),
+            None => regex.push_str(&format!("[{}]", class.replace('\\', "\\\\"))),
+          }
+          i += len;
+        },
+        None => regex.push_str("\\["),
+      },
+      '{' => {
+        let rest: String = chars[i..].iter().collect();
+        if let Some(captures) = NUMERIC_RANGE.captures(&rest) {
+          let (from, to): (i64, i64) = (captures[1].parse().unwrap_or(0), captures[2].parse().unwrap_or(0));
+          let (from, to) = (from.min(to), from.max(to));
+          let numbers: Vec<String> = match to - from < MAX_RANGE {
+            true => (from..=to).map(|n| n.to_string()).collect(),
+            false => Vec::new(),
+          };
+          regex.push_str(&format!("(?:{})", numbers.join("|")));
+          i += captures[0].chars().count() - 1;
+        } else if rest.contains('}') {
+          braces += 1;
+          regex.push_str("(?:");
+        } else {
+          regex.push_str("\\{");
+        }
+      },
+      ',' if braces > 0 => regex.push('|'),
+      '}' if braces > 0 => {
+        braces -= 1;
+        regex.push(')');
+      },
+      '\\' if i + 1 < chars.len() => {
+        regex.push_str(&regex::escape(&chars[i + 1].to_string()));
+        i += 1;
+      },
+      char => regex.push_str(&regex::escape(&char.to_string())),
+    }
+    i += 1;
+  }
+  regex
+}
+
+/// the properties that apply to `path`, lowercased, with `unset` ones removed.
+pub fn properties(path: &Path) -> HashMap<String, String> {
+  let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+  let mut configs = Vec::new();
+  for dir in path.ancestors().skip(1) {
+    let Ok(text) = std::fs::read_to_string(dir.join(".editorconfig")) else { continue };
+    let config = parse(&text);
+    let root = config.root;
+    configs.push((dir, config));
+    if root {
+      break;
+    }
+  }
+  let mut properties = HashMap::new();
+  for (dir, config) in configs.into_iter().rev() {
+    let Ok(relative) = path.strip_prefix(dir) else { continue };
+    let relative = relative.to_string_lossy().replace('\\', "/");
+    let matching = config.sections.into_iter()
+      .filter(|section| section.pattern.as_ref().is_some_and(|pattern| pattern.is_match(&relative)));
+    for section in matching {
+      for (key, value) in section.properties {
+        properties.insert(key, value.to_lowercase());
+      }
+    }
+  }
+  properties.retain(|_, value| value != "unset");
+  properties
+}
+
+/// what an `.editorconfig` says about a file, in the editor's terms.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Settings {
+  pub indent_unit: Option<String>,
+  pub tab_width: Option<u32>,
+  pub line_ending: Option<&'static str>,
+  /// whether to write a byte order mark.
+  pub bom: Option<bool>,
+  pub trim_trailing_whitespace: Option<bool>,
+  pub insert_final_newline: Option<bool>,
+  pub max_line_length: Option<u32>,
+}
+
+impl Settings {
+  pub fn from_properties(properties: &HashMap<String, String>) -> Self {
+    let get = |key: &str| properties.get(key).map(String::as_str);
+    let number = |key: &str| get(key).and_then(|value| value.parse::<u32>().ok()).filter(|n| *n > 0);
+    let boolean = |key: &str| match get(key) {
+      Some("true") => Some(true),
+      Some("false") => Some(false),
+      _ => None,
+    };
+    let tab_width = number("tab_width").or_else(|| number("indent_size"));
+    let indent_size = match get("indent_size") {
+      Some("tab") => tab_width,
+      _ => number("indent_size").or(tab_width),
+    };
+    let indent_unit = match get("indent_style") {
+      Some("tab") => Some("\t".to_string()),
+      Some("space") | None => indent_size.map(|size| " ".repeat(size as usize)),
+      Some(other) => {
+        log!("editorconfig: unknown indent_style {:?}", other);
+        None
+      },
+    };
+    let line_ending = match get("end_of_line") {
+      Some("lf") => Some("\n"),
+      Some("crlf") => Some("\r\n"),
+      Some(other) => {
+        log!("editorconfig: end_of_line {:?} is not supported", other);
+        None
+      },
+      None => None,
+    };
+    let bom = match get("charset") {
+      Some("utf-8") => Some(false),
+      Some("utf-8-bom") => Some(true),
+      Some(other) => {
+        log!("editorconfig: charset {:?} is not supported, keeping utf-8", other);
+        None
+      },
+      None => None,
+    };
+    Self {
+      indent_unit,
+      tab_width,
+      line_ending,
+      bom,
+      trim_trailing_whitespace: boolean("trim_trailing_whitespace"),
+      insert_final_newline: boolean("insert_final_newline"),
+      max_line_length: number("max_line_length"),
+    }
+  }
+
+  pub fn for_path(path: &Path) -> Self {
+    Self::from_properties(&properties(path))
+  }
+
+  pub fn apply(&self, editor: &mut Context) {
+    if let Some(unit) = &self.indent_unit {
+      editor.set_indent_unit(unit);
+    }
+    if self.tab_width.is_some() {
+      editor.set_tab_width(self.tab_width);
+    }
+    if let Some(line_ending) = self.line_ending {
+      editor.set_line_ending(line_ending);
+    }
+    if let Some(bom) = self.bom {
+      editor.set_bom(bom);
+    }
+    let mut actions = editor.save_actions();
+    if let Some(trim) = self.trim_trailing_whitespace {
+      actions.trim_trailing_whitespace = trim;
+    }
+    if let Some(insert) = self.insert_final_newline {
+      actions.insert_final_newline = insert;
+    }
+    editor.set_save_actions(actions);
+    if let Some(max) = self.max_line_length {
+      editor.set_rulers(vec![max]);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_globs() {
+    let matches = |header: &str, path: &str| Regex::new(&pattern_regex(header)).unwrap().is_match(path);
+    assert!(matches("*", "src/main.rs"));
+    assert!(matches("*.{rs,toml}", "Cargo.toml"));
+    assert!(!matches("*.{rs,toml}", "README.md"));
+    assert!(matches("/src/*.rs", "src/main.rs"));
+    assert!(!matches("/src/*.rs", "src/editor/mod.rs"));
+    assert!(matches("src/**.rs", "src/editor/mod.rs"));
+    assert!(matches("file[0-9].txt", "a/file7.txt"));
+    assert!(!matches("file[!0-9].txt", "file7.txt"));
+    assert!(matches("v{1..12}.md", "v10.md"));
+    assert!(!matches("v{1..12}.md", "v13.md"));
+  }
+
+  #[test]
+  fn test_properties() {
+    let base = std::env::temp_dir().join(format!("pound-{}-editorconfig", std::process::id()));
+    std::fs::create_dir_all(base.join("inner")).unwrap();
+    std::fs::write(base.join(".editorconfig"), "root = true\n\n[*]\nindent_style = space\nindent_size = 2\nend_of_line = CRLF\n\n[*.md]\ntrim_trailing_whitespace = false\n").unwrap();
+    std::fs::write(base.join("inner/.editorconfig"), "[*.rs]\nindent_style = tab\ntab_width = 8\nend_of_line = unset\nmax_line_length = 100\n").unwrap();
+    let settings = Settings::for_path(&base.join("inner/lib.rs"));
+    assert_eq!(settings.indent_unit.as_deref(), Some("\t"));
+    assert_eq!(settings.tab_width, Some(8));
+    assert_eq!(settings.line_ending, None);
+    assert_eq!(settings.max_line_length, Some(100));
+    let settings = Settings::for_path(&base.join("inner/notes.md"));
+    assert_eq!(settings.indent_unit.as_deref(), Some("  "));
+    assert_eq!(settings.line_ending, Some("\r\n"));
+    assert_eq!(settings.trim_trailing_whitespace, Some(false));
+    std::fs::remove_dir_all(base).unwrap();
+  }
+}
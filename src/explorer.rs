@@ -0,0 +1,309 @@
+//! The file explorer sidebar's model: a tree of the working directory,
+//! honoring `.gitignore`, with the expanded directories flattened into rows.
+
+use std::{
+  collections::HashSet,
+  fs,
+  path::{Path, PathBuf},
+  time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{Context, Result, bail};
+use ignore::WalkBuilder;
+
+pub const DEFAULT_WIDTH: u16 = 30;
+pub const MIN_WIDTH: u16 = 12;
+/// how often expanded directories are checked for changes.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+  pub path: PathBuf,
+  pub name: String,
+  pub depth: usize,
+  pub is_dir: bool,
+}
+
+pub struct Explorer {
+  root: PathBuf,
+  expanded: HashSet<PathBuf>,
+  entries: Vec<Entry>,
+  pub selected: usize,
+  /// the first visible row.
+  pub scroll: usize,
+  pub width: u16,
+  pub visible: bool,
+  pub focused: bool,
+  /// whether the selected entry is waiting for a delete confirmation.
+  pub confirm_delete: bool,
+  /// modification times of the root and every expanded directory.
+  stamps: Vec<Option<SystemTime>>,
+  last_check: Instant,
+}
+
+impl Explorer {
+  /// nothing is read from disk until the sidebar is first shown.
+  pub fn new(root: &Path) -> Self {
+    Self {
+      root: root.to_path_buf(),
+      expanded: HashSet::new(),
+      entries: Vec::new(),
+      selected: 0,
+      scroll: 0,
+      width: DEFAULT_WIDTH,
+      visible: false,
+      focused: false,
+      confirm_delete: false,
+      stamps: Vec::new(),
+      last_check: Instant::now(),
+    }
+  }
+
+  pub fn entries(&self) -> &[Entry] {
+    &self.entries
+  }
+
+  /// `path` the way the entries spell it. relative paths are taken from the
+  /// root, the working directory, so nothing is looked up on disk.
+  pub fn entry_path(&self, path: &Path) -> PathBuf {
+    self.root.join(path)
+  }
+
+  pub fn is_expanded(&self, path: &Path) -> bool {
+    self.expanded.contains(path)
+  }
+
+  pub fn selected_entry(&self) -> Option<&Entry> {
+    self.entries.get(self.selected)
+  }
+
+  /// show and focus the sidebar, focus it if it is only shown, or hide it.
+  pub fn toggle(&mut self) {
+    if !self.visible {
+      self.visible = true;
+      self.focused = true;
+      self.refresh();
+    } else if !self.focused {
+      self.focused = true;
+    } else {
+      self.visible = false;
+      self.focused = false;
+    }
+  }
+
+  pub fn resize(&mut self, width: u16) {
+    self.width = width.max(MIN_WIDTH);
+  }
+
+  /// read the tree again, keeping the selection on the same path.
+  pub fn refresh(&mut self) {
+    let selected = self.selected_entry().map(|entry| entry.path.clone());
+    self.expanded.retain(|dir| dir.is_dir());
+    self.entries.clear();
+    list(&self.root, 0, &self.expanded, &mut self.entries);
+    self.stamps = self.watched().iter().map(|dir| modified(dir)).collect();
+    self.last_check = Instant::now();
+    if let Some(path) = selected {
+      self.select_path(&path);
+    }
+    self.selected = self.selected.min(self.entries.len().saturating_sub(1));
+  }
+
+  /// refresh when a watched directory changed on disk.
+  pub fn poll(&mut self) {
+    if !self.visible || self.last_check.elapsed() < REFRESH_INTERVAL {
+      return;
+    }
+    self.last_check = Instant::now();
+    let stamps: Vec<Option<SystemTime>> = self.watched().iter().map(|dir| modified(dir)).collect();
+    if stamps != self.stamps {
+      self.refresh();
+    }
+  }
+
+  fn watched(&self) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = self.expanded.iter().cloned().collect();
+    dirs.sort();
+    dirs.insert(0, self.root.clone());
+    dirs
+  }
+
+  pub fn select_path(&mut self, path: &Path) {
+    if let Some(index) = self.entries.iter().position(|entry| entry.path == path) {
+      self.selected = index;
+    }
+  }
+
+  pub fn up(&mut self) {
+    self.selected = self.selected.saturating_sub(1);
+  }
+
+  pub fn down(&mut self) {
+    if self.selected + 1 < self.entries.len() {
+      self.selected += 1;
+    }
+  }
+
+  /// keep the selection within `height` rows.
+  pub fn scroll_to_selected(&mut self, height: usize) {
+    if self.selected < self.scroll {
+      self.scroll = self.selected;
+    } else if height > 0 && self.selected >= self.scroll + height {
+      self.scroll = self.selected + 1 - height;
+    }
+  }
+
+  /// expand or collapse the selected directory, or return the selected file to open it.
+  pub fn activate(&mut self) -> Option<PathBuf> {
+    let entry = self.selected_entry()?.clone();
+    if !entry.is_dir {
+      return Some(entry.path);
+    }
+    if !self.expanded.remove(&entry.path) {
+      self.expanded.insert(entry.path);
+    }
+    self.refresh();
+    None
+  }
+
+  /// collapse the selected directory, or move to the parent directory.
+  pub fn collapse(&mut self) {
+    let Some(entry) = self.selected_entry().cloned() else { return };
+    if entry.is_dir && self.expanded.remove(&entry.path) {
+      self.refresh();
+    } else if let Some(parent) = entry.path.parent() {
+      self.select_path(parent);
+    }
+  }
+
+  /// the directory new files go into: the selected directory, or the one the selected file is in.
+  fn target_dir(&self) -> PathBuf {
+    match self.selected_entry() {
+      Some(entry) if entry.is_dir => entry.path.clone(),
+      Some(entry) => entry.path.parent().unwrap_or(&self.root).to_path_buf(),
+      None => self.root.clone(),
+    }
+  }
+
+  pub fn create(&mut self, name: &str) -> Result<PathBuf> {
+    if name.trim().is_empty() {
+      bail!("no name given");
+    }
+    let dir = self.target_dir();
+    let path = dir.join(name.trim_end_matches('/'));
+    if path.exists() {
+      bail!("{} already exists", path.display());
+    }
+    if name.ends_with('/') {
+      fs::create_dir_all(&path).with_context(|| format!("failed to create {}", path.display()))?;
+    } else {
+      if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("failed to create {}", parent.display()))?;
+      }
+      fs::File::create_new(&path).with_context(|| format!("failed to create {}", path.display()))?;
+    }
+    if dir != self.root {
+      self.expanded.insert(dir);
+    }
+    self.refresh();
+    self.select_path(&path);
+    Ok(path)
+  }
+
+  /// rename the selected entry, keeping it in its directory.
+  pub fn rename(&mut self, name: &str) -> Result<PathBuf> {
+    let Some(entry) = self.selected_entry().cloned() else { bail!("nothing selected") };
+    if name.trim().is_empty() {
+      bail!("no name given");
+    }
+    let path = entry.path.parent().unwrap_or(&self.root).join(name);
+    if path.exists() {
+      bail!("{} already exists", path.display());
+    }
+    fs::rename(&entry.path, &path).with_context(|| format!("failed to rename {}", entry.path.display()))?;
+    if self.expanded.remove(&entry.path) {
+      self.expanded.insert(path.clone());
+    }
+    self.refresh();
+    self.select_path(&path);
+    Ok(path)
+  }
+
+  pub fn delete(&mut self) -> Result<PathBuf> {
+    let Some(entry) = self.selected_entry().cloned() else { bail!("nothing selected") };
+    if entry.is_dir {
+      fs::remove_dir_all(&entry.path)
+    } else {
+      fs::remove_file(&entry.path)
+    }.with_context(|| format!("failed to delete {}", entry.path.display()))?;
+    self.refresh();
+    Ok(entry.path)
+  }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+  fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// append the entries of `dir`, directories first, descending into expanded ones.
+fn list(dir: &Path, depth: usize, expanded: &HashSet<PathBuf>, entries: &mut Vec<Entry>) {
+  let mut children: Vec<Entry> = WalkBuilder::new(dir)
+    .max_depth(Some(1))
+    .hidden(false)
+    .require_git(false)
+    .filter_entry(|entry| entry.file_name() != ".git")
+    .build()
+    .filter_map(|entry| entry.ok())
+    .filter(|entry| entry.depth() == 1)
+    .map(|entry| Entry {
+      name: entry.file_name().to_string_lossy().to_string(),
+      is_dir: entry.file_type().is_some_and(|t| t.is_dir()),
+      path: entry.into_path(),
+      depth,
+    })
+    .collect();
+  children.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+  for child in children {
+    let descend = child.is_dir && expanded.contains(&child.path);
+    let path = child.path.clone();
+    entries.push(child);
+    if descend {
+      list(&path, depth + 1, expanded, entries);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_explorer() {
+    let root = std::env::temp_dir().join(format!("pound-{}-explorer", std::process::id()));
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::write(root.join("src/main.rs"), "").unwrap();
+    fs::write(root.join("notes.txt"), "").unwrap();
+    fs::write(root.join("debug.log"), "").unwrap();
+    fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+    let mut explorer = Explorer::new(&root);
+    explorer.toggle();
+    let names: Vec<&str> = explorer.entries().iter().map(|e| e.name.as_str()).collect();
+    assert_eq!(names, ["src", ".gitignore", "notes.txt"]);
+    assert_eq!(explorer.activate(), None);
+    assert_eq!(explorer.entries()[1].name, "main.rs");
+    assert_eq!(explorer.entries()[1].depth, 1);
+    explorer.down();
+    assert_eq!(explorer.activate(), Some(root.join("src/main.rs")));
+    let created = explorer.create("lib.rs").unwrap();
+    assert_eq!(created, root.join("src/lib.rs"));
+    assert_eq!(explorer.selected_entry().unwrap().path, created);
+    explorer.rename("mod.rs").unwrap();
+    assert!(root.join("src/mod.rs").exists());
+    explorer.delete().unwrap();
+    assert!(!root.join("src/mod.rs").exists());
+    explorer.select_path(&root.join("src/main.rs"));
+    explorer.collapse();
+    assert_eq!(explorer.selected_entry().unwrap().name, "src");
+    fs::remove_dir_all(root).unwrap();
+  }
+}
@@ -0,0 +1,241 @@
+//! The Ctrl+P file finder: a background index of the files under the working
+//! directory, fuzzy matched against the typed query.
+
+use std::{
+  path::{Path, PathBuf},
+  sync::{Arc, RwLock, atomic::{AtomicBool, Ordering}},
+  thread,
+};
+
+use ignore::WalkBuilder;
+
+use crate::{editor::location::Location, fuzzy};
+
+/// at most this many matches are kept.
+const MAX_RESULTS: usize = 200;
+/// how many lines of the highlighted file are previewed.
+const PREVIEW_LINES: usize = 200;
+/// added when the whole query matches within the file name.
+const FILE_NAME_BONUS: i64 = 15;
+
+/// paths relative to the root, filled in by a background thread.
+#[derive(Clone)]
+pub struct FileIndex {
+  files: Arc<RwLock<Vec<String>>>,
+  done: Arc<AtomicBool>,
+}
+
+impl FileIndex {
+  pub fn spawn(root: &Path) -> Self {
+    let index = Self { files: Arc::new(RwLock::new(Vec::new())), done: Arc::new(AtomicBool::new(false)) };
+    let (files, done, root) = (index.files.clone(), index.done.clone(), root.to_path_buf());
+    thread::spawn(move || {
+      let mut batch = Vec::new();
+      let walk = WalkBuilder::new(&root)
+        .hidden(false)
+        .require_git(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build();
+      for entry in walk.filter_map(|entry| entry.ok()) {
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+          continue;
+        }
+        if let Ok(relative) = entry.path().strip_prefix(&root) {
+          batch.push(relative.to_string_lossy().to_string());
+        }
+        if batch.len() >= 1024 {
+          files.write().unwrap().append(&mut batch);
+        }
+      }
+      files.write().unwrap().append(&mut batch);
+      done.store(true, Ordering::Release);
+    });
+    index
+  }
+
+  pub fn len(&self) -> usize {
+    self.files.read().unwrap().len()
+  }
+
+  pub fn is_done(&self) -> bool {
+    self.done.load(Ordering::Acquire)
+  }
+}
+
+/// a matched file, its score, and the char indices that matched.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Match {
+  pub path: String,
+  pub score: i64,
+  pub positions: Vec<usize>,
+}
+
+/// score `path`, favoring matches inside the file name over ones spread across directories.
+pub fn rank(query: &str, path: &str) -> Option<Match> {
+  let (score, positions) = fuzzy::match_positions(query, path)?;
+  let name_start = path.rfind('/').map_or(0, |slash| slash + 1);
+  let name_offset = path[..name_start].chars().count();
+  let (score, positions) = match fuzzy::match_positions(query, &path[name_start..]) {
+    Some((name_score, name_positions)) if name_score + FILE_NAME_BONUS > score => {
+      (name_score + FILE_NAME_BONUS, name_positions.into_iter().map(|p| p + name_offset).collect())
+    },
+    _ => (score, positions),
+  };
+  Some(Match { path: path.to_string(), score, positions })
+}
+
+/// split an optional `:line` or `:line:col` suffix, one based, off the query.
+pub fn parse_query(query: &str) -> (&str, Option<Location>) {
+  let mut parts = query.rsplitn(3, ':');
+  let last = parts.next().unwrap_or_default();
+  let middle = parts.next();
+  let first = parts.next();
+  let number = |text: &str| text.parse::<u32>().ok().filter(|n| *n > 0);
+  match (first, middle.and_then(number), number(last)) {
+    (Some(path), Some(ln), Some(col)) => (path, Some(Location { ln: ln - 1, col: col - 1 })),
+    _ => match (middle, number(last)) {
+      (Some(_), Some(ln)) => {
+        let path = &query[..query.len() - last.len() - 1];
+        (path, Some(Location { ln: ln - 1, col: 0 }))
+      },
+      _ => (query, None),
+    },
+  }
+}
+
+pub struct Finder {
+  root: PathBuf,
+  index: FileIndex,
+  pub query: String,
+  matches: Vec<Match>,
+  pub selected: usize,
+  /// how many files were indexed when `matches` was computed.
+  matched_len: usize,
+  preview: Option<(String, Vec<String>)>,
+}
+
+impl Finder {
+  pub fn new(root: &Path, index: FileIndex) -> Self {
+    let mut finder = Self {
+      root: root.to_path_buf(),
+      index,
+      query: String::new(),
+      matches: Vec::new(),
+      selected: 0,
+      matched_len: 0,
+      preview: None,
+    };
+    finder.refilter();
+    finder
+  }
+
+  pub fn matches(&self) -> &[Match] {
+    &self.matches
+  }
+
+  pub fn is_indexing(&self) -> bool {
+    !self.index.is_done()
+  }
+
+  pub fn indexed(&self) -> usize {
+    self.index.len()
+  }
+
+  /// pick up files the indexer found since the last match.
+  pub fn update(&mut self) {
+    if self.index.len() != self.matched_len {
+      self.refilter();
+    }
+  }
+
+  pub fn set_query(&mut self, query: String) {
+    self.query = query;
+    self.selected = 0;
+    self.refilter();
+  }
+
+  fn refilter(&mut self) {
+    let (query, _) = parse_query(&self.query);
+    let files = self.index.files.read().unwrap();
+    let mut matches: Vec<Match> = files.iter().filter_map(|path| rank(query, path)).collect();
+    // best first, then shorter paths, then alphabetical
+    matches.sort_by(|a, b| b.score.cmp(&a.score).then(a.path.len().cmp(&b.path.len())).then(a.path.cmp(&b.path)));
+    matches.truncate(MAX_RESULTS);
+    self.matched_len = files.len();
+    self.matches = matches;
+    self.selected = self.selected.min(self.matches.len().saturating_sub(1));
+  }
+
+  pub fn up(&mut self) {
+    self.selected = self.selected.saturating_sub(1);
+  }
+
+  pub fn down(&mut self) {
+    if self.selected + 1 < self.matches.len() {
+      self.selected += 1;
+    }
+  }
+
+  /// the highlighted file and where to put the cursor in it.
+  pub fn selection(&self) -> Option<(PathBuf, Option<Location>)> {
+    let found = self.matches.get(self.selected)?;
+    let (_, location) = parse_query(&self.query);
+    Some((self.root.join(&found.path), location))
+  }
+
+  /// the first lines of the highlighted file. binary files are not shown.
+  pub fn preview(&mut self) -> &[String] {
+    let Some(found) = self.matches.get(self.selected) else { return &[] };
+    if self.preview.as_ref().map(|(path, _)| path) != Some(&found.path) {
+      let lines = std::fs::read(self.root.join(&found.path))
+        .ok()
+        .filter(|bytes| !bytes.iter().take(8192).any(|b| *b == 0))
+        .map(|bytes| String::from_utf8_lossy(&bytes).lines().take(PREVIEW_LINES).map(str::to_string).collect())
+        .unwrap_or_default();
+      self.preview = Some((found.path.clone(), lines));
+    }
+    self.preview.as_ref().map(|(_, lines)| lines.as_slice()).unwrap_or_default()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_rank() {
+    let name = rank("main", "src/main.rs").unwrap();
+    let spread = rank("main", "src/mod/ai/new.rs").unwrap();
+    assert!(name.score > spread.score);
+    assert_eq!(name.positions, vec![4, 5, 6, 7]);
+    assert!(rank("sm", "src/main.rs").is_some());
+    assert!(rank("xyz", "src/main.rs").is_none());
+  }
+
+  #[test]
+  fn test_parse_query() {
+    assert_eq!(parse_query("main.rs"), ("main.rs", None));
+    assert_eq!(parse_query("main.rs:12"), ("main.rs", Some(Location { ln: 11, col: 0 })));
+    assert_eq!(parse_query("main.rs:12:3"), ("main.rs", Some(Location { ln: 11, col: 2 })));
+    assert_eq!(parse_query("a:b"), ("a:b", None));
+    assert_eq!(parse_query("main.rs:"), ("main.rs:", None));
+  }
+
+  #[test]
+  fn test_finder() {
+    let root = std::env::temp_dir().join(format!("pound-{}-finder", std::process::id()));
+    std::fs::create_dir_all(root.join("src")).unwrap();
+    std::fs::write(root.join("src/lib.rs"), "pub fn lib() {}\n").unwrap();
+    std::fs::write(root.join("README"), "readme\n").unwrap();
+    let index = FileIndex::spawn(&root);
+    while !index.is_done() {
+      thread::sleep(std::time::Duration::from_millis(1));
+    }
+    let mut finder = Finder::new(&root, index);
+    assert_eq!(finder.matches().len(), 2);
+    finder.set_query("lib:1:5".to_string());
+    assert_eq!(finder.selection(), Some((root.join("src/lib.rs"), Some(Location { ln: 0, col: 4 }))));
+    assert_eq!(finder.preview(), ["pub fn lib() {}"]);
+    std::fs::remove_dir_all(root).unwrap();
+  }
+}
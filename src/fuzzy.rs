@@ -0,0 +1,87 @@
+//! Fuzzy matching: the query's characters have to appear in the candidate in
+//! order, and matches at word boundaries and in runs score higher.
+
+const MATCH: i64 = 1;
+const CONSECUTIVE: i64 = 5;
+const BOUNDARY: i64 = 10;
+const FIRST_CHAR: i64 = 10;
+const GAP: i64 = 1;
+
+/// score `candidate` against `query`, case-insensitively. `None` when it doesn't match.
+pub fn score(query: &str, candidate: &str) -> Option<i64> {
+  Some(match_positions(query, candidate)?.0)
+}
+
+/// the score and the char indices of `candidate` that matched.
+pub fn match_positions(query: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+  let chars: Vec<char> = candidate.chars().collect();
+  let query: Vec<char> = query.chars().map(lower).collect();
+  // the last index each query char can match at with the rest still fitting after it
+  let mut latest = vec![0; query.len()];
+  let mut end = chars.len();
+  for (k, q) in query.iter().enumerate().rev() {
+    end = (0..end).rev().find(|i| lower(chars[*i]) == *q)?;
+    latest[k] = end;
+  }
+  let mut positions = Vec::with_capacity(query.len());
+  let mut score = 0;
+  let mut from = 0;
+  for (k, q) in query.into_iter().enumerate() {
+    // prefer the next boundary match over the next plain one
+    let plain = (from..chars.len()).find(|i| lower(chars[*i]) == q)?;
+    let boundary = (plain..=latest[k]).find(|i| lower(chars[*i]) == q && is_boundary(&chars, *i));
+    let index = match boundary {
+      Some(index) if positions.last().map(|last| last + 1) != Some(plain) => index,
+      _ => plain,
+    };
+    score += MATCH;
+    if index == 0 {
+      score += FIRST_CHAR;
+    }
+    if is_boundary(&chars, index) {
+      score += BOUNDARY;
+    }
+    match positions.last() {
+      Some(last) if *last + 1 == index => score += CONSECUTIVE,
+      Some(last) => score -= GAP * (index - last - 1).min(10) as i64,
+      None => score -= GAP * index.min(10) as i64,
+    }
+    positions.push(index);
+    from = index + 1;
+  }
+  // shorter candidates win ties
+  score -= (chars.len() as i64 - positions.len() as i64).min(20) / 4;
+  Some((score, positions))
+}
+
+fn lower(char: char) -> char {
+  char.to_lowercase().next().unwrap_or(char)
+}
+
+/// the start of a word: the first char, a char after a separator, or an
+/// uppercase char after a lowercase one.
+pub fn is_boundary(chars: &[char], index: usize) -> bool {
+  if index == 0 {
+    return true;
+  }
+  let previous = chars[index - 1];
+  matches!(previous, '_' | '-' | '.' | '/' | '\\' | ' ' | ':')
+    || (previous.is_lowercase() && chars[index].is_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_score() {
+    assert_eq!(score("xyz", "abc"), None);
+    assert!(score("", "abc").is_some());
+    assert!(score("fb", "foo_bar") > score("fb", "fooxbar"));
+    assert!(score("gl", "get_line") > score("gl", "global"));
+    assert!(score("fb", "fooBar") > score("fb", "foobar"));
+    assert_eq!(match_positions("gln", "get_line").unwrap().1, vec![0, 4, 6]);
+    // a later boundary match must leave room for the rest of the query
+    assert_eq!(match_positions("ab", "xab_a").unwrap().1, vec![1, 2]);
+  }
+}
@@ -0,0 +1,268 @@
+//! Compare a document with the version of its file in git's HEAD, for the
+//! change markers in the gutter.
+
+use std::{
+  path::Path,
+  process::Command,
+  sync::{Arc, mpsc::{self, Receiver}},
+  thread,
+  time::{Duration, Instant},
+};
+
+use crate::editor::document::Document;
+
+/// how long the document has to stay unchanged before it is diffed again.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+/// the longest edit script `diff` looks for. the search keeps a row per
+/// edit, so this bounds its time and memory.
+const MAX_EDITS: usize = 500;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HunkKind {
+  Added,
+  Modified,
+  Removed,
+}
+
+/// a run of changed lines. `new_start` and `new_len` count lines of the
+/// document, `old_lines` are the lines they replaced, '\n' included.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hunk {
+  pub new_start: u32,
+  pub new_len: u32,
+  pub old_lines: Vec<String>,
+}
+
+impl Hunk {
+  pub fn kind(&self) -> HunkKind {
+    if self.old_lines.is_empty() {
+      HunkKind::Added
+    } else if self.new_len == 0 {
+      HunkKind::Removed
+    } else {
+      HunkKind::Modified
+    }
+  }
+
+  /// whether the hunk's marker is on line `ln`. removed lines are marked on
+  /// the line above them.
+  pub fn marks(&self, ln: u32) -> bool {
+    match self.kind() {
+      HunkKind::Removed => ln == self.new_start.saturating_sub(1),
+      _ => self.new_start <= ln && ln < self.new_start + self.new_len,
+    }
+  }
+}
+
+/// the contents of `path` in HEAD, or `None` when it is not tracked by git.
+pub fn head_text(path: &Path) -> Option<String> {
+  let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+  let name = path.file_name()?.to_string_lossy();
+  let output = Command::new("git")
+    .arg("-C")
+    .arg(dir)
+    .arg("show")
+    .arg(format!("HEAD:./{}", name))
+    .output()
+    .ok()?;
+  if !output.status.success() {
+    return None;
+  }
+  String::from_utf8(output.stdout).ok()
+}
+
+/// the hunks turning `old` into `new`, found with Myers' O(ND) algorithm.
+/// texts further apart than `MAX_EDITS` come back as one hunk, everything
+/// between their common start and end modified.
+pub fn diff(old: &[&str], new: &[&str]) -> Vec<Hunk> {
+  // common prefix and suffix never end up in a hunk, so skip them up front
+  let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+  let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+  let a = &old[prefix..old.len() - suffix];
+  let b = &new[prefix..new.len() - suffix];
+  let (n, m) = (a.len() as isize, b.len() as isize);
+  let max = ((n + m) as usize).min(MAX_EDITS);
+  let offset = max as isize + 1;
+  let mut v = vec![0isize; 2 * max + 3];
+  let mut trace = Vec::new();
+  let mut found = false;
+  'search: for d in 0..=max as isize {
+    trace.push(v.clone());
+    for k in (-d..=d).step_by(2) {
+      let index = (k + offset) as usize;
+      let mut x = if k == -d || (k != d && v[index - 1] < v[index + 1]) {
+        v[index + 1]
+      } else {
+        v[index - 1] + 1
+      };
+      let mut y = x - k;
+      while x < n && y < m && a[x as usize] == b[y as usize] {
+        x += 1;
+        y += 1;
+      }
+      v[index] = x;
+      if x >= n && y >= m {
+        found = true;
+        break 'search;
+      }
+    }
+  }
+  if !found {
+    return vec![Hunk {
+      new_start: prefix as u32,
+      new_len: b.len() as u32,
+      old_lines: a.iter().map(|line| line.to_string()).collect(),
+    }];
+  }
+  // walk the trace back, collecting which lines on each side are unchanged
+  let mut same = Vec::new();
+  let (mut x, mut y) = (n, m);
+  for (d, v) in trace.iter().enumerate().rev() {
+    let d = d as isize;
+    let k = x - y;
+    let index = (k + offset) as usize;
+    let previous_k = if k == -d || (k != d && v[index - 1] < v[index + 1]) { k + 1 } else { k - 1 };
+    let previous_x = v[(previous_k + offset) as usize];
+    let previous_y = previous_x - previous_k;
+    while x > previous_x && y > previous_y {
+      x -= 1;
+      y -= 1;
+      same.push((x as usize, y as usize));
+    }
+    if d > 0 {
+      x = previous_x;
+      y = previous_y;
+    }
+  }
+  same.reverse();
+  same.push((a.len(), b.len()));
+  let mut hunks = Vec::new();
+  let (mut old_at, mut new_at) = (0, 0);
+  for (old_same, new_same) in same {
+    if old_same > old_at || new_same > new_at {
+      hunks.push(Hunk {
+        new_start: (prefix + new_at) as u32,
+        new_len: (new_same - new_at) as u32,
+        old_lines: a[old_at..old_same].iter().map(|line| line.to_string()).collect(),
+      });
+    }
+    old_at = old_same + 1;
+    new_at = new_same + 1;
+  }
+  hunks
+}
+
+/// the changes of one document against HEAD, refreshed a little after edits.
+/// the diff runs on a background thread, the hunks stay those of the last
+/// finished one meanwhile.
+pub struct Gutter {
+  base: Arc<str>,
+  hunks: Vec<Hunk>,
+  /// the document version the hunks were computed for.
+  diffed: Option<u64>,
+  /// the last version seen, and when it was first seen.
+  seen: (u64, Instant),
+  /// the diff in progress, and the version it is for.
+  running: Option<(u64, Receiver<Vec<Hunk>>)>,
+}
+
+impl Gutter {
+  /// start tracking `path`, if git knows it.
+  pub fn open(path: &Path) -> Option<Self> {
+    Some(Self {
+      base: head_text(path)?.into(),
+      hunks: Vec::new(),
+      diffed: None,
+      seen: (0, Instant::now() - DEBOUNCE),
+      running: None,
+    })
+  }
+
+  /// take the hunks of a finished diff, and diff again once the document
+  /// has stopped changing for a moment.
+  pub fn update(&mut self, document: &Document) {
+    if let Some((version, receiver)) = &self.running {
+      if let Ok(hunks) = receiver.try_recv() {
+        self.hunks = hunks;
+        self.diffed = Some(*version);
+        self.running = None;
+      }
+    }
+    let version = document.version();
+    if self.seen.0 != version {
+      self.seen = (version, Instant::now());
+    }
+    let pending = self.running.as_ref().map(|(version, _)| *version).or(self.diffed);
+    if pending != Some(version) && self.seen.1.elapsed() >= DEBOUNCE {
+      self.refresh(document);
+    }
+  }
+
+  /// start diffing right away, in place of any diff still running.
+  pub fn refresh(&mut self, document: &Document) {
+    let (sender, receiver) = mpsc::channel();
+    let base = self.base.clone();
+    let text = document.text();
+    thread::spawn(move || {
+      let old: Vec<&str> = base.split_inclusive('\n').collect();
+      let new: Vec<&str> = text.split_inclusive('\n').collect();
+      let _ = sender.send(diff(&old, &new));
+    });
+    self.running = Some((document.version(), receiver));
+  }
+
+  /// whether the hunks are those of `document` as it is now.
+  pub fn is_current(&self, document: &Document) -> bool {
+    self.diffed == Some(document.version())
+  }
+
+  pub fn hunks(&self) -> &[Hunk] {
+    &self.hunks
+  }
+
+  pub fn hunk_at(&self, ln: u32) -> Option<&Hunk> {
+    self.hunks.iter().find(|hunk| hunk.marks(ln))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_diff() {
+    let old = ["a\n", "b\n", "c\n", "d\n"];
+    assert!(diff(&old, &old).is_empty());
+    let hunks = diff(&old, &["a\n", "x\n", "c\n", "d\n", "e\n"]);
+    assert_eq!(hunks, vec![
+      Hunk { new_start: 1, new_len: 1, old_lines: vec!["b\n".to_string()] },
+      Hunk { new_start: 4, new_len: 1, old_lines: vec![] },
+    ]);
+    assert_eq!(hunks[0].kind(), HunkKind::Modified);
+    assert_eq!(hunks[1].kind(), HunkKind::Added);
+    let hunks = diff(&old, &["a\n", "d\n"]);
+    assert_eq!(hunks, vec![Hunk { new_start: 1, new_len: 0, old_lines: vec!["b\n".to_string(), "c\n".to_string()] }]);
+    assert_eq!(hunks[0].kind(), HunkKind::Removed);
+    assert!(hunks[0].marks(0));
+  }
+
+  #[test]
+  fn test_diff_interleaved() {
+    let hunks = diff(&["a", "b", "c", "a", "b", "b", "a"], &["c", "b", "a", "b", "a", "c"]);
+    let changed_old: usize = hunks.iter().map(|hunk| hunk.old_lines.len()).sum();
+    let changed_new: u32 = hunks.iter().map(|hunk| hunk.new_len).sum();
+    // the shortest edit script for this classic example has 5 edits
+    assert_eq!(changed_old + changed_new as usize, 5);
+  }
+
+  #[test]
+  fn test_diff_too_different() {
+    let old: Vec<String> = (0..400).map(|ln| format!("a{}\n", ln)).collect();
+    let new: Vec<String> = (0..400).map(|ln| format!("b{}\n", ln)).collect();
+    let old: Vec<&str> = ["head\n"].into_iter().chain(old.iter().map(String::as_str)).collect();
+    let new: Vec<&str> = ["head\n"].into_iter().chain(new.iter().map(String::as_str)).collect();
+    let hunks = diff(&old, &new);
+    assert_eq!(hunks.len(), 1);
+    assert_eq!((hunks[0].new_start, hunks[0].new_len, hunks[0].old_lines.len()), (1, 400, 400));
+  }
+}
@@ -0,0 +1,68 @@
+//! Parsing what was typed into the goto prompt.
+
+use anyhow::{Result, bail};
+
+use crate::editor::location::Location;
+
+/// shown in front of what is typed into the goto prompt.
+pub const LABEL: &str = "goto line[:col], +N, -N or N%: ";
+
+/// where `input` points: `line`, `line:col`, `+N`/`-N` lines from `current`,
+/// or `N%` of the way through. lines and columns are one based.
+pub fn parse(input: &str, current: Location, line_count: usize) -> Result<Location> {
+  let input = input.trim();
+  let last = line_count.saturating_sub(1) as i64;
+  let number = |text: &str| -> Result<i64> {
+    match text.parse::<i64>() {
+      Ok(number) => Ok(number),
+      Err(_) => bail!("not a number: {:?}", text),
+    }
+  };
+  let (ln, col) = if let Some(percent) = input.strip_suffix('%') {
+    let percent = number(percent)?;
+    if !(0..=100).contains(&percent) {
+      bail!("{}% is not between 0% and 100%", percent);
+    }
+    (last * percent / 100, 0)
+  } else if input.starts_with('+') || input.starts_with('-') {
+    let ln = current.ln as i64 + number(input)?;
+    if !(0..=last).contains(&ln) {
+      bail!("line {} is outside the document ({} lines)", ln + 1, line_count);
+    }
+    (ln, current.col as i64)
+  } else {
+    let (ln, col) = match input.split_once(':') {
+      Some((ln, col)) => (number(ln)?, number(col)?),
+      None => (number(input)?, 1),
+    };
+    if ln < 1 || ln > last + 1 {
+      bail!("line {} is outside the document ({} lines)", ln, line_count);
+    }
+    if col < 1 {
+      bail!("columns start at 1");
+    }
+    (ln - 1, col - 1)
+  };
+  Ok(Location { ln: ln as u32, col: col as u32 })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse() {
+    let current = Location { ln: 10, col: 3 };
+    assert_eq!(parse("5", current, 100).unwrap(), Location { ln: 4, col: 0 });
+    assert_eq!(parse("5:7", current, 100).unwrap(), Location { ln: 4, col: 6 });
+    assert_eq!(parse("+5", current, 100).unwrap(), Location { ln: 15, col: 3 });
+    assert_eq!(parse("-10", current, 100).unwrap(), Location { ln: 0, col: 3 });
+    assert_eq!(parse("50%", current, 101).unwrap(), Location { ln: 50, col: 0 });
+    assert_eq!(parse("100%", current, 101).unwrap(), Location { ln: 100, col: 0 });
+    assert!(parse("101", current, 100).is_err());
+    assert!(parse("0", current, 100).is_err());
+    assert!(parse("-11", current, 100).is_err());
+    assert!(parse("abc", current, 100).is_err());
+    assert!(parse("150%", current, 100).is_err());
+  }
+}
@@ -1,17 +1,360 @@
-use crossterm::event::{Event, KeyCode};
-use crate::{state::AppState, macros::log};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use crate::{state::{AppState, QuitChoice}, macros::log, widget::scrollbar, editor::position::Position, prompt::Prompt, search::Field};
+
+/// lines or columns one notch of the mouse wheel scrolls.
+const WHEEL_STEP: usize = 3;
 
 pub fn event_handler(event: Event, state: &mut AppState) {
+  if let Event::Mouse(mouse) = event {
+    if sidebar_mouse(mouse, state) || scrollbar_mouse(mouse, state) {
+      return;
+    }
+    if let MouseEventKind::ScrollDown | MouseEventKind::ScrollUp = mouse.kind {
+      return edit_area_wheel(mouse, state);
+    }
+    if let MouseEventKind::Down(MouseButton::Left) = mouse.kind {
+      let area = state.edit_area;
+      if mouse.row < area.top() || mouse.row >= area.bottom() || mouse.column >= area.right() {
+        return;
+      }
+      // a click in the gutter opens or closes the fold on that line
+      if mouse.column < area.left() {
+        if let Some(ln) = state.editor.visible_lines().get((mouse.row - area.top()) as usize) {
+          state.editor.toggle_fold(*ln);
+        }
+        return;
+      }
+      let position = Position::new((mouse.column - area.left()) as u32, (mouse.row - area.top()) as u32);
+      let location = state.editor.location_at_visible(position);
+      state.completion = None;
+      state.explorer.focused = false;
+      state.search_focused = false;
+      if mouse.modifiers.contains(KeyModifiers::ALT) {
+        state.editor.add_cursor(location);
+      } else {
+        state.editor.set_cursor(location);
+      }
+    }
+  }
+  if let Event::FocusLost = event {
+    state.autosave_now();
+  }
   if let Event::Key(key) = event {
     log!("{:?}", key);
+    if let Some(choice) = state.quit_dialog {
+      match key.code {
+        KeyCode::Left | KeyCode::BackTab => state.quit_dialog = Some(choice.previous()),
+        KeyCode::Right | KeyCode::Tab => state.quit_dialog = Some(choice.next()),
+        KeyCode::Enter => state.answer_quit_dialog(choice),
+        KeyCode::Char('s') => state.answer_quit_dialog(QuitChoice::Save),
+        KeyCode::Char('d') => state.answer_quit_dialog(QuitChoice::Discard),
+        KeyCode::Char('c') | KeyCode::Esc => state.answer_quit_dialog(QuitChoice::Cancel),
+        _ => {},
+      }
+      return;
+    }
+    if state.prompt.is_some() {
+      return prompt_key(key, state);
+    }
+    if let Some(finder) = state.finder.as_mut() {
+      match key.code {
+        KeyCode::Esc => state.finder = None,
+        KeyCode::Enter => state.accept_finder(),
+        KeyCode::Up => finder.up(),
+        KeyCode::Down => finder.down(),
+        KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => finder.up(),
+        KeyCode::Char('n') if key.modifiers.contains(KeyModifiers::CONTROL) => finder.down(),
+        KeyCode::Backspace => {
+          let mut query = finder.query.clone();
+          query.pop();
+          finder.set_query(query);
+        },
+        KeyCode::Char(char) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+          let query = format!("{}{}", finder.query, char);
+          finder.set_query(query);
+        },
+        _ => {},
+      }
+      return;
+    }
+    if state.recovery.is_some() {
+      match key.code {
+        KeyCode::Char(char) if !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => state.answer_recovery(char),
+        _ => {},
+      }
+      return;
+    }
+    if state.disk_conflict.is_some() {
+      match key.code {
+        KeyCode::Char(char) if !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => state.answer_disk_conflict(char),
+        _ => {},
+      }
+      return;
+    }
+    if let Some(command) = state.keymap.get(&key) {
+      return state.run_command(command);
+    }
+    if state.explorer.focused {
+      return explorer_key(key, state);
+    }
+    if state.search_focused && state.search_panel.is_some() {
+      return search_key(key, state);
+    }
+    state.message = None;
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+      match key.code {
+        KeyCode::Char('r') => state.editor.reindent_selection(),
+        KeyCode::Char('b') => state.editor.jump_to_matching_bracket(),
+        KeyCode::Char('d') => state.editor.select_next_occurrence(),
+        KeyCode::Char('z') => state.editor.undo(),
+        KeyCode::Char('y') => state.editor.redo(),
+        KeyCode::Char('K') => state.editor.delete_line(),
+        KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::SHIFT) => state.editor.delete_line(),
+        KeyCode::Char('k') => state.editor.kill_to_end_of_line(),
+        KeyCode::Backspace | KeyCode::Char('h') => state.editor.delete_word_backward(),
+        KeyCode::Delete => state.editor.delete_word_forward(),
+        KeyCode::Char(' ') => state.open_completion(),
+        _ => {},
+      }
+      state.update_completion();
+      return;
+    }
+    if let Some(completion) = state.completion.as_mut() {
+      match key.code {
+        KeyCode::Up => return completion.previous(),
+        KeyCode::Down => return completion.next(),
+        KeyCode::Tab | KeyCode::Enter => return state.accept_completion(),
+        KeyCode::Esc => {
+          state.completion = None;
+          return;
+        },
+        _ => {},
+      }
+    }
+    if key.modifiers.contains(KeyModifiers::ALT) {
+      match key.code {
+        KeyCode::Up => state.editor.add_cursor_above(),
+        KeyCode::Down => state.editor.add_cursor_below(),
+        KeyCode::Char('h') => state.request_hover(),
+        KeyCode::Char('j') => state.next_hunk(),
+        KeyCode::Char('k') => state.previous_hunk(),
+        KeyCode::Char('p') => state.preview_hunk(),
+        KeyCode::Char('r') => state.revert_hunk(),
+        _ => {},
+      }
+      return;
+    }
     if let KeyCode::Char(char) = key.code {
-      state.editor.input(&format!("{}", char));
+      state.editor.insert_char(char);
     }
     if let KeyCode::Backspace = key.code {
       state.editor.backspace();
     }
+    if let KeyCode::Delete = key.code {
+      state.editor.delete();
+    }
     if let KeyCode::Enter = key.code {
-      state.editor.input("\n");
+      state.editor.newline();
+    }
+    if let KeyCode::Tab = key.code {
+      state.editor.insert_indent();
+    }
+    match key.code {
+      KeyCode::Up => state.editor.up(),
+      KeyCode::Down => state.editor.down(),
+      KeyCode::Left => state.editor.left(),
+      KeyCode::Right => state.editor.right(),
+      KeyCode::Esc => state.editor.collapse_cursors(),
+      KeyCode::F(8) if key.modifiers.contains(KeyModifiers::SHIFT) => state.editor.previous_diagnostic(),
+      KeyCode::F(8) => state.editor.next_diagnostic(),
+      KeyCode::F(12) => state.request_definition(),
+      _ => {},
+    }
+    state.update_completion();
+  }
+}
+
+/// clicks and drags on the scrollbar or minimap, which scroll the row under
+/// the mouse to the middle of the edit area. returns whether the event was used.
+fn scrollbar_mouse(mouse: MouseEvent, state: &mut AppState) -> bool {
+  let area = state.scrollbar_area;
+  let inside = area.width > 0 && mouse.column >= area.left() && mouse.column < area.right()
+    && mouse.row >= area.top() && mouse.row < area.bottom();
+  match mouse.kind {
+    MouseEventKind::Down(MouseButton::Left) if inside => state.dragging_scrollbar = true,
+    MouseEventKind::Drag(MouseButton::Left) if state.dragging_scrollbar => {},
+    MouseEventKind::Up(MouseButton::Left) if state.dragging_scrollbar => {
+      state.dragging_scrollbar = false;
+      return true;
+    },
+    _ => return false,
+  }
+  let y = mouse.row.clamp(area.top(), area.bottom() - 1) - area.top();
+  let (total, height) = (state.editor.row_count(), area.height as u32);
+  let on_minimap = state.settings.minimap && (mouse.column < area.right() - 1 || !state.settings.scrollbar);
+  let row = if on_minimap {
+    scrollbar::minimap_row_at(y as u32, total, height)
+  } else {
+    scrollbar::row_at(y as u32, total, height)
+  };
+  state.editor.scroll_to(row.saturating_sub(state.edit_area.height as u32 / 2));
+  true
+}
+
+/// the mouse wheel over the edit area scrolls it without moving the cursor,
+/// sideways while shift is held.
+fn edit_area_wheel(mouse: MouseEvent, state: &mut AppState) {
+  let area = state.edit_area;
+  if mouse.row < area.top() || mouse.row >= area.bottom() || mouse.column >= area.right() {
+    return;
+  }
+  let sideways = mouse.modifiers.contains(KeyModifiers::SHIFT);
+  for _ in 0..WHEEL_STEP {
+    match (mouse.kind, sideways) {
+      (MouseEventKind::ScrollDown, false) => state.editor.scroll_up(),
+      (MouseEventKind::ScrollUp, false) => state.editor.scroll_down(),
+      (MouseEventKind::ScrollDown, true) => state.editor.scroll_left(),
+      (MouseEventKind::ScrollUp, true) => state.editor.scroll_right(),
+      _ => {},
+    }
+  }
+}
+
+/// clicks on the file explorer and drags of its border. returns whether the event was used.
+fn sidebar_mouse(mouse: MouseEvent, state: &mut AppState) -> bool {
+  let area = state.sidebar_area;
+  let inside = area.width > 0 && mouse.column >= area.left() && mouse.column < area.right()
+    && mouse.row >= area.top() && mouse.row < area.bottom();
+  match mouse.kind {
+    MouseEventKind::Down(MouseButton::Left) if inside && mouse.column == area.right() - 1 => {
+      state.resizing_sidebar = true;
+    },
+    MouseEventKind::Drag(MouseButton::Left) if state.resizing_sidebar => {
+      state.explorer.resize((mouse.column + 1).saturating_sub(area.left()));
+    },
+    MouseEventKind::Up(MouseButton::Left) if state.resizing_sidebar => {
+      state.resizing_sidebar = false;
+    },
+    MouseEventKind::Down(MouseButton::Left) if inside => {
+      let index = state.explorer.scroll + (mouse.row - area.top()) as usize;
+      if index < state.explorer.entries().len() {
+        state.explorer.focused = true;
+        state.explorer.selected = index;
+        if let Some(path) = state.explorer.activate() {
+          state.open_file(&path);
+        }
+      }
+    },
+    MouseEventKind::ScrollDown if inside => state.explorer.down(),
+    MouseEventKind::ScrollUp if inside => state.explorer.up(),
+    _ => return false,
+  }
+  true
+}
+
+/// keys while the file explorer has focus.
+fn explorer_key(key: KeyEvent, state: &mut AppState) {
+  let explorer = &mut state.explorer;
+  if explorer.confirm_delete {
+    explorer.confirm_delete = false;
+    if key.code == KeyCode::Char('y') {
+      let result = explorer.delete().map(|path| format!("deleted {}", path.display()));
+      state.message = Some(result.unwrap_or_else(|err| format!("{:#}", err)));
     }
+    return;
+  }
+  match key.code {
+    KeyCode::Up | KeyCode::Char('k') => explorer.up(),
+    KeyCode::Down | KeyCode::Char('j') => explorer.down(),
+    KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => {
+      if let Some(path) = explorer.activate() {
+        state.open_file(&path);
+      }
+    },
+    KeyCode::Left | KeyCode::Char('h') => explorer.collapse(),
+    // a trailing '/' creates a directory
+    KeyCode::Char('a') => state.open_prompt(Prompt::new("new: ", Box::new(|state, name| {
+      let path = state.explorer.create(name)?;
+      state.message = Some(format!("created {}", path.display()));
+      Ok(())
+    }))),
+    KeyCode::Char('r') => {
+      let Some(name) = explorer.selected_entry().map(|entry| entry.name.clone()) else { return };
+      let prompt = Prompt::new("rename: ", Box::new(|state, name| {
+        let path = state.explorer.rename(name)?;
+        state.message = Some(format!("renamed to {}", path.display()));
+        Ok(())
+      }));
+      state.open_prompt(prompt.with_text(&name));
+    },
+    KeyCode::Char('d') if explorer.selected_entry().is_some() => explorer.confirm_delete = true,
+    KeyCode::Char('R') => explorer.refresh(),
+    KeyCode::Char('<') => explorer.resize(explorer.width.saturating_sub(2)),
+    KeyCode::Char('>') => explorer.resize(explorer.width + 2),
+    KeyCode::Esc => explorer.focused = false,
+    _ => {},
+  }
+}
+
+/// keys while a prompt is open.
+fn prompt_key(key: KeyEvent, state: &mut AppState) {
+  let Some(prompt) = state.prompt.as_mut() else { return };
+  let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+  let alt = key.modifiers.contains(KeyModifiers::ALT);
+  match key.code {
+    KeyCode::Esc => state.prompt = None,
+    KeyCode::Enter => state.submit_prompt(),
+    KeyCode::Tab => prompt.complete(),
+    KeyCode::Up => prompt.history_up(),
+    KeyCode::Down => prompt.history_down(),
+    KeyCode::Left => prompt.left(),
+    KeyCode::Right => prompt.right(),
+    KeyCode::Home => prompt.home(),
+    KeyCode::End => prompt.end(),
+    KeyCode::Char('a') if ctrl => prompt.home(),
+    KeyCode::Char('e') if ctrl => prompt.end(),
+    KeyCode::Char('w') | KeyCode::Char('h') if ctrl => prompt.delete_word_backward(),
+    KeyCode::Backspace if ctrl || alt => prompt.delete_word_backward(),
+    KeyCode::Backspace => prompt.backspace(),
+    KeyCode::Delete => prompt.delete(),
+    KeyCode::Char(char) if !ctrl && !alt => prompt.insert(&char.to_string()),
+    _ => {},
+  }
+}
+
+/// keys while the search panel has focus.
+fn search_key(key: KeyEvent, state: &mut AppState) {
+  let Some(panel) = state.search_panel.as_mut() else { return };
+  if panel.confirm.is_some() {
+    match key.code {
+      KeyCode::Char(char) => state.answer_replace(char),
+      KeyCode::Esc => state.answer_replace('q'),
+      _ => {},
+    }
+    return;
+  }
+  match key.code {
+    KeyCode::Esc => {
+      state.search_panel = None;
+      state.search_focused = false;
+    },
+    KeyCode::Tab => panel.next_field(),
+    KeyCode::Up => panel.up(),
+    KeyCode::Down => panel.down(),
+    KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => panel.is_regex = !panel.is_regex,
+    KeyCode::Enter if panel.field == Field::Results => state.open_search_result(),
+    KeyCode::Enter => state.run_search(),
+    KeyCode::Char('r') if panel.field == Field::Results => panel.start_replace(),
+    KeyCode::Char(char) => {
+      if let Some(text) = panel.text_mut() {
+        text.push(char);
+      }
+    },
+    KeyCode::Backspace => {
+      if let Some(text) = panel.text_mut() {
+        text.pop();
+      }
+    },
+    _ => {},
   }
 }
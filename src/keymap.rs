@@ -0,0 +1,192 @@
+//! Key bindings for app level commands, so they can be rebound instead of
+//! being matched in the event loop.
+
+use std::collections::HashMap;
+
+use anyhow::{Result, bail};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+  /// quit, asking first when there are unsaved changes.
+  Quit,
+  /// quit right away. unsaved changes stay in the swap file.
+  ForceQuit,
+  Save,
+  /// show, focus or hide the file explorer.
+  ToggleExplorer,
+  /// open the fuzzy file finder.
+  FindFile,
+  /// open the search in files panel.
+  SearchFiles,
+  /// ask for a line to go to.
+  GotoLine,
+  /// close the block at the cursor, or the one around it.
+  Fold,
+  /// open the fold on the cursor's line.
+  Unfold,
+  FoldAll,
+  UnfoldAll,
+  /// show or hide spaces, tabs and line breaks.
+  ToggleWhitespace,
+  /// change a setting, see `settings::Config::set`.
+  Set,
+  /// scroll the cursor's line to the middle of the screen.
+  CenterLine,
+  LineToTop,
+  LineToBottom,
+}
+
+impl Command {
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "quit" => Some(Self::Quit),
+      "force_quit" => Some(Self::ForceQuit),
+      "save" => Some(Self::Save),
+      "toggle_explorer" => Some(Self::ToggleExplorer),
+      "find_file" => Some(Self::FindFile),
+      "search_files" => Some(Self::SearchFiles),
+      "goto_line" => Some(Self::GotoLine),
+      "fold" => Some(Self::Fold),
+      "unfold" => Some(Self::Unfold),
+      "fold_all" => Some(Self::FoldAll),
+      "unfold_all" => Some(Self::UnfoldAll),
+      "toggle_whitespace" => Some(Self::ToggleWhitespace),
+      "set" => Some(Self::Set),
+      "center_line" => Some(Self::CenterLine),
+      "line_to_top" => Some(Self::LineToTop),
+      "line_to_bottom" => Some(Self::LineToBottom),
+      _ => None,
+    }
+  }
+}
+
+pub struct Keymap {
+  bindings: HashMap<(KeyCode, KeyModifiers), Command>,
+}
+
+impl Default for Keymap {
+  fn default() -> Self {
+    let mut keymap = Self { bindings: HashMap::new() };
+    keymap.bind(KeyCode::Char('c'), KeyModifiers::CONTROL, Command::Quit);
+    keymap.bind(KeyCode::Char('q'), KeyModifiers::CONTROL, Command::ForceQuit);
+    keymap.bind(KeyCode::Char('s'), KeyModifiers::CONTROL, Command::Save);
+    keymap.bind(KeyCode::Char('e'), KeyModifiers::CONTROL, Command::ToggleExplorer);
+    keymap.bind(KeyCode::Char('p'), KeyModifiers::CONTROL, Command::FindFile);
+    keymap.bind(KeyCode::Char('f'), KeyModifiers::CONTROL, Command::SearchFiles);
+    keymap.bind(KeyCode::Char('g'), KeyModifiers::CONTROL, Command::GotoLine);
+    keymap.bind(KeyCode::Char('['), KeyModifiers::ALT, Command::Fold);
+    keymap.bind(KeyCode::Char(']'), KeyModifiers::ALT, Command::Unfold);
+    keymap.bind(KeyCode::Char('{'), KeyModifiers::ALT, Command::FoldAll);
+    keymap.bind(KeyCode::Char('}'), KeyModifiers::ALT, Command::UnfoldAll);
+    keymap.bind(KeyCode::Char('w'), KeyModifiers::ALT, Command::ToggleWhitespace);
+    keymap.bind(KeyCode::Char('o'), KeyModifiers::CONTROL, Command::Set);
+    keymap.bind(KeyCode::Char('l'), KeyModifiers::CONTROL, Command::CenterLine);
+    keymap.bind(KeyCode::Char('t'), KeyModifiers::ALT, Command::LineToTop);
+    keymap.bind(KeyCode::Char('b'), KeyModifiers::ALT, Command::LineToBottom);
+    keymap
+  }
+}
+
+impl Keymap {
+  pub fn bind(&mut self, code: KeyCode, modifiers: KeyModifiers, command: Command) {
+    self.bindings.insert((code, modifiers), command);
+  }
+
+  /// bind a key written like "ctrl+c" or "alt+shift+f4", see `parse_key`.
+  pub fn bind_str(&mut self, key: &str, command: Command) -> Result<()> {
+    let (code, modifiers) = parse_key(key)?;
+    self.bind(code, modifiers, command);
+    Ok(())
+  }
+
+  pub fn unbind(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+    self.bindings.remove(&(code, modifiers));
+  }
+
+  /// apply a binding written as "key=command", e.g. "ctrl+x=quit".
+  /// the command "none" removes the key's binding.
+  pub fn apply(&mut self, binding: &str) -> Result<()> {
+    let Some((key, name)) = binding.rsplit_once('=') else { bail!("expected key=command, got {:?}", binding) };
+    if name == "none" {
+      let (code, modifiers) = parse_key(key)?;
+      self.unbind(code, modifiers);
+      return Ok(());
+    }
+    let Some(command) = Command::from_name(name) else { bail!("unknown command {:?}", name) };
+    self.bind_str(key, command)
+  }
+
+  pub fn get(&self, key: &KeyEvent) -> Option<Command> {
+    self.bindings.get(&(key.code, key.modifiers)).copied()
+  }
+}
+
+/// parse a key like "ctrl+c", "alt+enter" or "f5". modifiers come first,
+/// joined with '+'.
+pub fn parse_key(key: &str) -> Result<(KeyCode, KeyModifiers)> {
+  let mut parts: Vec<&str> = key.split('+').collect();
+  // "ctrl++" binds the '+' key itself
+  if key.ends_with("++") {
+    parts.truncate(parts.len() - 2);
+    parts.push("+");
+  }
+  let Some(name) = parts.pop().filter(|name| !name.is_empty()) else { bail!("empty key: {:?}", key) };
+  let mut modifiers = KeyModifiers::NONE;
+  for modifier in parts {
+    modifiers |= match modifier.to_ascii_lowercase().as_str() {
+      "ctrl" | "control" => KeyModifiers::CONTROL,
+      "alt" => KeyModifiers::ALT,
+      "shift" => KeyModifiers::SHIFT,
+      other => bail!("unknown modifier {:?} in {:?}", other, key),
+    };
+  }
+  let lower = name.to_ascii_lowercase();
+  let code = match lower.as_str() {
+    "enter" | "return" => KeyCode::Enter,
+    "esc" | "escape" => KeyCode::Esc,
+    "tab" => KeyCode::Tab,
+    "backspace" => KeyCode::Backspace,
+    "delete" | "del" => KeyCode::Delete,
+    "space" => KeyCode::Char(' '),
+    "up" => KeyCode::Up,
+    "down" => KeyCode::Down,
+    "left" => KeyCode::Left,
+    "right" => KeyCode::Right,
+    "home" => KeyCode::Home,
+    "end" => KeyCode::End,
+    "pageup" => KeyCode::PageUp,
+    "pagedown" => KeyCode::PageDown,
+    _ if lower.len() > 1 && lower.starts_with('f') && lower[1..].parse::<u8>().is_ok() => KeyCode::F(lower[1..].parse()?),
+    _ if name.chars().count() == 1 => KeyCode::Char(name.chars().next().unwrap()),
+    _ => bail!("unknown key {:?}", key),
+  };
+  Ok((code, modifiers))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_key() {
+    assert_eq!(parse_key("ctrl+c").unwrap(), (KeyCode::Char('c'), KeyModifiers::CONTROL));
+    assert_eq!(parse_key("Alt+Shift+F4").unwrap(), (KeyCode::F(4), KeyModifiers::ALT | KeyModifiers::SHIFT));
+    assert_eq!(parse_key("ctrl++").unwrap(), (KeyCode::Char('+'), KeyModifiers::CONTROL));
+    assert_eq!(parse_key("enter").unwrap(), (KeyCode::Enter, KeyModifiers::NONE));
+    assert!(parse_key("hyper+x").is_err());
+    assert!(parse_key("").is_err());
+  }
+
+  #[test]
+  fn test_rebind() {
+    let mut keymap = Keymap::default();
+    let ctrl_c = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+    assert_eq!(keymap.get(&ctrl_c), Some(Command::Quit));
+    keymap.apply("ctrl+c=none").unwrap();
+    keymap.apply("ctrl+x=quit").unwrap();
+    assert!(keymap.apply("ctrl+x=fly").is_err());
+    assert_eq!(keymap.get(&ctrl_c), None);
+    assert_eq!(keymap.get(&KeyEvent::new(KeyCode::Char('x'), KeyModifiers::CONTROL)), Some(Command::Quit));
+  }
+}
@@ -0,0 +1,463 @@
+use std::{
+  collections::HashMap,
+  io::{BufReader, Read, Write},
+  path::{Path, PathBuf},
+  process::{Child, Command, Stdio},
+  sync::mpsc::{self, Receiver, RecvTimeoutError},
+  thread,
+  time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result, bail};
+use serde_json::{Value, json};
+
+use super::{transport, Diagnostic, Severity, LspPosition, CompletionItem};
+use crate::editor::document::Change;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum RequestKind {
+  Initialize,
+  Hover,
+  Definition,
+  Completion,
+  Shutdown,
+}
+
+/// something the server told us, ready for the UI.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+  /// the server answered `initialize` and waits for the document, see `did_open`.
+  Initialized,
+  Diagnostics(Vec<Diagnostic>),
+  Hover(String),
+  Definition { path: PathBuf, position: LspPosition },
+  Completion(Vec<CompletionItem>),
+  Message(String),
+}
+
+/// a connection to one language server, serving one document.
+pub struct Client {
+  writer: Box<dyn Write + Send>,
+  incoming: Receiver<Value>,
+  /// messages that arrived while waiting for a specific response.
+  backlog: Vec<Value>,
+  child: Option<Child>,
+  next_id: i64,
+  pending: HashMap<i64, RequestKind>,
+  uri: String,
+  version: i32,
+  incremental: bool,
+  initialized: bool,
+}
+
+impl Client {
+  /// start `command` and talk to it over its stdin and stdout.
+  pub fn spawn(command: &str, args: &[&str]) -> Result<Self> {
+    let mut child = Command::new(command)
+      .args(args)
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::null())
+      .spawn()
+      .with_context(|| format!("failed to start language server {}", command))?;
+    let stdin = child.stdin.take().context("language server has no stdin")?;
+    let stdout = child.stdout.take().context("language server has no stdout")?;
+    let mut client = Self::connect(stdout, stdin);
+    client.child = Some(child);
+    Ok(client)
+  }
+
+  /// talk to a server over an arbitrary pair of streams.
+  pub fn connect(reader: impl Read + Send + 'static, writer: impl Write + Send + 'static) -> Self {
+    let (sender, incoming) = mpsc::channel();
+    thread::spawn(move || {
+      let mut reader = BufReader::new(reader);
+      while let Ok(Some(message)) = transport::read_message(&mut reader) {
+        if sender.send(message).is_err() {
+          break;
+        }
+      }
+    });
+    Self {
+      writer: Box::new(writer),
+      incoming,
+      backlog: Vec::new(),
+      child: None,
+      next_id: 0,
+      pending: HashMap::new(),
+      uri: String::new(),
+      version: 0,
+      incremental: false,
+      initialized: false,
+    }
+  }
+
+  /// start the `initialize` handshake for the document at `path`. the
+  /// answer comes through `poll` as `Event::Initialized`.
+  pub fn initialize(&mut self, path: &Path) -> Result<()> {
+    let root = path.parent().map(path_to_uri);
+    self.request("initialize", json!({
+      "processId": std::process::id(),
+      "rootUri": root,
+      "capabilities": {
+        "textDocument": {
+          "synchronization": { "dynamicRegistration": false },
+          "hover": { "contentFormat": ["plaintext", "markdown"] },
+          "definition": { "linkSupport": true },
+          "completion": { "completionItem": { "snippetSupport": false } },
+          "publishDiagnostics": {},
+        },
+      },
+    }), RequestKind::Initialize)?;
+    self.uri = path_to_uri(path);
+    Ok(())
+  }
+
+  /// whether the server answered `initialize`. nothing else may be sent before.
+  pub fn is_initialized(&self) -> bool {
+    self.initialized
+  }
+
+  /// open the document, with its text as it is once the server is ready.
+  pub fn did_open(&mut self, language_id: &str, text: &str) -> Result<()> {
+    self.version = 1;
+    self.notify("textDocument/didOpen", json!({
+      "textDocument": { "uri": self.uri, "languageId": language_id, "version": self.version, "text": text },
+    }))
+  }
+
+  /// send edits. servers that can't take incremental changes get `full_text` instead.
+  pub fn did_change(&mut self, changes: &[Change], full_text: impl FnOnce() -> String) -> Result<()> {
+    if changes.is_empty() {
+      return Ok(());
+    }
+    self.version += 1;
+    let content_changes: Vec<Value> = if self.incremental {
+      changes.iter().map(|change| {
+        let start = LspPosition { line: change.start.ln, character: change.start_utf16 };
+        let end = end_of(start, &change.removed);
+        json!({ "range": { "start": start.to_json(), "end": end.to_json() }, "text": change.inserted })
+      }).collect()
+    } else {
+      vec![json!({ "text": full_text() })]
+    };
+    self.notify("textDocument/didChange", json!({
+      "textDocument": { "uri": self.uri, "version": self.version },
+      "contentChanges": content_changes,
+    }))
+  }
+
+  pub fn hover(&mut self, position: LspPosition) -> Result<()> {
+    self.request("textDocument/hover", self.position_params(position), RequestKind::Hover).map(|_| ())
+  }
+
+  pub fn definition(&mut self, position: LspPosition) -> Result<()> {
+    self.request("textDocument/definition", self.position_params(position), RequestKind::Definition).map(|_| ())
+  }
+
+  pub fn completion(&mut self, position: LspPosition) -> Result<()> {
+    self.request("textDocument/completion", self.position_params(position), RequestKind::Completion).map(|_| ())
+  }
+
+  /// everything the server sent since the last call.
+  pub fn poll(&mut self) -> Vec<Event> {
+    let mut messages: Vec<Value> = std::mem::take(&mut self.backlog);
+    messages.extend(self.incoming.try_iter());
+    messages.into_iter().filter_map(|message| self.handle(message)).collect()
+  }
+
+  /// ask the server to shut down and exit.
+  pub fn shutdown(&mut self) {
+    if let Ok(id) = self.request("shutdown", Value::Null, RequestKind::Shutdown) {
+      let _ = self.wait_for(id, Duration::from_millis(500));
+    }
+    let _ = self.notify("exit", Value::Null);
+  }
+
+  fn position_params(&self, position: LspPosition) -> Value {
+    json!({ "textDocument": { "uri": self.uri }, "position": position.to_json() })
+  }
+
+  fn request(&mut self, method: &str, params: Value, kind: RequestKind) -> Result<i64> {
+    self.next_id += 1;
+    let id = self.next_id;
+    self.pending.insert(id, kind);
+    transport::write_message(&mut self.writer, &json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))?;
+    Ok(id)
+  }
+
+  fn notify(&mut self, method: &str, params: Value) -> Result<()> {
+    transport::write_message(&mut self.writer, &json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+  }
+
+  /// block until the response to request `id` arrives, keeping everything
+  /// else for `poll`.
+  fn wait_for(&mut self, id: i64, timeout: Duration) -> Result<Value> {
+    let deadline = Instant::now() + timeout;
+    loop {
+      let remaining = deadline.saturating_duration_since(Instant::now());
+      match self.incoming.recv_timeout(remaining) {
+        Ok(message) if message["id"] == json!(id) && message.get("method").is_none() => {
+          self.pending.remove(&id);
+          if let Some(error) = message.get("error") {
+            bail!("language server error: {}", error["message"]);
+          }
+          return Ok(message);
+        },
+        Ok(message) => self.backlog.push(message),
+        Err(RecvTimeoutError::Timeout) => bail!("language server did not answer in time"),
+        Err(RecvTimeoutError::Disconnected) => bail!("language server exited"),
+      }
+    }
+  }
+
+  fn handle(&mut self, message: Value) -> Option<Event> {
+    let method = message.get("method").and_then(Value::as_str);
+    match (message.get("id"), method) {
+      // a request from the server. we support none of them, but an answer
+      // keeps servers that wait for one going.
+      (Some(id), Some(_)) => {
+        let reply = json!({ "jsonrpc": "2.0", "id": id, "result": Value::Null });
+        let _ = transport::write_message(&mut self.writer, &reply);
+        None
+      },
+      (Some(id), None) => {
+        let kind = self.pending.remove(&id.as_i64()?)?;
+        if let Some(error) = message.get("error") {
+          return Some(Event::Message(format!("language server error: {}", error["message"])));
+        }
+        if kind == RequestKind::Initialize {
+          return self.initialized(&message["result"]);
+        }
+        parse_response(kind, &message["result"])
+      },
+      (None, Some("textDocument/publishDiagnostics")) => {
+        let params = &message["params"];
+        if params["uri"].as_str() != Some(self.uri.as_str()) {
+          return None;
+        }
+        let diagnostics = params["diagnostics"].as_array()?.iter().filter_map(parse_diagnostic).collect();
+        Some(Event::Diagnostics(diagnostics))
+      },
+      (None, Some("window/showMessage" | "window/logMessage")) => {
+        message["params"]["message"].as_str().map(|text| Event::Message(text.to_string()))
+      },
+      _ => None,
+    }
+  }
+
+  /// finish the handshake with the server's answer to `initialize`.
+  fn initialized(&mut self, result: &Value) -> Option<Event> {
+    let sync = &result["capabilities"]["textDocumentSync"];
+    let change_kind = sync.get("change").unwrap_or(sync).as_i64().unwrap_or(1);
+    self.incremental = change_kind == 2;
+    if let Err(err) = self.notify("initialized", json!({})) {
+      return Some(Event::Message(format!("{:#}", err)));
+    }
+    self.initialized = true;
+    Some(Event::Initialized)
+  }
+}
+
+impl Drop for Client {
+  fn drop(&mut self) {
+    if let Some(child) = self.child.as_mut() {
+      let _ = child.kill();
+      let _ = child.wait();
+    }
+  }
+}
+
+fn parse_response(kind: RequestKind, result: &Value) -> Option<Event> {
+  match kind {
+    RequestKind::Hover => {
+      let text = markup_text(&result["contents"]);
+      (!text.trim().is_empty()).then_some(Event::Hover(text))
+    },
+    RequestKind::Definition => {
+      let location = match result {
+        Value::Array(locations) => locations.first()?,
+        location => location,
+      };
+      let uri = location.get("targetUri").or(location.get("uri"))?.as_str()?;
+      let range = location.get("targetSelectionRange").or(location.get("range"))?;
+      Some(Event::Definition { path: uri_to_path(uri)?, position: LspPosition::from_json(&range["start"])? })
+    },
+    RequestKind::Completion => {
+      let items = match result {
+        Value::Array(items) => items,
+        list => list["items"].as_array()?,
+      };
+      Some(Event::Completion(items.iter().filter_map(|item| {
+        let label = item["label"].as_str()?.to_string();
+        let insert_text = item["textEdit"]["newText"].as_str()
+          .or(item["insertText"].as_str())
+          .unwrap_or(&label)
+          .to_string();
+        Some(CompletionItem { label, insert_text, detail: item["detail"].as_str().map(str::to_string) })
+      }).collect()))
+    },
+    RequestKind::Initialize | RequestKind::Shutdown => None,
+  }
+}
+
+/// hover contents come as a string, a `MarkupContent`, a `MarkedString` or a list of those.
+fn markup_text(contents: &Value) -> String {
+  match contents {
+    Value::String(text) => text.clone(),
+    Value::Array(parts) => parts.iter().map(markup_text).collect::<Vec<_>>().join("\n"),
+    Value::Object(_) => contents["value"].as_str().unwrap_or_default().to_string(),
+    _ => String::new(),
+  }
+}
+
+fn parse_diagnostic(value: &Value) -> Option<Diagnostic> {
+  Some(Diagnostic {
+    start: LspPosition::from_json(&value["range"]["start"])?,
+    end: LspPosition::from_json(&value["range"]["end"])?,
+    severity: match value["severity"].as_i64() {
+      Some(1) => Severity::Error,
+      Some(2) => Severity::Warning,
+      Some(3) => Severity::Information,
+      _ => Severity::Hint,
+    },
+    message: value["message"].as_str()?.to_string(),
+    source: value["source"].as_str().map(str::to_string),
+  })
+}
+
+/// where text that started at `start` ended, in UTF-16 units.
+fn end_of(start: LspPosition, text: &str) -> LspPosition {
+  match text.rsplit_once('\n') {
+    Some((_, last)) => LspPosition {
+      line: start.line + text.matches('\n').count() as u32,
+      character: last.encode_utf16().count() as u32,
+    },
+    None => LspPosition { line: start.line, character: start.character + text.encode_utf16().count() as u32 },
+  }
+}
+
+pub fn path_to_uri(path: &Path) -> String {
+  let absolute = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+  let mut uri = String::from("file://");
+  for byte in absolute.to_string_lossy().bytes() {
+    match byte {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => uri.push(byte as char),
+      _ => uri.push_str(&format!("%{:02X}", byte)),
+    }
+  }
+  uri
+}
+
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+  let encoded = uri.strip_prefix("file://")?.as_bytes();
+  let mut bytes = Vec::with_capacity(encoded.len());
+  let mut i = 0;
+  while i < encoded.len() {
+    if encoded[i] == b'%' {
+      let hex = std::str::from_utf8(encoded.get(i + 1..i + 3)?).ok()?;
+      bytes.push(u8::from_str_radix(hex, 16).ok()?);
+      i += 3;
+    } else {
+      bytes.push(encoded[i]);
+      i += 1;
+    }
+  }
+  Some(PathBuf::from(String::from_utf8(bytes).ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::pipe;
+  use std::sync::mpsc::Sender;
+  use super::*;
+  use crate::editor::location::Location;
+
+  /// a tiny language server: answers `initialize` and `hover`, publishes one
+  /// diagnostic on open, and reports every `didChange` back to the test.
+  fn fake_server(changes: Sender<Value>) -> Client {
+    let (client_reader, mut server_writer) = pipe().unwrap();
+    let (server_reader, client_writer) = pipe().unwrap();
+    thread::spawn(move || {
+      let mut reader = BufReader::new(server_reader);
+      while let Ok(Some(message)) = transport::read_message(&mut reader) {
+        let reply = match message["method"].as_str() {
+          Some("initialize") => json!({ "id": message["id"], "result": { "capabilities": { "textDocumentSync": 2 } } }),
+          Some("textDocument/hover") => json!({ "id": message["id"], "result": { "contents": { "kind": "plaintext", "value": "fn main()" } } }),
+          Some("textDocument/didOpen") => json!({
+            "method": "textDocument/publishDiagnostics",
+            "params": {
+              "uri": message["params"]["textDocument"]["uri"],
+              "diagnostics": [{
+                "range": { "start": { "line": 0, "character": 3 }, "end": { "line": 0, "character": 7 } },
+                "severity": 2,
+                "message": "unused",
+                "source": "fake",
+              }],
+            },
+          }),
+          Some("textDocument/didChange") => {
+            changes.send(message["params"]["contentChanges"].clone()).unwrap();
+            continue;
+          },
+          _ => continue,
+        };
+        transport::write_message(&mut server_writer, &reply).unwrap();
+      }
+    });
+    Client::connect(client_reader, client_writer)
+  }
+
+  fn wait_for_events(client: &mut Client, count: usize) -> Vec<Event> {
+    let mut events = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while events.len() < count && Instant::now() < deadline {
+      events.extend(client.poll());
+      thread::sleep(Duration::from_millis(5));
+    }
+    events
+  }
+
+  #[test]
+  fn test_fake_server() {
+    let (sender, changes) = mpsc::channel();
+    let mut client = fake_server(sender);
+    client.initialize(Path::new("/tmp/main.rs")).unwrap();
+    assert!(!client.is_initialized());
+    assert_eq!(wait_for_events(&mut client, 1), vec![Event::Initialized]);
+    assert!(client.incremental);
+    client.did_open("rust", "fn main() {}").unwrap();
+    let events = wait_for_events(&mut client, 1);
+    assert_eq!(events, vec![Event::Diagnostics(vec![Diagnostic {
+      start: LspPosition { line: 0, character: 3 },
+      end: LspPosition { line: 0, character: 7 },
+      severity: Severity::Warning,
+      message: "unused".to_string(),
+      source: Some("fake".to_string()),
+    }])]);
+
+    let change = Change {
+      start: Location { ln: 0, col: 1 },
+      start_utf16: 2,
+      removed: "a\nb😀".to_string(),
+      inserted: "x".to_string(),
+    };
+    client.did_change(&[change], String::new).unwrap();
+    let sent = changes.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(sent, json!([{
+      "range": { "start": { "line": 0, "character": 2 }, "end": { "line": 1, "character": 3 } },
+      "text": "x",
+    }]));
+
+    client.hover(LspPosition { line: 0, character: 3 }).unwrap();
+    assert_eq!(wait_for_events(&mut client, 1), vec![Event::Hover("fn main()".to_string())]);
+  }
+
+  #[test]
+  fn test_uri() {
+    let path = Path::new("/tmp/with space/铁.rs");
+    let uri = path_to_uri(path);
+    assert_eq!(uri, "file:///tmp/with%20space/%E9%93%81.rs");
+    assert_eq!(uri_to_path(&uri).unwrap(), path);
+  }
+}
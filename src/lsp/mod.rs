@@ -0,0 +1,46 @@
+//! A small language server protocol client. One server runs per opened
+//! document, started from `Language::language_server`.
+
+mod client;
+mod transport;
+
+use serde_json::{Value, json};
+
+pub use client::{Client, Event};
+pub use crate::editor::diagnostic::Severity;
+
+/// a position as the protocol counts it: zero based line, UTF-16 column.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LspPosition {
+  pub line: u32,
+  pub character: u32,
+}
+
+impl LspPosition {
+  fn to_json(self) -> Value {
+    json!({ "line": self.line, "character": self.character })
+  }
+
+  fn from_json(value: &Value) -> Option<Self> {
+    Some(Self {
+      line: value["line"].as_u64()? as u32,
+      character: value["character"].as_u64()? as u32,
+    })
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+  pub start: LspPosition,
+  pub end: LspPosition,
+  pub severity: Severity,
+  pub message: String,
+  pub source: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompletionItem {
+  pub label: String,
+  pub insert_text: String,
+  pub detail: Option<String>,
+}
@@ -0,0 +1,52 @@
+//! JSON-RPC framing used by the language server protocol: a `Content-Length`
+//! header, a blank line, then the JSON body.
+
+use std::io::{BufRead, Write};
+use anyhow::{Context, Result, bail};
+use serde_json::Value;
+
+pub fn write_message(writer: &mut impl Write, message: &Value) -> Result<()> {
+  let body = message.to_string();
+  write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body).context("failed to write message")?;
+  writer.flush().context("failed to flush message")?;
+  Ok(())
+}
+
+/// read the next message. `None` means the other side closed the stream.
+pub fn read_message(reader: &mut impl BufRead) -> Result<Option<Value>> {
+  let mut content_length = None;
+  loop {
+    let mut header = String::new();
+    if reader.read_line(&mut header).context("failed to read header")? == 0 {
+      return Ok(None);
+    }
+    let header = header.trim_end();
+    if header.is_empty() {
+      break;
+    }
+    if let Some(value) = header.strip_prefix("Content-Length:") {
+      content_length = Some(value.trim().parse::<usize>().context("invalid Content-Length")?);
+    }
+  }
+  let Some(length) = content_length else { bail!("message without Content-Length") };
+  let mut body = vec![0; length];
+  reader.read_exact(&mut body).context("failed to read body")?;
+  Ok(Some(serde_json::from_slice(&body).context("invalid JSON body")?))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn test_round_trip() {
+    let mut buffer = Vec::new();
+    write_message(&mut buffer, &json!({ "id": 1, "text": "铁锈" })).unwrap();
+    write_message(&mut buffer, &json!({ "id": 2 })).unwrap();
+    let mut reader = std::io::Cursor::new(buffer);
+    assert_eq!(read_message(&mut reader).unwrap(), Some(json!({ "id": 1, "text": "铁锈" })));
+    assert_eq!(read_message(&mut reader).unwrap(), Some(json!({ "id": 2 })));
+    assert_eq!(read_message(&mut reader).unwrap(), None);
+  }
+}
@@ -5,10 +5,11 @@ use std::{
 
 use anyhow::{Context, Result};
 use crossterm::{
-  event::{self, Event, KeyCode, KeyModifiers, EnableMouseCapture, DisableMouseCapture},
+  event::{self, EnableMouseCapture, DisableMouseCapture, EnableFocusChange, DisableFocusChange},
   execute,
   terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
+use editor::whitespace::SaveActions;
 use macros::log;
 use ratatui::prelude::*;
 
@@ -16,19 +17,96 @@ mod view;
 mod state;
 mod handler;
 mod macros;
-mod utils;
 mod editor;
 mod widget;
 mod log;
+mod lsp;
+mod fuzzy;
+mod completion;
+mod git;
+mod session;
+mod swap;
+mod watch;
+mod keymap;
+mod explorer;
+mod finder;
+mod search;
+mod goto;
+mod prompt;
+mod editorconfig;
+mod settings;
 
 type Frame<'a> = ratatui::Frame<'a, CrosstermBackend<Stdout>>;
 
 fn main() -> Result<()> {
+  let cwd = std::env::current_dir().context("failed to get the working directory")?;
+  // command line flags override the config files
+  let mut config = settings::Config::load(&cwd);
+  let mut restore = false;
+  let mut bindings = Vec::new();
+  let mut path = None;
+  for arg in std::env::args_os().skip(1) {
+    if arg == "--restore" {
+      restore = true;
+    } else if let Some(delay) = arg.to_str().and_then(|arg| arg.strip_prefix("--autosave=")) {
+      let delay: i64 = delay.parse().with_context(|| format!("invalid autosave delay: {}", delay))?;
+      config.set_override("autosave", toml::Value::Integer(delay))?;
+    } else if let Some(names) = arg.to_str().and_then(|arg| arg.strip_prefix("--on-save=")) {
+      let actions = SaveActions::from_names(names)?;
+      config.set_override("trim_trailing_whitespace", toml::Value::Boolean(actions.trim_trailing_whitespace))?;
+      config.set_override("insert_final_newline", toml::Value::Boolean(actions.insert_final_newline))?;
+      config.set_override("trim_final_newlines", toml::Value::Boolean(actions.trim_final_newlines))?;
+    } else if let Some(binding) = arg.to_str().and_then(|arg| arg.strip_prefix("--bind=")) {
+      bindings.push(binding.to_string());
+    } else if path.is_none() {
+      path = Some(arg);
+    }
+  }
+  // every run saves its session, `--restore` reads the last one back
+  let state_dir = session::state_dir();
+  let saved_session = match state_dir.as_deref().filter(|_| restore && path.is_none()) {
+    Some(state_dir) => session::load(state_dir, &cwd).unwrap_or_else(|err| {
+      log!("failed to load the session: {:#}", err);
+      None
+    }),
+    None => None,
+  };
+  let mut restored_log = None;
+  let mut app_state = match (path, saved_session) {
+    (Some(path), _) => state::AppState::open(path.as_ref())?,
+    (None, Some(session)) => {
+      let show_log = session.show_log;
+      match session.restore() {
+        Ok(app_state) => {
+          restored_log = Some(show_log);
+          app_state
+        },
+        Err(err) => {
+          log!("failed to restore the session: {:#}", err);
+          state::AppState::default()
+        },
+      }
+    },
+    (None, None) => state::AppState::default(),
+  };
+  app_state.config = config;
+  app_state.apply_settings(None);
+  // a restored layout wins over the configured one
+  if let Some(show_log) = restored_log {
+    app_state.show_log = show_log;
+  }
+  for binding in bindings {
+    app_state.keymap.apply(&binding)?;
+  }
   setup_terminal().context("setup failed")?;
   let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout())).expect("creating terminal failed");
-  run(&mut terminal).context("app loop paniced")?;
+  let result = run(&mut terminal, &mut app_state).context("app loop paniced");
+  let saved = match state_dir {
+    Some(state_dir) => session::save(&state_dir, &cwd, &session::Session::capture(&app_state)),
+    None => Ok(()),
+  };
   restore_terminal().context("restore terminal failed")?;
-  Ok(())
+  result.and(saved)
 }
 
 /// Setup the terminal. Enable raw mode, enter the alternate screen, and hide the cursor.
@@ -39,9 +117,12 @@ fn setup_terminal() -> Result<()> {
     crossterm::cursor::Hide,
     EnterAlternateScreen,
     EnableMouseCapture,
+    EnableFocusChange,
   ).context("unable to enter alternate screen")?;
   let default_hook = std::panic::take_hook();
   std::panic::set_hook(Box::new(move |info| {
+    // save unsaved text first, in case restoring the terminal fails too
+    swap::write_pending();
     restore_terminal().expect("restore terminal failed");
     default_hook(info);
   }));
@@ -54,6 +135,7 @@ fn restore_terminal() -> Result<()> {
   execute!(
     io::stdout(),
     DisableMouseCapture,
+    DisableFocusChange,
     LeaveAlternateScreen,
     crossterm::cursor::Show,
   ).context("unable to switch to main screen")?;
@@ -61,34 +143,55 @@ fn restore_terminal() -> Result<()> {
 }
 
 /// Run the application loop. This is where is handle events and update the application state.
-fn run(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
-  let mut app_state = state::AppState::default();
+fn run(terminal: &mut Terminal<CrosstermBackend<Stdout>>, app_state: &mut state::AppState) -> Result<()> {
   loop {
-    terminal.draw(|frame| render_app(frame, &mut app_state))?;
-    if event_poll(&mut app_state)? { break }
+    terminal.draw(|frame| render_app(frame, app_state))?;
+    if event_poll(app_state)? { break }
+    app_state.sync_language_server();
+    app_state.poll_language_server();
+    app_state.update_git_gutter();
+    app_state.check_file();
+    app_state.explorer.poll();
+    app_state.autosave();
+    app_state.update_swap();
   }
+  app_state.shutdown();
   Ok(())
 }
 
 /// Render the application. This is where is draw the application UI.
 fn render_app(frame: &mut Frame, state: &mut state::AppState) {
-  let (top_window, status_bar, bottom_window) = view::create_areas(frame, state);
+  let (sidebar, top_window, status_bar, bottom_window) = view::create_areas(frame, state);
+  frame.render_stateful_widget(widget::Sidebar, sidebar, state);
+  let (top_window, scrollbar) = view::split_scrollbar(top_window, state);
   frame.render_stateful_widget(widget::EditArea, top_window, state);
-  frame.render_widget(widget::StatusBar, status_bar);
-  frame.render_widget(widget::Terminal, bottom_window);
+  frame.render_stateful_widget(widget::Scrollbar, scrollbar, state);
+  // the edit area leaves room for its gutter, so the text starts at `state.edit_area`
+  let text_area = state.edit_area;
+  frame.render_stateful_widget(widget::CompletionPopup, text_area, state);
+  frame.render_stateful_widget(widget::StatusBar, status_bar, state);
+  frame.render_stateful_widget(widget::PromptLine, status_bar, state);
+  // the search panel takes the log's place while it is open
+  if state.search_panel.is_some() {
+    frame.render_stateful_widget(widget::SearchPanel, bottom_window, state);
+  } else {
+    frame.render_widget(widget::Terminal, bottom_window);
+  }
+  frame.render_stateful_widget(widget::FinderOverlay, frame.size(), state);
+  frame.render_stateful_widget(widget::QuitDialog, frame.size(), state);
+  // while a prompt is open the cursor sits in it
+  if let Some(prompt) = &state.prompt {
+    frame.set_cursor(prompt.screen_cursor, status_bar.top());
+    return;
+  }
   let cursor_pos = state.editor.cursor_position();
-  frame.set_cursor(top_window.left() + cursor_pos.x as u16, top_window.top() + cursor_pos.y as u16);
+  frame.set_cursor(text_area.left() + cursor_pos.x as u16, text_area.top() + cursor_pos.y as u16);
 }
 
 fn event_poll(state: &mut state::AppState) -> Result<bool> {
   if event::poll(Duration::from_millis(250)).context("event poll failed")? {
     let event = event::read().context("event read failed")?;
-    if let Event::Key(key) =  event {
-      if matches!(key.code, KeyCode::Char('c')) && matches!(key.modifiers, KeyModifiers::CONTROL) {
-        return Ok(true)
-      }
-    }
     handler::event_handler(event, state);
   }
-  Ok(false)
+  Ok(state.quit)
 }
@@ -0,0 +1,350 @@
+//! A single-line prompt over the status bar row, for anything that needs a
+//! bit of typed input: goto line, save as, file names in the explorer.
+//! The text lives in a `Document`, so wide characters are measured the same
+//! way as in the edit area.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+use crate::{
+  editor::{document::Document, location::Location, range::Range},
+  state::AppState,
+};
+
+/// called with the input on Enter. an error keeps the prompt open and is shown next to it.
+pub type Submit = Box<dyn FnMut(&mut AppState, &str) -> Result<()>>;
+
+/// how many entries each prompt's history keeps.
+const HISTORY_LIMIT: usize = 100;
+
+/// suggests completions for the input on Tab.
+pub trait Completer {
+  /// whole replacement inputs for `input`, best first.
+  fn complete(&self, input: &str) -> Vec<String>;
+}
+
+/// completes file paths, relative to `base` unless absolute or starting with '~'.
+pub struct PathCompleter {
+  pub base: PathBuf,
+}
+
+impl Completer for PathCompleter {
+  fn complete(&self, input: &str) -> Vec<String> {
+    let (dir, prefix) = match input.rfind('/') {
+      Some(slash) => (&input[..=slash], &input[slash + 1..]),
+      None => ("", input),
+    };
+    let lookup = if dir.starts_with('/') {
+      PathBuf::from(dir)
+    } else if let Some(rest) = dir.strip_prefix('~') {
+      match std::env::var_os("HOME") {
+        Some(home) => Path::new(&home).join(rest.trim_start_matches('/')),
+        None => return Vec::new(),
+      }
+    } else {
+      self.base.join(dir)
+    };
+    let Ok(entries) = std::fs::read_dir(lookup) else { return Vec::new() };
+    let mut completions: Vec<String> = entries
+      .filter_map(|entry| entry.ok())
+      .filter_map(|entry| {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+          return None;
+        }
+        let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+        Some(format!("{}{}{}", dir, name, if is_dir { "/" } else { "" }))
+      })
+      .collect();
+    completions.sort();
+    completions
+  }
+}
+
+pub struct Prompt {
+  pub label: String,
+  text: Document,
+  /// column of the cursor in `text`.
+  cursor: u32,
+  history: Vec<String>,
+  /// which history entry is shown, counted from the newest.
+  history_index: Option<usize>,
+  /// what was typed before browsing the history.
+  draft: String,
+  completer: Option<Box<dyn Completer>>,
+  /// the completions being cycled through with repeated Tab presses.
+  completions: Option<(Vec<String>, usize)>,
+  pub error: Option<String>,
+  submit: Option<Submit>,
+  /// where the widget drew the cursor last.
+  pub screen_cursor: u16,
+}
+
+impl Prompt {
+  pub fn new(label: &str, submit: Submit) -> Self {
+    Self {
+      label: label.to_string(),
+      text: Document::default(),
+      cursor: 0,
+      history: Vec::new(),
+      history_index: None,
+      draft: String::new(),
+      completer: None,
+      completions: None,
+      error: None,
+      submit: Some(submit),
+      screen_cursor: 0,
+    }
+  }
+
+  /// start with `text` typed in, the cursor after it.
+  pub fn with_text(mut self, text: &str) -> Self {
+    self.set_text(text);
+    self
+  }
+
+  pub fn with_completer(mut self, completer: impl Completer + 'static) -> Self {
+    self.completer = Some(Box::new(completer));
+    self
+  }
+
+  pub fn set_history(&mut self, history: Vec<String>) {
+    self.history = history;
+  }
+
+  pub fn text(&self) -> String {
+    self.text.line_text(0)
+  }
+
+  fn len(&self) -> u32 {
+    self.text.line(0).map_or(0, |line| line.len() as u32)
+  }
+
+  /// replace the input, moving the cursor to its end.
+  pub fn set_text(&mut self, text: &str) {
+    let text: String = text.chars().filter(|c| *c != '\n').collect();
+    self.text = Document::from(text.as_str());
+    self.cursor = self.len();
+  }
+
+  /// display width of the input before the cursor.
+  pub fn cursor_x(&self) -> u32 {
+    self.text.line(0).unwrap_or_default().iter()
+      .take(self.cursor as usize)
+      .map(|c| c.width)
+      .sum()
+  }
+
+  pub fn insert(&mut self, text: &str) {
+    let text: String = text.chars().filter(|c| *c != '\n').collect();
+    let _ = self.text.insert(Location { ln: 0, col: self.cursor }, &text);
+    self.cursor += text.chars().count() as u32;
+    self.edited();
+  }
+
+  pub fn backspace(&mut self) {
+    if self.cursor > 0 {
+      self.remove(self.cursor - 1, self.cursor);
+      self.cursor -= 1;
+    }
+  }
+
+  pub fn delete(&mut self) {
+    if self.cursor < self.len() {
+      self.remove(self.cursor, self.cursor + 1);
+    }
+  }
+
+  /// delete back to the start of the word before the cursor.
+  pub fn delete_word_backward(&mut self) {
+    let chars: Vec<char> = self.text().chars().collect();
+    let mut start = self.cursor as usize;
+    while start > 0 && chars[start - 1].is_whitespace() {
+      start -= 1;
+    }
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let word = start > 0 && is_word(chars[start - 1]);
+    while start > 0 && !chars[start - 1].is_whitespace() && is_word(chars[start - 1]) == word {
+      start -= 1;
+    }
+    self.remove(start as u32, self.cursor);
+    self.cursor = start as u32;
+  }
+
+  fn remove(&mut self, start: u32, end: u32) {
+    let _ = self.text.remove(Range::new(Location { ln: 0, col: start }, Location { ln: 0, col: end }));
+    self.edited();
+  }
+
+  fn edited(&mut self) {
+    self.completions = None;
+    self.error = None;
+  }
+
+  pub fn left(&mut self) {
+    self.cursor = self.cursor.saturating_sub(1);
+  }
+
+  pub fn right(&mut self) {
+    self.cursor = (self.cursor + 1).min(self.len());
+  }
+
+  pub fn home(&mut self) {
+    self.cursor = 0;
+  }
+
+  pub fn end(&mut self) {
+    self.cursor = self.len();
+  }
+
+  /// show the previous history entry.
+  pub fn history_up(&mut self) {
+    let next = self.history_index.map_or(0, |index| index + 1);
+    if next >= self.history.len() {
+      return;
+    }
+    if self.history_index.is_none() {
+      self.draft = self.text();
+    }
+    self.history_index = Some(next);
+    let entry = self.history[self.history.len() - 1 - next].clone();
+    self.set_text(&entry);
+  }
+
+  /// show the next history entry, or what was typed before browsing.
+  pub fn history_down(&mut self) {
+    match self.history_index {
+      Some(0) => {
+        self.history_index = None;
+        let draft = std::mem::take(&mut self.draft);
+        self.set_text(&draft);
+      },
+      Some(index) => {
+        self.history_index = Some(index - 1);
+        let entry = self.history[self.history.len() - index].clone();
+        self.set_text(&entry);
+      },
+      None => {},
+    }
+  }
+
+  /// complete the input: extend it to the completions' common prefix, or
+  /// cycle through them on repeated presses.
+  pub fn complete(&mut self) {
+    if let Some((completions, index)) = self.completions.as_mut() {
+      *index = (*index + 1) % completions.len();
+      let completion = completions[*index].clone();
+      let completions = self.completions.take();
+      self.set_text(&completion);
+      self.completions = completions;
+      return;
+    }
+    let Some(completer) = self.completer.as_ref() else { return };
+    let input = self.text();
+    let completions = completer.complete(&input);
+    let Some(first) = completions.first() else {
+      self.error = Some("no completions".to_string());
+      return;
+    };
+    let prefix = completions.iter().fold(first.clone(), |prefix, completion| common_prefix(&prefix, completion));
+    if completions.len() == 1 || prefix.chars().count() > input.chars().count() {
+      self.set_text(&prefix);
+      return;
+    }
+    // nothing to extend: the first press shows the first completion, later ones cycle
+    self.set_text(first);
+    self.completions = Some((completions, 0));
+  }
+
+  /// the number of completions being cycled through, for showing next to the input.
+  pub fn completion_hint(&self) -> Option<String> {
+    let (completions, index) = self.completions.as_ref()?;
+    Some(format!("{}/{}", index + 1, completions.len()))
+  }
+
+  pub fn take_submit(&mut self) -> Option<Submit> {
+    self.submit.take()
+  }
+
+  pub fn restore_submit(&mut self, submit: Submit) {
+    self.submit = Some(submit);
+  }
+}
+
+fn common_prefix(a: &str, b: &str) -> String {
+  a.chars().zip(b.chars()).take_while(|(a, b)| a == b).map(|(a, _)| a).collect()
+}
+
+/// remember `entry` at the end of `history`, without repeating it.
+pub fn remember(history: &mut Vec<String>, entry: String) {
+  history.retain(|old| *old != entry);
+  history.push(entry);
+  if history.len() > HISTORY_LIMIT {
+    history.remove(0);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn prompt() -> Prompt {
+    Prompt::new("> ", Box::new(|_, _| Ok(())))
+  }
+
+  #[test]
+  fn test_editing() {
+    let mut prompt = prompt().with_text("铁锈 rust");
+    assert_eq!(prompt.cursor_x(), 9);
+    prompt.delete_word_backward();
+    assert_eq!(prompt.text(), "铁锈 ");
+    prompt.home();
+    prompt.right();
+    assert_eq!(prompt.cursor_x(), 2);
+    prompt.insert("x");
+    prompt.delete();
+    assert_eq!(prompt.text(), "铁x ");
+    prompt.end();
+    prompt.backspace();
+    prompt.delete_word_backward();
+    assert_eq!(prompt.text(), "");
+  }
+
+  #[test]
+  fn test_history() {
+    let mut prompt = prompt().with_text("draft");
+    let mut history = Vec::new();
+    remember(&mut history, "one".to_string());
+    remember(&mut history, "two".to_string());
+    remember(&mut history, "one".to_string());
+    prompt.set_history(history);
+    prompt.history_up();
+    assert_eq!(prompt.text(), "one");
+    prompt.history_up();
+    assert_eq!(prompt.text(), "two");
+    prompt.history_up();
+    assert_eq!(prompt.text(), "two");
+    prompt.history_down();
+    prompt.history_down();
+    assert_eq!(prompt.text(), "draft");
+  }
+
+  #[test]
+  fn test_path_completion() {
+    let base = std::env::temp_dir().join(format!("pound-{}-prompt", std::process::id()));
+    std::fs::create_dir_all(base.join("src")).unwrap();
+    std::fs::write(base.join("src/main.rs"), "").unwrap();
+    std::fs::write(base.join("src/mod.rs"), "").unwrap();
+    let mut prompt = prompt().with_completer(PathCompleter { base: base.clone() }).with_text("sr");
+    prompt.complete();
+    assert_eq!(prompt.text(), "src/");
+    prompt.insert("m");
+    prompt.complete();
+    assert_eq!(prompt.text(), "src/main.rs");
+    prompt.complete();
+    assert_eq!(prompt.text(), "src/mod.rs");
+    assert_eq!(prompt.completion_hint().as_deref(), Some("2/2"));
+    std::fs::remove_dir_all(base).unwrap();
+  }
+}
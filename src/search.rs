@@ -0,0 +1,434 @@
+//! Search in files: the working directory is scanned on background threads
+//! and matches stream into the results panel as files are done.
+
+use std::{
+  collections::HashMap,
+  path::{Path, PathBuf},
+  sync::{Arc, atomic::{AtomicBool, Ordering}, mpsc::{self, Receiver}},
+  thread,
+};
+
+use anyhow::{Context, Result};
+use ignore::{WalkBuilder, WalkState};
+use regex::Regex;
+
+use crate::editor::{document::Document, location::Location};
+
+/// files bigger than this are not searched.
+const MAX_FILE_SIZE: u64 = 4 * 1024 * 1024;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Match {
+  pub location: Location,
+  /// length of the match in chars.
+  pub len: usize,
+  /// the whole line, without its '\n'.
+  pub line: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileMatches {
+  /// relative to the search root.
+  pub path: PathBuf,
+  pub matches: Vec<Match>,
+}
+
+/// the query as a regex. literal queries are escaped.
+pub fn build_regex(query: &str, is_regex: bool) -> Result<Regex> {
+  let pattern = if is_regex { query.to_string() } else { regex::escape(query) };
+  Regex::new(&pattern).with_context(|| format!("invalid regex {:?}", query))
+}
+
+/// every match of `regex` in `text`.
+pub fn find_matches(regex: &Regex, text: &str) -> Vec<Match> {
+  let mut matches = Vec::new();
+  for (ln, line) in text.lines().enumerate() {
+    for found in regex.find_iter(line).filter(|found| !found.is_empty()) {
+      matches.push(Match {
+        location: Location { ln: ln as u32, col: line[..found.start()].chars().count() as u32 },
+        len: found.as_str().chars().count(),
+        line: line.to_string(),
+      });
+    }
+  }
+  matches
+}
+
+/// a running or finished search.
+pub struct Search {
+  pub root: PathBuf,
+  pub regex: Regex,
+  results: Vec<FileMatches>,
+  receiver: Receiver<FileMatches>,
+  done: Arc<AtomicBool>,
+  cancelled: Arc<AtomicBool>,
+}
+
+impl Search {
+  pub fn start(root: &Path, regex: Regex) -> Self {
+    let (sender, receiver) = mpsc::channel();
+    let done = Arc::new(AtomicBool::new(false));
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let search = Self {
+      root: root.to_path_buf(),
+      regex: regex.clone(),
+      results: Vec::new(),
+      receiver,
+      done: done.clone(),
+      cancelled: cancelled.clone(),
+    };
+    let root = root.to_path_buf();
+    thread::spawn(move || {
+      WalkBuilder::new(&root)
+        .hidden(false)
+        .require_git(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build_parallel()
+        .run(|| {
+          let (sender, regex, root, cancelled) = (sender.clone(), regex.clone(), root.clone(), cancelled.clone());
+          Box::new(move |entry| {
+            if cancelled.load(Ordering::Relaxed) {
+              return WalkState::Quit;
+            }
+            let Ok(entry) = entry else { return WalkState::Continue };
+            let is_file = entry.file_type().is_some_and(|t| t.is_file());
+            let small = entry.metadata().is_ok_and(|metadata| metadata.len() <= MAX_FILE_SIZE);
+            if !is_file || !small {
+              return WalkState::Continue;
+            }
+            // skip binary files
+            let Ok(text) = std::fs::read_to_string(entry.path()) else { return WalkState::Continue };
+            let matches = find_matches(&regex, &text);
+            if !matches.is_empty() {
+              let path = entry.path().strip_prefix(&root).unwrap_or(entry.path()).to_path_buf();
+              if sender.send(FileMatches { path, matches }).is_err() {
+                return WalkState::Quit;
+              }
+            }
+            WalkState::Continue
+          })
+        });
+      done.store(true, Ordering::Release);
+    });
+    search
+  }
+
+  /// take the results that arrived since the last poll. files are kept sorted by path.
+  pub fn poll(&mut self) {
+    let mut arrived = false;
+    for file in self.receiver.try_iter() {
+      self.results.push(file);
+      arrived = true;
+    }
+    if arrived {
+      self.results.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+  }
+
+  pub fn is_done(&self) -> bool {
+    self.done.load(Ordering::Acquire)
+  }
+
+  pub fn results(&self) -> &[FileMatches] {
+    &self.results
+  }
+
+  pub fn match_count(&self) -> usize {
+    self.results.iter().map(|file| file.matches.len()).sum()
+  }
+
+  /// the file and match at `index`, counting matches across all files.
+  pub fn nth_match(&self, index: usize) -> Option<(&FileMatches, &Match)> {
+    let mut index = index;
+    for file in &self.results {
+      if index < file.matches.len() {
+        return Some((file, &file.matches[index]));
+      }
+      index -= file.matches.len();
+    }
+    None
+  }
+}
+
+impl Drop for Search {
+  fn drop(&mut self) {
+    self.cancelled.store(true, Ordering::Relaxed);
+  }
+}
+
+/// the lines of the open document with a match, searched again only when
+/// the document or the regex changes. the scrollbar wants them every frame.
+#[derive(Default)]
+pub struct HitLines {
+  /// the document's path and version, and the regex, the lines are for.
+  key: Option<(Option<PathBuf>, u64, String)>,
+  lines: Vec<u32>,
+}
+
+impl HitLines {
+  pub fn get(&mut self, document: &Document, regex: &Regex) -> &[u32] {
+    let key = (document.path().map(Path::to_path_buf), document.version(), regex.as_str().to_string());
+    if self.key.as_ref() != Some(&key) {
+      self.lines = find_matches(regex, &document.text()).iter().map(|found| found.location.ln).collect();
+      self.lines.dedup();
+      self.key = Some(key);
+    }
+    &self.lines
+  }
+}
+
+/// one accepted replacement: `len` chars at `location` become `text`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replacement {
+  pub location: Location,
+  pub len: usize,
+  pub text: String,
+}
+
+/// replacements accepted during a confirmed replace, applied once it ends.
+#[derive(Default)]
+pub struct Replacements {
+  files: HashMap<PathBuf, Vec<Replacement>>,
+}
+
+impl Replacements {
+  pub fn add(&mut self, path: &Path, found: &Match, text: String) {
+    let replacement = Replacement { location: found.location, len: found.len, text };
+    self.files.entry(path.to_path_buf()).or_default().push(replacement);
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.files.is_empty()
+  }
+
+  /// the accepted replacements per file, last in the file first, so earlier
+  /// locations stay valid while applying them.
+  pub fn into_files(self) -> Vec<(PathBuf, Vec<Replacement>)> {
+    let mut files: Vec<_> = self.files.into_iter().collect();
+    for (_, replacements) in files.iter_mut() {
+      replacements.sort_by(|a, b| b.location.partial_cmp(&a.location).unwrap());
+    }
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    files
+  }
+}
+
+/// which input of the panel keys go to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Field {
+  Query,
+  Replace,
+  Results,
+}
+
+/// a replace in progress: each match is confirmed in turn.
+pub struct Confirm {
+  /// the match being asked about, counted across files.
+  pub index: usize,
+  pub replacements: Replacements,
+}
+
+/// the search panel shown in place of the log.
+pub struct SearchPanel {
+  pub query: String,
+  pub replacement: String,
+  pub is_regex: bool,
+  pub field: Field,
+  pub search: Option<Search>,
+  /// the highlighted match, counted across files.
+  pub selected: usize,
+  pub confirm: Option<Confirm>,
+  pub error: Option<String>,
+}
+
+impl Default for SearchPanel {
+  fn default() -> Self {
+    Self {
+      query: String::new(),
+      replacement: String::new(),
+      is_regex: false,
+      field: Field::Query,
+      search: None,
+      selected: 0,
+      confirm: None,
+      error: None,
+    }
+  }
+}
+
+impl SearchPanel {
+  /// (re)start the search for the current query under `root`.
+  pub fn run(&mut self, root: &Path) {
+    self.search = None;
+    self.selected = 0;
+    self.error = None;
+    if self.query.is_empty() {
+      return;
+    }
+    match build_regex(&self.query, self.is_regex) {
+      Ok(regex) => {
+        self.search = Some(Search::start(root, regex));
+        self.field = Field::Results;
+      },
+      Err(err) => self.error = Some(format!("{:#}", err)),
+    }
+  }
+
+  pub fn text_mut(&mut self) -> Option<&mut String> {
+    match self.field {
+      Field::Query => Some(&mut self.query),
+      Field::Replace => Some(&mut self.replacement),
+      Field::Results => None,
+    }
+  }
+
+  pub fn next_field(&mut self) {
+    self.field = match self.field {
+      Field::Query => Field::Replace,
+      Field::Replace => Field::Results,
+      Field::Results => Field::Query,
+    };
+  }
+
+  pub fn up(&mut self) {
+    self.selected = self.selected.saturating_sub(1);
+    self.field = Field::Results;
+  }
+
+  pub fn down(&mut self) {
+    let count = self.search.as_ref().map_or(0, Search::match_count);
+    if self.selected + 1 < count {
+      self.selected += 1;
+    }
+    self.field = Field::Results;
+  }
+
+  /// the absolute path and location of the highlighted match.
+  pub fn selected_location(&self) -> Option<(PathBuf, Location)> {
+    let search = self.search.as_ref()?;
+    let (file, found) = search.nth_match(self.selected)?;
+    Some((search.root.join(&file.path), found.location))
+  }
+
+  /// start confirming each match of a finished search.
+  pub fn start_replace(&mut self) {
+    let Some(search) = self.search.as_ref() else { return };
+    if search.is_done() && search.match_count() > 0 {
+      self.selected = 0;
+      self.confirm = Some(Confirm { index: 0, replacements: Replacements::default() });
+    }
+  }
+
+  /// answer the confirmation for the current match: (y)es, (n)o, (a)ll or (q)uit.
+  /// returns the accepted replacements once there is nothing left to ask.
+  pub fn answer(&mut self, answer: char) -> Option<Replacements> {
+    let search = self.search.as_ref()?;
+    let confirm = self.confirm.as_mut()?;
+    let count = search.match_count();
+    let accept = |confirm: &mut Confirm, index: usize| {
+      if let Some((file, found)) = search.nth_match(index) {
+        let matched: String = found.line.chars().skip(found.location.col as usize).take(found.len).collect();
+        let replacement = if self.is_regex {
+          search.regex.replace(&matched, self.replacement.as_str()).to_string()
+        } else {
+          self.replacement.clone()
+        };
+        confirm.replacements.add(&search.root.join(&file.path), found, replacement);
+      }
+    };
+    match answer {
+      'y' => {
+        accept(confirm, confirm.index);
+        confirm.index += 1;
+      },
+      'n' => confirm.index += 1,
+      'a' => {
+        for index in confirm.index..count {
+          accept(confirm, index);
+        }
+        confirm.index = count;
+      },
+      'q' => confirm.index = count,
+      _ => return None,
+    }
+    if confirm.index < count {
+      self.selected = confirm.index;
+      return None;
+    }
+    self.confirm.take().map(|confirm| confirm.replacements)
+  }
+}
+
+/// apply replacements (last first) to `text`.
+pub fn replace_in_text(text: &str, replacements: &[Replacement]) -> String {
+  let mut lines: Vec<String> = text.split_inclusive('\n').map(str::to_string).collect();
+  for Replacement { location, len, text: replacement } in replacements {
+    let Some(line) = lines.get_mut(location.ln as usize) else { continue };
+    let mut chars: Vec<char> = line.chars().collect();
+    let start = (location.col as usize).min(chars.len());
+    let end = (start + len).min(chars.len());
+    chars.splice(start..end, replacement.chars());
+    *line = chars.into_iter().collect();
+  }
+  lines.concat()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_find_matches() {
+    let regex = build_regex("a.c", false).unwrap();
+    let matches = find_matches(&regex, "abc a.c\n铁a.c");
+    assert_eq!(matches.len(), 2);
+    assert_eq!(matches[0].location, Location { ln: 0, col: 4 });
+    assert_eq!(matches[1].location, Location { ln: 1, col: 1 });
+    assert_eq!(matches[1].line, "铁a.c");
+    let regex = build_regex("a.c", true).unwrap();
+    assert_eq!(find_matches(&regex, "abc a.c").len(), 2);
+    assert!(build_regex("(", true).is_err());
+  }
+
+  #[test]
+  fn test_hit_lines() {
+    let mut document = Document::from("foo foo\nbar\nfoo\n");
+    let mut hits = HitLines::default();
+    assert_eq!(hits.get(&document, &build_regex("foo", false).unwrap()), [0, 2]);
+    assert_eq!(hits.get(&document, &build_regex("bar", false).unwrap()), [1]);
+    document.insert(Location { ln: 0, col: 0 }, "bar").unwrap();
+    assert_eq!(hits.get(&document, &build_regex("bar", false).unwrap()), [0, 1]);
+  }
+
+  #[test]
+  fn test_replace_in_text() {
+    let regex = build_regex("foo", false).unwrap();
+    let text = "foo foo\nbar foo\n";
+    let mut replacements = Replacements::default();
+    for found in find_matches(&regex, text).iter().skip(1) {
+      replacements.add(Path::new("a"), found, "quux".to_string());
+    }
+    let files = replacements.into_files();
+    assert_eq!(replace_in_text(text, &files[0].1), "foo quux\nbar quux\n");
+  }
+
+  #[test]
+  fn test_search() {
+    let root = std::env::temp_dir().join(format!("pound-{}-search", std::process::id()));
+    std::fs::create_dir_all(root.join("src")).unwrap();
+    std::fs::write(root.join("src/a.rs"), "fn needle() {}\n").unwrap();
+    std::fs::write(root.join("b.txt"), "no\nneedle\nneedle needle\n").unwrap();
+    std::fs::write(root.join("skip.log"), "needle\n").unwrap();
+    std::fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+    let mut search = Search::start(&root, build_regex("needle", false).unwrap());
+    while !search.is_done() {
+      thread::sleep(std::time::Duration::from_millis(1));
+    }
+    search.poll();
+    let paths: Vec<&Path> = search.results().iter().map(|file| file.path.as_path()).collect();
+    assert_eq!(paths, [Path::new("b.txt"), Path::new("src/a.rs")]);
+    assert_eq!(search.match_count(), 4);
+    assert_eq!(search.nth_match(3).unwrap().1.location, Location { ln: 0, col: 3 });
+    std::fs::remove_dir_all(root).unwrap();
+  }
+}
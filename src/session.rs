@@ -0,0 +1,192 @@
+//! Sessions: what was open when pound quit, saved per working directory so
+//! `--restore` can bring it back.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+use serde_json::{Value, json};
+
+use crate::{editor::location::Location, macros::log, state::AppState};
+
+/// one open document and where it was looked at.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Buffer {
+  pub path: PathBuf,
+  pub cursor: Location,
+  pub offset_top: u32,
+  pub offset_left: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Session {
+  pub buffers: Vec<Buffer>,
+  /// index into `buffers` of the one that had focus.
+  pub active: usize,
+  pub show_log: bool,
+}
+
+impl Session {
+  pub fn capture(state: &AppState) -> Self {
+    let editor = &state.editor;
+    let buffers = editor.document().path()
+      .and_then(|path| path.canonicalize().ok())
+      .map(|path| {
+        let (offset_top, offset_left) = editor.offsets();
+        Buffer { path, cursor: editor.cursor_location(), offset_top, offset_left }
+      })
+      .into_iter()
+      .collect();
+    Self { buffers, active: 0, show_log: state.show_log }
+  }
+
+  /// rebuild the app state. buffers whose files are gone are skipped.
+  pub fn restore(self) -> Result<AppState> {
+    let mut buffers: Vec<Buffer> = Vec::with_capacity(self.buffers.len());
+    let mut active = 0;
+    for (i, buffer) in self.buffers.into_iter().enumerate() {
+      if !buffer.path.is_file() {
+        log!("{} no longer exists, not restoring it", buffer.path.display());
+        continue;
+      }
+      if i <= self.active {
+        active = buffers.len();
+      }
+      buffers.push(buffer);
+    }
+    let mut state = match buffers.get(active) {
+      Some(buffer) => {
+        let mut state = AppState::open(&buffer.path)?;
+        state.editor.set_cursor(buffer.cursor);
+        state.editor.set_offsets(buffer.offset_top, buffer.offset_left);
+        state
+      },
+      None => AppState::default(),
+    };
+    state.show_log = self.show_log;
+    Ok(state)
+  }
+
+  pub fn to_json(&self) -> Value {
+    let buffers: Vec<Value> = self.buffers.iter().map(|buffer| json!({
+      "path": buffer.path.to_string_lossy(),
+      "cursor": { "ln": buffer.cursor.ln, "col": buffer.cursor.col },
+      "offset_top": buffer.offset_top,
+      "offset_left": buffer.offset_left,
+    })).collect();
+    json!({
+      "buffers": buffers,
+      "active": self.active,
+      "layout": { "show_log": self.show_log },
+    })
+  }
+
+  /// parse a session. malformed buffers are dropped rather than failing the whole session.
+  pub fn from_json(value: &Value) -> Self {
+    let number = |value: &Value| value.as_u64().unwrap_or(0) as u32;
+    let buffers = value["buffers"].as_array().map(Vec::as_slice).unwrap_or_default().iter()
+      .filter_map(|buffer| Some(Buffer {
+        path: PathBuf::from(buffer["path"].as_str()?),
+        cursor: Location { ln: number(&buffer["cursor"]["ln"]), col: number(&buffer["cursor"]["col"]) },
+        offset_top: number(&buffer["offset_top"]),
+        offset_left: number(&buffer["offset_left"]),
+      }))
+      .collect();
+    Self {
+      buffers,
+      active: value["active"].as_u64().unwrap_or(0) as usize,
+      show_log: value["layout"]["show_log"].as_bool().unwrap_or(true),
+    }
+  }
+}
+
+/// where sessions and swap files are kept: `$XDG_STATE_HOME/pound`,
+/// falling back to `~/.local/state/pound`.
+pub fn state_dir() -> Option<PathBuf> {
+  let state_home = std::env::var_os("XDG_STATE_HOME")
+    .map(PathBuf::from)
+    .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))?;
+  Some(state_home.join("pound"))
+}
+
+/// a file under `state_dir/<kind>`, named after `key` with its separators escaped.
+pub fn state_file(state_dir: &Path, kind: &str, key: &Path, extension: &str) -> PathBuf {
+  let name: String = key.to_string_lossy()
+    .chars()
+    .map(|c| if c == '/' || c == '\\' || c == ':' { '%' } else { c })
+    .collect();
+  state_dir.join(kind).join(format!("{}.{}", name, extension))
+}
+
+/// where the session for `dir` is kept, one file per working directory.
+fn session_path(state_dir: &Path, dir: &Path) -> PathBuf {
+  state_file(state_dir, "sessions", dir, "json")
+}
+
+pub fn save(state_dir: &Path, dir: &Path, session: &Session) -> Result<()> {
+  let path = session_path(state_dir, dir);
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent).with_context(|| format!("failed to create {}", parent.display()))?;
+  }
+  std::fs::write(&path, session.to_json().to_string()).with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// the session saved for `dir`, if there is one.
+pub fn load(state_dir: &Path, dir: &Path) -> Result<Option<Session>> {
+  let path = session_path(state_dir, dir);
+  if !path.exists() {
+    return Ok(None);
+  }
+  let content = std::fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+  let value: Value = serde_json::from_str(&content).with_context(|| format!("failed to parse {}", path.display()))?;
+  Ok(Some(Session::from_json(&value)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_round_trip() {
+    let session = Session {
+      buffers: vec![Buffer {
+        path: PathBuf::from("/tmp/a.rs"),
+        cursor: Location { ln: 3, col: 4 },
+        offset_top: 2,
+        offset_left: 1,
+      }],
+      active: 0,
+      show_log: false,
+    };
+    assert_eq!(Session::from_json(&session.to_json()), session);
+    assert_eq!(Session::from_json(&json!({ "buffers": [{ "cursor": {} }] })), Session { show_log: true, ..Session::default() });
+  }
+
+  #[test]
+  fn test_save_and_load() {
+    let state_dir = std::env::temp_dir().join(format!("pound-{}-sessions", std::process::id()));
+    let cwd = Path::new("/home/someone/project");
+    assert_eq!(load(&state_dir, cwd).unwrap(), None);
+    let session = Session { show_log: false, ..Session::default() };
+    save(&state_dir, cwd, &session).unwrap();
+    assert!(state_dir.join("sessions/%home%someone%project.json").is_file());
+    assert_eq!(load(&state_dir, cwd).unwrap(), Some(session));
+    std::fs::remove_dir_all(state_dir).unwrap();
+  }
+
+  #[test]
+  fn test_restore_skips_missing_files() {
+    let session = Session {
+      buffers: vec![Buffer {
+        path: PathBuf::from("/nonexistent/pound/file.rs"),
+        cursor: Location { ln: 0, col: 0 },
+        offset_top: 0,
+        offset_left: 0,
+      }],
+      active: 0,
+      show_log: false,
+    };
+    let state = session.restore().unwrap();
+    assert!(state.editor.document().path().is_none());
+    assert!(!state.show_log);
+  }
+}
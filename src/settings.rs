@@ -0,0 +1,399 @@
+//! Settings from TOML files, layered: the global config, then the project's
+//! `.pound.toml`, each followed by its `[language.<id>]` table for the open
+//! document's language, then overrides from the command line and `:set`.
+
+use std::{collections::BTreeMap, path::{Path, PathBuf}};
+
+use anyhow::{Context, Result, bail};
+use ratatui::style::{Color, Style};
+use serde::{Deserialize, Serialize};
+use toml::{Table, Value};
+
+use crate::{editor::scroll::HorizontalScroll, macros::log, prompt::Completer};
+
+/// the project config, looked for in the working directory and above.
+pub const PROJECT_FILE: &str = ".pound.toml";
+
+/// every setting, for `:set` and its completion.
+const NAMES: [&str; 20] = [
+  "autosave", "cursor_column", "cursor_line", "expand_tabs", "horizontal_scroll", "indent_width", "insert_final_newline",
+  "keys", "line_numbers", "minimap", "rulers", "scroll_off", "scrollbar", "show_log", "show_whitespace", "side_scroll_off",
+  "tab_width", "theme", "trim_final_newlines", "trim_trailing_whitespace",
+];
+
+/// settings people expect from other editors that pound doesn't have. `:set`
+/// says so instead of calling them unknown.
+const UNSUPPORTED: [&str; 1] = ["wrap"];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineNumbers {
+  #[default]
+  Absolute,
+  /// distances from the cursor line.
+  Relative,
+  Off,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+  #[default]
+  Dark,
+  Light,
+}
+
+impl Theme {
+  /// the text area.
+  pub fn text(self) -> Style {
+    match self {
+      Self::Dark => Style::default(),
+      Self::Light => Style::default().fg(Color::Black).bg(Color::White),
+    }
+  }
+
+  /// the status bar and the prompt.
+  pub fn bar(self) -> Color {
+    match self {
+      Self::Dark => Color::Blue,
+      Self::Light => Color::LightBlue,
+    }
+  }
+
+  /// line numbers, fold markers and hints.
+  pub fn faint(self) -> Color {
+    match self {
+      Self::Dark => Color::DarkGray,
+      Self::Light => Color::Gray,
+    }
+  }
+
+  pub fn selection(self) -> Color {
+    match self {
+      Self::Dark => Color::Blue,
+      Self::Light => Color::LightCyan,
+    }
+  }
+
+  /// the cursor's line and column, when highlighted.
+  pub fn cursor_line(self) -> Color {
+    match self {
+      Self::Dark => Color::Indexed(236),
+      Self::Light => Color::Indexed(254),
+    }
+  }
+
+  /// ruler columns and the matching bracket.
+  pub fn guide(self) -> Color {
+    match self {
+      Self::Dark => Color::DarkGray,
+      Self::Light => Color::Gray,
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+  /// columns between tab stops.
+  pub tab_width: u32,
+  /// indent with spaces instead of tabs. the language decides when unset.
+  pub expand_tabs: Option<bool>,
+  /// spaces per indent level. the language decides when unset.
+  pub indent_width: Option<u32>,
+  pub line_numbers: LineNumbers,
+  pub theme: Theme,
+  pub show_log: bool,
+  pub show_whitespace: bool,
+  /// lines kept visible above and below the cursor.
+  pub scroll_off: u32,
+  /// columns kept visible left and right of the cursor.
+  pub side_scroll_off: u32,
+  pub horizontal_scroll: HorizontalScroll,
+  /// display columns to draw a guide line at. double width characters
+  /// count as two.
+  pub rulers: Vec<u32>,
+  /// highlight the line the cursor is on.
+  pub cursor_line: bool,
+  /// highlight the cursor's display column on every line.
+  pub cursor_column: bool,
+  /// a scrollbar right of the text, marking diagnostics, search hits and
+  /// git changes.
+  pub scrollbar: bool,
+  /// a zoomed out outline of the whole document next to the scrollbar.
+  pub minimap: bool,
+  /// save after this many milliseconds without edits.
+  pub autosave: Option<u64>,
+  pub trim_trailing_whitespace: bool,
+  pub insert_final_newline: bool,
+  pub trim_final_newlines: bool,
+  /// key bindings on top of the defaults, like `"ctrl+x" = "quit"`.
+  pub keys: BTreeMap<String, String>,
+}
+
+impl Default for Settings {
+  fn default() -> Self {
+    Self {
+      tab_width: 4,
+      expand_tabs: None,
+      indent_width: None,
+      line_numbers: LineNumbers::default(),
+      theme: Theme::default(),
+      show_log: true,
+      show_whitespace: false,
+      scroll_off: 0,
+      side_scroll_off: 0,
+      horizontal_scroll: HorizontalScroll::default(),
+      rulers: Vec::new(),
+      cursor_line: false,
+      cursor_column: false,
+      scrollbar: true,
+      minimap: false,
+      autosave: None,
+      trim_trailing_whitespace: false,
+      insert_final_newline: false,
+      trim_final_newlines: false,
+      keys: BTreeMap::new(),
+    }
+  }
+}
+
+impl Settings {
+  fn from_table(table: Table) -> Result<Self> {
+    let settings: Self = Value::Table(table).try_into()?;
+    if settings.tab_width == 0 {
+      bail!("tab_width must be at least 1");
+    }
+    Ok(settings)
+  }
+
+  /// the value of setting `name`, `None` when it is unset.
+  fn get(&self, name: &str) -> Option<Value> {
+    match Value::try_from(self) {
+      Ok(Value::Table(mut table)) => table.remove(name),
+      _ => None,
+    }
+  }
+}
+
+/// one config file.
+#[derive(Clone, Debug, Default)]
+struct Layer {
+  settings: Table,
+  /// tables under `[language.<id>]`.
+  languages: Table,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+  layers: Vec<Layer>,
+  /// from the command line and `:set`, over everything else.
+  overrides: Table,
+}
+
+impl Config {
+  /// read the global and project config files. a broken file is logged and
+  /// left out, the others still apply.
+  pub fn load(cwd: &Path) -> Self {
+    let files = [global_path(), cwd.ancestors().map(|dir| dir.join(PROJECT_FILE)).find(|path| path.is_file())];
+    let mut config = Self::default();
+    for path in files.into_iter().flatten().filter(|path| path.is_file()) {
+      match read_layer(&path) {
+        Ok(layer) => config.layers.push(layer),
+        Err(err) => log!("settings: {:#}", err),
+      }
+    }
+    config
+  }
+
+  /// the settings for documents of `language`, see `Language::language_id`.
+  pub fn settings(&self, language: &str) -> Settings {
+    let mut table = Table::new();
+    for layer in &self.layers {
+      merge(&mut table, &layer.settings);
+      if let Some(Value::Table(language)) = layer.languages.get(language) {
+        merge(&mut table, language);
+      }
+    }
+    merge(&mut table, &self.overrides);
+    Settings::from_table(table).unwrap_or_else(|err| {
+      log!("settings: {:#}", err);
+      Settings::default()
+    })
+  }
+
+  /// set one value on top of the config files, e.g. from the command line.
+  pub fn set_override(&mut self, name: &str, value: Value) -> Result<()> {
+    let mut overrides = self.overrides.clone();
+    let mut layer = Table::new();
+    layer.insert(name.to_string(), value);
+    merge(&mut overrides, &layer);
+    Settings::from_table(overrides.clone()).with_context(|| format!("invalid value for {}", name))?;
+    self.overrides = overrides;
+    Ok(())
+  }
+
+  /// apply a `:set` command: `name=value`, `name value`, `name` to turn a
+  /// switch on, `noname` to turn it off, `name!` to flip it, or `name?` to
+  /// only show the value. returns what the setting is now.
+  pub fn set(&mut self, command: &str, language: &str) -> Result<String> {
+    let command = command.trim();
+    let current = self.settings(language);
+    let (name, value) = match command.split_once(['=', ' ']) {
+      Some((name, value)) => (name.trim(), Some(parse_value(value.trim()))),
+      None => (command, None),
+    };
+    let (name, value) = match (name, value) {
+      (name, Some(value)) => (name, value),
+      (name, None) if name.ends_with('?') => {
+        let name = name.trim_end_matches('?');
+        check_name(name)?;
+        return Ok(describe(&current, name));
+      },
+      (name, None) if name.ends_with('!') => {
+        let name = name.trim_end_matches('!');
+        match current.get(name) {
+          Some(Value::Boolean(on)) => (name, Value::Boolean(!on)),
+          _ => {
+            check_name(name)?;
+            bail!("{} is not a switch", name)
+          },
+        }
+      },
+      (name, None) => match name.strip_prefix("no") {
+        Some(switch) if !NAMES.contains(&name) && NAMES.contains(&switch) => (switch, Value::Boolean(false)),
+        _ => (name, Value::Boolean(true)),
+      },
+    };
+    check_name(name)?;
+    self.set_override(name, value)?;
+    Ok(describe(&self.settings(language), name))
+  }
+}
+
+/// fail unless `name` is a setting, or `noname` of one.
+fn check_name(name: &str) -> Result<()> {
+  let switch = name.strip_prefix("no").unwrap_or(name);
+  if UNSUPPORTED.contains(&name) || UNSUPPORTED.contains(&switch) {
+    bail!("{} is not supported", switch);
+  }
+  if !NAMES.contains(&name) {
+    bail!("unknown setting {:?}", name);
+  }
+  Ok(())
+}
+
+fn describe(settings: &Settings, name: &str) -> String {
+  match settings.get(name) {
+    Some(value) => format!("{} = {}", name, value),
+    None => format!("{} is unset", name),
+  }
+}
+
+/// `~/.config/pound/config.toml`, or under `$XDG_CONFIG_HOME`.
+fn global_path() -> Option<PathBuf> {
+  let config_home = std::env::var_os("XDG_CONFIG_HOME")
+    .map(PathBuf::from)
+    .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+  Some(config_home.join("pound").join("config.toml"))
+}
+
+fn read_layer(path: &Path) -> Result<Layer> {
+  let text = std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+  let mut settings: Table = text.parse().with_context(|| format!("{} is not valid TOML", path.display()))?;
+  let languages = match settings.remove("language") {
+    Some(Value::Table(languages)) => languages,
+    Some(_) => bail!("{}: `language` should be a table of tables", path.display()),
+    None => Table::new(),
+  };
+  Settings::from_table(settings.clone()).with_context(|| format!("invalid settings in {}", path.display()))?;
+  for (name, language) in &languages {
+    let Value::Table(language) = language else { bail!("{}: [language.{}] should be a table", path.display(), name) };
+    Settings::from_table(language.clone()).with_context(|| format!("invalid settings for {} in {}", name, path.display()))?;
+  }
+  Ok(Layer { settings, languages })
+}
+
+/// copy `layer` over `base`. tables like `keys` are merged key by key.
+fn merge(base: &mut Table, layer: &Table) {
+  for (key, value) in layer {
+    match (base.get_mut(key), value) {
+      (Some(Value::Table(base)), Value::Table(layer)) => merge(base, layer),
+      _ => {
+        base.insert(key.clone(), value.clone());
+      },
+    }
+  }
+}
+
+/// a TOML value, or a bare string for convenience, so `theme=light` works.
+fn parse_value(text: &str) -> Value {
+  match format!("value = {}", text).parse::<Table>() {
+    Ok(mut table) => table.remove("value").unwrap_or(Value::String(text.to_string())),
+    Err(_) => Value::String(text.to_string()),
+  }
+}
+
+/// completes setting names for `:set`.
+pub struct SettingNames;
+
+impl Completer for SettingNames {
+  fn complete(&self, input: &str) -> Vec<String> {
+    let prefix = input.trim_start_matches("no");
+    let negated = &input[..input.len() - prefix.len()];
+    NAMES.iter()
+      .filter(|name| name.starts_with(prefix))
+      .map(|name| format!("{}{}", negated, name))
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_layers() {
+    let global = "tab_width = 8\nrulers = [80]\n[keys]\n\"ctrl+x\" = \"quit\"\n[language.rust]\nrulers = [100]\n";
+    let project = "show_log = false\n[keys]\n\"ctrl+o\" = \"save\"\n[language.python]\ntab_width = 2\n";
+    let dir = std::env::temp_dir().join(format!("pound-{}-settings", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("global.toml"), global).unwrap();
+    std::fs::write(dir.join("project.toml"), project).unwrap();
+    std::fs::write(dir.join("broken.toml"), "tab_width = \"wide\"").unwrap();
+    let config = Config {
+      layers: vec![read_layer(&dir.join("global.toml")).unwrap(), read_layer(&dir.join("project.toml")).unwrap()],
+      overrides: Table::new(),
+    };
+    assert!(read_layer(&dir.join("broken.toml")).is_err());
+    std::fs::remove_dir_all(dir).unwrap();
+    let rust = config.settings("rust");
+    assert_eq!((rust.tab_width, rust.rulers, rust.show_log), (8, vec![100], false));
+    assert_eq!(rust.keys.len(), 2);
+    assert_eq!(config.settings("python").tab_width, 2);
+  }
+
+  #[test]
+  fn test_set() {
+    let mut config = Config::default();
+    assert_eq!(config.set("tab_width=2", "rust").unwrap(), "tab_width = 2");
+    assert_eq!(config.set("theme light", "rust").unwrap(), "theme = \"light\"");
+    assert_eq!(config.set("noshow_log", "rust").unwrap(), "show_log = false");
+    assert_eq!(config.set("show_log!", "rust").unwrap(), "show_log = true");
+    assert_eq!(config.set("scroll_off?", "rust").unwrap(), "scroll_off = 0");
+    assert!(config.set("tab_width=wide", "rust").is_err());
+    assert!(config.set("tab_width=0", "rust").is_err());
+    assert!(config.set("colour=red", "rust").is_err());
+    assert_eq!(config.set("wrap", "rust").unwrap_err().to_string(), "wrap is not supported");
+    assert_eq!(config.set("nowrap", "rust").unwrap_err().to_string(), "wrap is not supported");
+    assert_eq!(config.settings("go").tab_width, 2);
+    assert_eq!(SettingNames.complete("nosh"), vec!["noshow_log", "noshow_whitespace"]);
+  }
+
+  #[test]
+  fn test_names() {
+    let settings = Settings { expand_tabs: Some(true), indent_width: Some(2), autosave: Some(500), ..Settings::default() };
+    let Ok(Value::Table(table)) = Value::try_from(&settings) else { panic!("settings should serialize to a table") };
+    assert_eq!(table.keys().map(String::as_str).collect::<Vec<_>>(), NAMES);
+  }
+}
@@ -1,15 +1,820 @@
-use crate::editor::Context;
+use std::{collections::HashMap, path::{Path, PathBuf}, time::Duration};
+use anyhow::Result;
+use ratatui::layout::Rect;
+use crate::completion::{self, Completion};
+use crate::editor::{Context, diagnostic::Diagnostic, document::{self, Document}, location::Location, range::Range, scroll::ScrollOff, whitespace::SaveActions};
+use crate::lsp::{self, LspPosition};
+use crate::{editorconfig, git, goto, session, swap, watch, explorer::Explorer, finder::{FileIndex, Finder}, search::{self, SearchPanel}, keymap::{Command, Keymap}, prompt::{self, PathCompleter, Prompt}, settings::{Config, SettingNames, Settings}, macros::log};
+
+const RECOVERY_PROMPT: &str = "found unsaved changes: (r)ecover, (d)iff or (x) discard?";
+const CONFLICT_PROMPT: &str = "file changed on disk: (r)eload, (d)iff or (k)eep yours?";
+
+/// the buttons of the quit dialog.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QuitChoice {
+  Save,
+  Discard,
+  Cancel,
+}
+
+impl QuitChoice {
+  pub fn label(self) -> &'static str {
+    match self {
+      Self::Save => "Save",
+      Self::Discard => "Discard",
+      Self::Cancel => "Cancel",
+    }
+  }
+
+  pub fn next(self) -> Self {
+    match self {
+      Self::Save => Self::Discard,
+      Self::Discard => Self::Cancel,
+      Self::Cancel => Self::Save,
+    }
+  }
+
+  pub fn previous(self) -> Self {
+    self.next().next()
+  }
+}
 
 pub struct AppState {
   pub editor: Context,
   pub show_log: bool,
+  /// draw spaces, tabs and line breaks as glyphs.
+  pub show_whitespace: bool,
+  pub config: Config,
+  /// `config` resolved for the open document's language.
+  pub settings: Settings,
+  /// where the edit area was drawn last, for mapping mouse events.
+  pub edit_area: Rect,
+  pub language_server: Option<lsp::Client>,
+  /// a one-line message for the status bar, like hover text.
+  pub message: Option<String>,
+  /// the completion popup, while it is open.
+  pub completion: Option<Completion>,
+  pub completion_sources: Vec<Box<dyn completion::Source>>,
+  /// changes against git's HEAD, when the file is tracked.
+  pub git_gutter: Option<git::Gutter>,
+  /// where swap files are kept, see `session::state_dir`.
+  pub state_dir: Option<PathBuf>,
+  pub swap: Option<swap::Swap>,
+  /// unsaved text found in a swap file, waiting for the user to decide what to do with it.
+  pub recovery: Option<String>,
+  pub watcher: Option<watch::FileWatcher>,
+  /// saves after a pause in typing and on focus loss, when turned on.
+  pub autosave: Option<watch::Autosave>,
+  /// what another program wrote to the file while we had unsaved changes.
+  pub disk_conflict: Option<String>,
+  pub keymap: Keymap,
+  /// the selected button while the quit dialog is open.
+  pub quit_dialog: Option<QuitChoice>,
+  /// set once the app should exit.
+  pub quit: bool,
+  pub explorer: Explorer,
+  /// where the sidebar was drawn last, for mapping mouse events.
+  pub sidebar_area: Rect,
+  /// whether the sidebar border is being dragged.
+  pub resizing_sidebar: bool,
+  /// where the scrollbar and minimap were drawn last, for mapping mouse events.
+  pub scrollbar_area: Rect,
+  /// whether the scrollbar is being dragged.
+  pub dragging_scrollbar: bool,
+  /// the Ctrl+P file finder, while it is open.
+  pub finder: Option<Finder>,
+  pub search_panel: Option<SearchPanel>,
+  /// the search panel's hits in the open document, for the scrollbar.
+  pub search_hits: search::HitLines,
+  /// whether keys go to the search panel.
+  pub search_focused: bool,
+  /// the prompt over the status bar, while it is open.
+  pub prompt: Option<Prompt>,
+  /// what was submitted to each prompt, keyed by its label, oldest first.
+  pub prompt_history: HashMap<String, Vec<String>>,
 }
 
 impl Default for AppState {
   fn default() -> Self {
+    let settings = Settings::default();
     Self {
       editor: Context::with_size(50, 50),
-      show_log: true
+      show_log: settings.show_log,
+      show_whitespace: settings.show_whitespace,
+      config: Config::default(),
+      settings,
+      edit_area: Rect::default(),
+      language_server: None,
+      message: None,
+      completion: None,
+      completion_sources: completion::default_sources(),
+      git_gutter: None,
+      state_dir: session::state_dir(),
+      swap: None,
+      recovery: None,
+      watcher: None,
+      autosave: None,
+      disk_conflict: None,
+      keymap: Keymap::default(),
+      quit_dialog: None,
+      quit: false,
+      explorer: Explorer::new(&std::env::current_dir().unwrap_or_else(|_| ".".into())),
+      sidebar_area: Rect::default(),
+      resizing_sidebar: false,
+      scrollbar_area: Rect::default(),
+      dragging_scrollbar: false,
+      finder: None,
+      search_panel: None,
+      search_hits: search::HitLines::default(),
+      search_focused: false,
+      prompt: None,
+      prompt_history: HashMap::new(),
+    }
+  }
+}
+
+impl AppState {
+  pub fn open(path: &Path) -> Result<Self> {
+    let mut state = Self {
+      editor: Context::with_document(Document::open(path)?, 50, 50),
+      ..Self::default()
+    };
+    state.configure_editor();
+    state.start_language_server();
+    if !state.editor.document().is_large() {
+      state.git_gutter = git::Gutter::open(path);
+      if let Some(state_dir) = &state.state_dir {
+        state.swap = swap::Swap::new(state_dir, path);
+        state.recovery = swap::recoverable(state_dir, path);
+      }
+      state.watcher = Some(watch::FileWatcher::new(path));
+      if state.recovery.is_some() {
+        state.message = Some(RECOVERY_PROMPT.to_string());
+      }
+    }
+    Ok(state)
+  }
+
+  /// resolve the settings for the open document's language and apply them.
+  /// app wide ones only change when they differ from `previous`, so e.g. a
+  /// log panel opened since is left alone.
+  pub fn apply_settings(&mut self, previous: Option<&Settings>) {
+    self.settings = self.config.settings(self.editor.language().language_id());
+    self.configure_editor();
+    let settings = &self.settings;
+    if previous.map(|p| p.show_log) != Some(settings.show_log) {
+      self.show_log = settings.show_log;
+    }
+    if previous.map(|p| p.show_whitespace) != Some(settings.show_whitespace) {
+      self.show_whitespace = settings.show_whitespace;
+    }
+    if previous.map(|p| p.autosave) != Some(settings.autosave) {
+      self.autosave = settings.autosave.map(|delay| watch::Autosave::new(Duration::from_millis(delay)));
+    }
+    if previous.map(|p| &p.keys) != Some(&settings.keys) {
+      for (key, command) in &settings.keys {
+        if let Err(err) = self.keymap.apply(&format!("{}={}", key, command)) {
+          log!("settings: {:#}", err);
+        }
+      }
+    }
+  }
+
+  /// apply the editor settings, then whatever `.editorconfig` files say about
+  /// the document.
+  pub fn configure_editor(&mut self) {
+    let settings = &self.settings;
+    let language = self.editor.language();
+    let indent_unit = match (settings.expand_tabs, settings.indent_width) {
+      (Some(false), _) => "\t".to_string(),
+      (_, Some(width)) => " ".repeat(width as usize),
+      (Some(true), None) if language.indent_unit() == "\t" => " ".repeat(settings.tab_width as usize),
+      _ => language.indent_unit().to_string(),
+    };
+    self.editor.set_indent_unit(&indent_unit);
+    self.editor.set_tab_width(Some(settings.tab_width));
+    self.editor.set_save_actions(SaveActions {
+      trim_trailing_whitespace: settings.trim_trailing_whitespace,
+      insert_final_newline: settings.insert_final_newline,
+      trim_final_newlines: settings.trim_final_newlines,
+    });
+    self.editor.set_rulers(settings.rulers.clone());
+    self.editor.set_scroll_off(ScrollOff {
+      vertical: settings.scroll_off,
+      horizontal: settings.side_scroll_off,
+      horizontal_scroll: settings.horizontal_scroll,
+    });
+    let settings = self.editor.document().path().map(editorconfig::Settings::for_path);
+    if let Some(settings) = settings {
+      settings.apply(&mut self.editor);
+    }
+  }
+
+  /// start the language server configured for the document's language, if any.
+  /// a server that fails to start is logged and otherwise ignored. the
+  /// document is opened once the server answers, see `poll_language_server`.
+  fn start_language_server(&mut self) {
+    let document = self.editor.document();
+    let (Some(path), Some((command, args))) = (document.path(), self.editor.language().language_server()) else {
+      return;
+    };
+    if document.is_large() {
+      return;
+    }
+    let started = lsp::Client::spawn(command, args).and_then(|mut client| {
+      client.initialize(path)?;
+      Ok(client)
+    });
+    match started {
+      Ok(client) => {
+        log!("started language server {}", command);
+        self.language_server = Some(client);
+      },
+      Err(err) => log!("{:#}", err),
+    }
+  }
+
+  /// send the edits made since the last sync to the language server.
+  pub fn sync_language_server(&mut self) {
+    let Some(client) = self.language_server.as_mut().filter(|client| client.is_initialized()) else { return };
+    let changes = self.editor.take_changes();
+    let document = self.editor.document();
+    if let Err(err) = client.did_change(&changes, || document.text()) {
+      log!("{:#}", err);
+    }
+  }
+
+  /// handle whatever the language server sent since the last poll.
+  pub fn poll_language_server(&mut self) {
+    let Some(client) = self.language_server.as_mut() else { return };
+    for event in client.poll() {
+      match event {
+        lsp::Event::Initialized => {
+          let language_id = self.editor.language().language_id();
+          let text = self.editor.document().text();
+          let Some(client) = self.language_server.as_mut() else { continue };
+          if let Err(err) = client.did_open(language_id, &text) {
+            log!("{:#}", err);
+          }
+          // the text sent has every edit so far, only later ones are synced
+          self.editor.track_changes();
+          self.editor.take_changes();
+        },
+        lsp::Event::Diagnostics(diagnostics) => {
+          log!("{} diagnostics", diagnostics.len());
+          let diagnostics = diagnostics.into_iter().map(|d| Diagnostic {
+            range: Range::new(self.location_from_lsp(d.start), self.location_from_lsp(d.end)),
+            severity: d.severity,
+            message: d.message,
+            source: d.source,
+          }).collect();
+          self.editor.set_diagnostics(diagnostics);
+        },
+        lsp::Event::Hover(text) => {
+          for line in text.lines() {
+            log!("{}", line);
+          }
+          self.message = text.lines().find(|line| !line.trim().is_empty()).map(str::to_string);
+        },
+        lsp::Event::Definition { path, position } => {
+          if self.editor.document().path().and_then(|p| p.canonicalize().ok()) == path.canonicalize().ok() {
+            let location = self.location_from_lsp(position);
+            self.editor.set_cursor(location);
+          } else {
+            self.message = Some(format!("definition in {}:{}", path.display(), position.line + 1));
+          }
+        },
+        lsp::Event::Completion(items) => {
+          let Some(completion) = self.completion.as_mut() else { continue };
+          completion.add(items.into_iter().map(|item| completion::Candidate {
+            label: item.label,
+            insert_text: item.insert_text,
+            detail: item.detail,
+          }).collect());
+        },
+        lsp::Event::Message(text) => log!("{}", text),
+      }
+    }
+  }
+
+  pub fn run_command(&mut self, command: Command) {
+    match command {
+      Command::Quit => self.request_quit(),
+      Command::ForceQuit => self.quit = true,
+      Command::Save => self.save(),
+      Command::ToggleExplorer => self.explorer.toggle(),
+      Command::FindFile => self.open_finder(),
+      Command::GotoLine => self.open_prompt(Prompt::new(goto::LABEL, Box::new(|state, input| {
+        let location = goto::parse(input, state.editor.cursor_location(), state.editor.document().line_count())?;
+        state.editor.goto(location);
+        Ok(())
+      }))),
+      Command::Fold => self.editor.fold(),
+      Command::Unfold => self.editor.unfold(),
+      Command::FoldAll => self.editor.fold_all(),
+      Command::UnfoldAll => self.editor.unfold_all(),
+      Command::ToggleWhitespace => self.show_whitespace = !self.show_whitespace,
+      Command::CenterLine => self.editor.center_cursor_line(),
+      Command::LineToTop => self.editor.cursor_line_to_top(),
+      Command::LineToBottom => self.editor.cursor_line_to_bottom(),
+      Command::Set => self.open_prompt(Prompt::new(":set ", Box::new(|state, input| {
+        let language = state.editor.language().language_id();
+        match state.config.set(input, language) {
+          Ok(message) => {
+            let previous = state.settings.clone();
+            state.apply_settings(Some(&previous));
+            state.message = Some(message);
+            Ok(())
+          },
+          Err(err) => {
+            log!("settings: {:#}", err);
+            Err(err)
+          },
+        }
+      })).with_completer(SettingNames)),
+      Command::SearchFiles => {
+        self.search_panel.get_or_insert_with(SearchPanel::default).field = search::Field::Query;
+        self.search_focused = true;
+      },
+    }
+  }
+
+  /// open `prompt` over the status bar, with what was submitted to prompts
+  /// of the same label before as its history.
+  pub fn open_prompt(&mut self, mut prompt: Prompt) {
+    prompt.set_history(self.prompt_history.get(&prompt.label).cloned().unwrap_or_default());
+    self.prompt = Some(prompt);
+  }
+
+  /// hand the prompt's input to its callback. an error keeps the prompt open
+  /// and shows next to the input.
+  pub fn submit_prompt(&mut self) {
+    let Some(mut prompt) = self.prompt.take() else { return };
+    let Some(mut submit) = prompt.take_submit() else { return };
+    let input = prompt.text();
+    match submit(self, &input) {
+      Ok(()) => prompt::remember(self.prompt_history.entry(prompt.label).or_default(), input),
+      Err(err) => {
+        prompt.error = Some(format!("{:#}", err));
+        prompt.restore_submit(submit);
+        // the callback may have opened a prompt of its own
+        self.prompt.get_or_insert(prompt);
+      },
+    }
+  }
+
+  /// ask for a file name and save there.
+  pub fn save_as(&mut self) {
+    let base = std::env::current_dir().unwrap_or_else(|_| ".".into());
+    let prompt = Prompt::new("save as: ", Box::new(|state, input| {
+      let path = Path::new(input.trim());
+      if path.as_os_str().is_empty() {
+        anyhow::bail!("no file name given");
+      }
+      state.editor.save_as(path)?;
+      state.configure_editor();
+      state.message = Some(format!("saved as {}", path.display()));
+      if !state.editor.document().is_large() {
+        state.git_gutter = git::Gutter::open(path);
+        state.swap = state.state_dir.as_deref().and_then(|state_dir| swap::Swap::new(state_dir, path));
+        state.watcher = Some(watch::FileWatcher::new(path));
+      }
+      state.start_language_server();
+      state.explorer.refresh();
+      Ok(())
+    }));
+    self.open_prompt(prompt.with_completer(PathCompleter { base }));
+  }
+
+  /// search the working directory for the panel's query.
+  pub fn run_search(&mut self) {
+    let Some(panel) = self.search_panel.as_mut() else { return };
+    panel.run(&std::env::current_dir().unwrap_or_else(|_| ".".into()));
+  }
+
+  /// open the highlighted search result and leave the panel open.
+  pub fn open_search_result(&mut self) {
+    let Some((path, location)) = self.search_panel.as_ref().and_then(|panel| panel.selected_location()) else { return };
+    self.open_file(&path);
+    if self.editor.document().path().and_then(|p| p.canonicalize().ok()) == path.canonicalize().ok() {
+      self.editor.set_cursor(location);
+      self.search_focused = false;
+    }
+  }
+
+  /// answer the confirmation of a replace, applying the accepted replacements once done.
+  pub fn answer_replace(&mut self, answer: char) {
+    let Some(panel) = self.search_panel.as_mut() else { return };
+    let Some(replacements) = panel.answer(answer) else { return };
+    if replacements.is_empty() {
+      self.message = Some("nothing replaced".to_string());
+      return;
+    }
+    let open = self.editor.document().path().and_then(|path| path.canonicalize().ok());
+    let (mut files, mut count, mut skipped) = (0, 0, false);
+    for (path, replacements) in replacements.into_files() {
+      if path.canonicalize().ok() == open {
+        // the matches were found on disk, so they only fit an unchanged buffer
+        if self.editor.document().is_dirty() {
+          log!("not replacing in {}, it has unsaved changes", path.display());
+          skipped = true;
+          continue;
+        }
+        // the open document is edited in place, so the replace can be undone
+        let ranges: Vec<(Range, String)> = replacements.iter().map(|replacement| {
+          let Location { ln, col } = replacement.location;
+          (Range::new(replacement.location, Location { ln, col: col + replacement.len as u32 }), replacement.text.clone())
+        }).collect();
+        self.editor.replace_ranges(&ranges);
+      } else {
+        let result = std::fs::read_to_string(&path)
+          .and_then(|text| std::fs::write(&path, search::replace_in_text(&text, &replacements)));
+        if let Err(err) = result {
+          log!("failed to replace in {}: {}", path.display(), err);
+        }
+      }
+      count += replacements.len();
+      files += 1;
+    }
+    self.message = Some(match skipped {
+      true => format!("replaced {} matches in {} files, save the open file to replace in it", count, files),
+      false => format!("replaced {} matches in {} files", count, files),
+    });
+    self.run_search();
+  }
+
+  /// open the file finder over the working directory, indexing it afresh.
+  pub fn open_finder(&mut self) {
+    let root = std::env::current_dir().unwrap_or_else(|_| ".".into());
+    self.finder = Some(Finder::new(&root, FileIndex::spawn(&root)));
+  }
+
+  /// open the file highlighted in the finder, at the location typed after it.
+  pub fn accept_finder(&mut self) {
+    let Some((path, location)) = self.finder.take().and_then(|finder| finder.selection()) else { return };
+    self.open_file(&path);
+    if let Some(location) = location.filter(|_| self.editor.document().path() == Some(path.as_path())) {
+      self.editor.set_cursor(location);
+    }
+  }
+
+  /// replace the open document with the file at `path`. app wide state like
+  /// the layout, key bindings, the explorer and the search panel carries over.
+  pub fn open_file(&mut self, path: &Path) {
+    if self.editor.document().path() == Some(path) {
+      return;
+    }
+    if self.editor.document().is_dirty() {
+      self.message = Some("unsaved changes, save them first".to_string());
+      return;
+    }
+    let opened = match Self::open(path) {
+      Ok(opened) => opened,
+      Err(err) => return self.message = Some(format!("{:#}", err)),
+    };
+    self.shutdown();
+    let previous = std::mem::replace(self, opened);
+    self.show_log = previous.show_log;
+    self.show_whitespace = previous.show_whitespace;
+    self.completion_sources = previous.completion_sources;
+    self.keymap = previous.keymap;
+    self.autosave = previous.autosave;
+    self.config = previous.config;
+    self.apply_settings(Some(&previous.settings));
+    self.explorer = previous.explorer;
+    self.explorer.focused = false;
+    self.prompt_history = previous.prompt_history;
+    self.search_panel = previous.search_panel;
+    self.search_hits = previous.search_hits;
+  }
+
+  /// quit, or ask what to do with unsaved changes first.
+  pub fn request_quit(&mut self) {
+    if self.editor.document().is_dirty() {
+      self.quit_dialog = Some(QuitChoice::Save);
+    } else {
+      self.quit = true;
+    }
+  }
+
+  pub fn answer_quit_dialog(&mut self, choice: QuitChoice) {
+    self.quit_dialog = None;
+    match choice {
+      QuitChoice::Save => {
+        self.save();
+        // a failed save leaves its error in the status bar instead of quitting
+        self.quit = !self.editor.document().is_dirty();
+      },
+      QuitChoice::Discard => {
+        if let (Some(state_dir), Some(path)) = (&self.state_dir, self.editor.document().path()) {
+          swap::discard(state_dir, path);
+        }
+        self.swap = None;
+        self.quit = true;
+      },
+      QuitChoice::Cancel => {},
+    }
+  }
+
+  pub fn save(&mut self) {
+    if self.editor.document().path().is_none() {
+      return self.save_as();
+    }
+    match self.editor.save() {
+      Ok(()) => self.message = Some("saved".to_string()),
+      Err(err) => self.message = Some(format!("{:#}", err)),
     }
+    if let Some(watcher) = self.watcher.as_mut() {
+      watcher.sync();
+    }
+    self.update_swap();
+  }
+
+  /// save if autosave is on and the document has been idle long enough.
+  pub fn autosave(&mut self) {
+    let Some(autosave) = self.autosave.as_mut() else { return };
+    if autosave.is_due(self.editor.document()) {
+      self.save();
+    }
+  }
+
+  /// save right away if autosave is on, e.g. when the terminal loses focus.
+  pub fn autosave_now(&mut self) {
+    if self.autosave.is_some() && self.editor.document().is_dirty() && self.editor.document().path().is_some() {
+      self.save();
+    }
+  }
+
+  /// reload the file if another program changed it. with unsaved changes,
+  /// ask first.
+  pub fn check_file(&mut self) {
+    let Some(watcher) = self.watcher.as_mut() else { return };
+    if !watcher.changed() {
+      return;
+    }
+    let Some(path) = self.editor.document().path() else { return };
+    let text = match document::read_text(path) {
+      Ok((text, _)) => text,
+      Err(err) => return log!("{:#}", err),
+    };
+    // e.g. our own save, or a touch. the watcher already took the new stamp
+    if text == self.editor.document().text() {
+      return;
+    }
+    if self.editor.document().is_dirty() {
+      log_diff(&self.editor.document().text(), &text);
+      self.show_log = true;
+      self.disk_conflict = Some(text);
+      self.message = Some(CONFLICT_PROMPT.to_string());
+    } else {
+      self.editor.reload(&text);
+      self.message = Some("reloaded, the file changed on disk".to_string());
+    }
+  }
+
+  /// handle the answer to the prompt shown when the file changed under unsaved edits.
+  pub fn answer_disk_conflict(&mut self, answer: char) {
+    let Some(text) = self.disk_conflict.as_ref() else { return };
+    match answer {
+      'r' => {
+        let text = text.clone();
+        self.editor.reload(&text);
+        self.disk_conflict = None;
+        self.message = Some("reloaded".to_string());
+      },
+      'd' => {
+        log_diff(&self.editor.document().text(), text);
+        self.show_log = true;
+      },
+      'k' => {
+        self.disk_conflict = None;
+        self.message = None;
+      },
+      _ => self.message = Some(CONFLICT_PROMPT.to_string()),
+    }
+  }
+
+  pub fn update_swap(&mut self) {
+    let Some(swap) = self.swap.as_mut() else { return };
+    if let Err(err) = swap.update(self.editor.document()) {
+      log!("{:#}", err);
+    }
+  }
+
+  /// handle the answer to the recovery prompt shown when a swap file was found.
+  pub fn answer_recovery(&mut self, answer: char) {
+    let Some(text) = self.recovery.as_ref() else { return };
+    match answer {
+      'r' => {
+        let text = text.clone();
+        let line_count = self.editor.document().line_count() as u32;
+        self.editor.replace_lines(0, line_count, &text);
+        self.recovery = None;
+        self.message = Some("recovered unsaved changes".to_string());
+      },
+      'd' => {
+        log_diff(&self.editor.document().text(), text);
+        self.show_log = true;
+        self.message = Some(RECOVERY_PROMPT.to_string());
+      },
+      'x' => {
+        if let (Some(state_dir), Some(path)) = (&self.state_dir, self.editor.document().path()) {
+          swap::discard(state_dir, path);
+        }
+        self.recovery = None;
+        self.message = None;
+      },
+      _ => self.message = Some(RECOVERY_PROMPT.to_string()),
+    }
+  }
+
+  pub fn update_git_gutter(&mut self) {
+    if let Some(gutter) = self.git_gutter.as_mut() {
+      gutter.update(self.editor.document());
+    }
+  }
+
+  /// move to the next changed hunk, wrapping around at the end.
+  pub fn next_hunk(&mut self) {
+    let Some(gutter) = self.git_gutter.as_ref() else { return };
+    let ln = self.editor.cursor_location().ln;
+    let hunks = gutter.hunks();
+    if let Some(hunk) = hunks.iter().find(|hunk| hunk.new_start > ln).or(hunks.first()) {
+      self.editor.set_cursor(Location { ln: hunk.new_start, col: 0 });
+    }
+  }
+
+  /// move to the previous changed hunk, wrapping around at the start.
+  pub fn previous_hunk(&mut self) {
+    let Some(gutter) = self.git_gutter.as_ref() else { return };
+    let ln = self.editor.cursor_location().ln;
+    let hunks = gutter.hunks();
+    if let Some(hunk) = hunks.iter().rev().find(|hunk| hunk.new_start < ln).or(hunks.last()) {
+      self.editor.set_cursor(Location { ln: hunk.new_start, col: 0 });
+    }
+  }
+
+  /// show what the hunk under the cursor looked like in HEAD.
+  pub fn preview_hunk(&mut self) {
+    let ln = self.editor.cursor_location().ln;
+    let Some(hunk) = self.git_gutter.as_ref().and_then(|gutter| gutter.hunk_at(ln)) else {
+      self.message = Some("no change here".to_string());
+      return;
+    };
+    for line in &hunk.old_lines {
+      log!("- {}", line.trim_end_matches('\n'));
+    }
+    self.message = Some(match hunk.old_lines.len() {
+      0 => "added lines".to_string(),
+      len => format!("{} original line(s): {}", len, hunk.old_lines[0].trim_end()),
+    });
+  }
+
+  /// put the hunk under the cursor back the way it is in HEAD.
+  pub fn revert_hunk(&mut self) {
+    let ln = self.editor.cursor_location().ln;
+    let Some(gutter) = self.git_gutter.as_mut() else { return };
+    // hunks from before the last edit could put back the wrong lines
+    if !gutter.is_current(self.editor.document()) {
+      self.message = Some("changes are still being compared".to_string());
+      return;
+    }
+    let Some(hunk) = gutter.hunk_at(ln).cloned() else {
+      self.message = Some("no change here".to_string());
+      return;
+    };
+    self.editor.replace_lines(hunk.new_start, hunk.new_len, &hunk.old_lines.concat());
+    gutter.refresh(self.editor.document());
+  }
+
+  pub fn request_hover(&mut self) {
+    self.request(|client, position| client.hover(position));
+  }
+
+  pub fn request_definition(&mut self) {
+    self.request(|client, position| client.definition(position));
+  }
+
+  /// open the completion popup at the cursor. the language server's answer
+  /// is added to it once it arrives.
+  pub fn open_completion(&mut self) {
+    self.completion = Some(Completion::open(&self.editor, &self.completion_sources));
+    if self.language_server.is_some() {
+      self.request(|client, position| client.completion(position));
+    }
+  }
+
+  /// keep the popup in step with the text typed since it opened.
+  pub fn update_completion(&mut self) {
+    if let Some(completion) = self.completion.as_mut() {
+      if !completion.update(&self.editor) {
+        self.completion = None;
+      }
+    }
+  }
+
+  pub fn accept_completion(&mut self) {
+    if let Some(completion) = self.completion.take() {
+      completion.accept(&mut self.editor);
+    }
+  }
+
+  fn request(&mut self, send: impl FnOnce(&mut lsp::Client, LspPosition) -> Result<()>) {
+    let position = self.lsp_position(self.editor.cursor_location());
+    let Some(client) = self.language_server.as_mut() else {
+      self.message = Some("no language server".to_string());
+      return;
+    };
+    if !client.is_initialized() {
+      self.message = Some("language server is starting".to_string());
+      return;
+    }
+    if let Err(err) = send(client, position) {
+      log!("{:#}", err);
+    }
+  }
+
+  pub fn lsp_position(&self, location: Location) -> LspPosition {
+    LspPosition { line: location.ln, character: self.editor.document().utf16_column(location) }
+  }
+
+  pub fn location_from_lsp(&self, position: LspPosition) -> Location {
+    Location { ln: position.line, col: self.editor.document().column_from_utf16(position.line, position.character) }
+  }
+
+  /// stop background work before quitting.
+  pub fn shutdown(&mut self) {
+    if let Some(swap) = self.swap.as_mut() {
+      if let Err(err) = swap.flush(self.editor.document()) {
+        log!("{:#}", err);
+      }
+    }
+    if let Some(client) = self.language_server.as_mut() {
+      client.shutdown();
+    }
+  }
+}
+
+/// write the line diff from `old` to `new` to the log.
+fn log_diff(old: &str, new: &str) {
+  let old: Vec<&str> = old.split_inclusive('\n').collect();
+  let new: Vec<&str> = new.split_inclusive('\n').collect();
+  for hunk in git::diff(&old, &new) {
+    log!("@@ line {} @@", hunk.new_start + 1);
+    for line in &hunk.old_lines {
+      log!("- {}", line.trim_end_matches('\n'));
+    }
+    for line in &new[hunk.new_start as usize..(hunk.new_start + hunk.new_len) as usize] {
+      log!("+ {}", line.trim_end_matches('\n'));
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_open_search_result() {
+    let root = std::env::temp_dir().join(format!("pound-{}-open-result", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("a.txt"), "needle\n").unwrap();
+    std::fs::write(root.join("b.txt"), "no\nsome needle\n").unwrap();
+    let mut state = AppState::open(&root.join("a.txt")).unwrap();
+    let mut panel = SearchPanel { query: "needle".to_string(), ..SearchPanel::default() };
+    panel.run(&root);
+    let search = panel.search.as_mut().unwrap();
+    while !search.is_done() {
+      std::thread::sleep(Duration::from_millis(1));
+    }
+    search.poll();
+    panel.selected = 1;
+    state.search_panel = Some(panel);
+    state.open_search_result();
+    assert_eq!(state.editor.document().path(), Some(root.join("b.txt").as_path()));
+    assert_eq!(state.editor.cursor_location(), Location { ln: 1, col: 5 });
+    assert!(state.search_panel.is_some());
+    state.shutdown();
+    std::fs::remove_dir_all(root).unwrap();
+  }
+
+  #[test]
+  fn test_check_file_with_bom() {
+    let path = std::env::temp_dir().join(format!("pound-{}-bom.txt", std::process::id()));
+    // a watcher from before the file existed sees it as changed
+    let _ = std::fs::remove_file(&path);
+    let watcher = watch::FileWatcher::new(&path);
+    std::fs::write(&path, "\u{feff}one\n").unwrap();
+    let mut state = AppState::open(&path).unwrap();
+    state.watcher = Some(watcher);
+    state.check_file();
+    assert!(state.disk_conflict.is_none());
+    assert_eq!(state.editor.document().text(), "one\n");
+    assert!(!state.editor.document().is_dirty());
+    state.editor.input("two ");
+    state.editor.save().unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "\u{feff}two one\n");
+    state.shutdown();
+    std::fs::remove_file(path).unwrap();
   }
 }
@@ -0,0 +1,153 @@
+//! Swap files: the unsaved text of a dirty document, written every few
+//! seconds and from the panic hook, so a crash does not lose it.
+
+use std::{
+  fs,
+  path::{Path, PathBuf},
+  sync::Mutex,
+  time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+
+use crate::{editor::document::{self, Document}, session};
+
+/// how often a dirty document is written to its swap file.
+const INTERVAL: Duration = Duration::from_secs(4);
+
+lazy_static! {
+  /// the latest unsaved text and its swap file, for the panic hook.
+  static ref PENDING: Mutex<Option<(PathBuf, String)>> = Mutex::new(None);
+}
+
+/// where the swap file for `path` lives under `state_dir`.
+pub fn swap_path(state_dir: &Path, path: &Path) -> Option<PathBuf> {
+  let absolute = path.canonicalize().ok()?;
+  Some(session::state_file(state_dir, "swap", &absolute, "swp"))
+}
+
+/// the contents of a swap file for `path` that is newer than the file and
+/// differs from it, i.e. edits that were never saved.
+pub fn recoverable(state_dir: &Path, path: &Path) -> Option<String> {
+  let swap = swap_path(state_dir, path)?;
+  let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+  if modified(&swap)? <= modified(path)? {
+    return None;
+  }
+  let text = fs::read_to_string(&swap).ok()?;
+  let current = document::read_text(path).map(|(text, _)| text).unwrap_or_default();
+  (text != current).then_some(text)
+}
+
+/// remove the swap file of `path`, if any.
+pub fn discard(state_dir: &Path, path: &Path) {
+  if let Some(swap) = swap_path(state_dir, path) {
+    let _ = fs::remove_file(swap);
+  }
+}
+
+/// write whatever text is pending. meant for the panic hook, so it never blocks or panics.
+pub fn write_pending() {
+  let Ok(pending) = PENDING.try_lock() else { return };
+  if let Some((path, text)) = pending.as_ref() {
+    let _ = fs::write(path, text);
+  }
+}
+
+/// keeps the swap file of one document up to date.
+pub struct Swap {
+  path: PathBuf,
+  /// the document version last handed to the panic hook.
+  snapshot: Option<u64>,
+  /// the document version in the swap file.
+  written: Option<u64>,
+  last_write: Instant,
+}
+
+impl Swap {
+  pub fn new(state_dir: &Path, file: &Path) -> Option<Self> {
+    Some(Self {
+      path: swap_path(state_dir, file)?,
+      snapshot: None,
+      written: None,
+      last_write: Instant::now(),
+    })
+  }
+
+  /// refresh the panic hook's copy, and write the swap file once `INTERVAL`
+  /// has passed. a clean document has no swap file.
+  pub fn update(&mut self, document: &Document) -> Result<()> {
+    if !document.is_dirty() {
+      return self.remove();
+    }
+    let version = document.version();
+    if self.snapshot != Some(version) {
+      *PENDING.lock().unwrap() = Some((self.path.clone(), document.text()));
+      self.snapshot = Some(version);
+    }
+    if self.written != Some(version) && self.last_write.elapsed() >= INTERVAL {
+      self.write(document)?;
+    }
+    Ok(())
+  }
+
+  /// write the swap file now if the document is dirty, e.g. before quitting.
+  pub fn flush(&mut self, document: &Document) -> Result<()> {
+    if !document.is_dirty() {
+      return self.remove();
+    }
+    if self.written != Some(document.version()) {
+      self.write(document)?;
+    }
+    Ok(())
+  }
+
+  fn write(&mut self, document: &Document) -> Result<()> {
+    if let Some(parent) = self.path.parent() {
+      fs::create_dir_all(parent).with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    fs::write(&self.path, document.text()).with_context(|| format!("failed to write {}", self.path.display()))?;
+    self.written = Some(document.version());
+    self.last_write = Instant::now();
+    Ok(())
+  }
+
+  fn remove(&mut self) -> Result<()> {
+    if self.snapshot.take().is_some() {
+      *PENDING.lock().unwrap() = None;
+    }
+    if self.written.take().is_some() {
+      fs::remove_file(&self.path).with_context(|| format!("failed to remove {}", self.path.display()))?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::location::Location;
+
+  #[test]
+  fn test_swap() {
+    let dir = std::env::temp_dir().join(format!("pound-{}-swap", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let state_dir = dir.join("state");
+    let file = dir.join("file.txt");
+    fs::write(&file, "saved\n").unwrap();
+    let mut document = Document::open(&file).unwrap();
+    let mut swap = Swap::new(&state_dir, &file).unwrap();
+    swap.update(&document).unwrap();
+    assert!(recoverable(&state_dir, &file).is_none());
+    document.insert(Location { ln: 0, col: 0 }, "un").unwrap();
+    // make sure the swap file ends up newer than the file
+    std::thread::sleep(Duration::from_millis(20));
+    swap.flush(&document).unwrap();
+    assert_eq!(recoverable(&state_dir, &file).as_deref(), Some("unsaved\n"));
+    document.save().unwrap();
+    swap.update(&document).unwrap();
+    assert!(recoverable(&state_dir, &file).is_none());
+    fs::remove_dir_all(dir).unwrap();
+  }
+}
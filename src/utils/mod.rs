@@ -1 +0,0 @@
-pub mod char_columns;
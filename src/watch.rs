@@ -0,0 +1,95 @@
+//! Keeping a document and its file in step: autosave after a pause in
+//! typing, and noticing when another program changes the file.
+
+use std::{
+  fs,
+  path::{Path, PathBuf},
+  time::{Duration, Instant, SystemTime},
+};
+
+use crate::editor::document::Document;
+
+/// what the file looked like when we last read or wrote it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Stamp {
+  modified: Option<SystemTime>,
+  len: u64,
+}
+
+impl Stamp {
+  fn of(path: &Path) -> Option<Self> {
+    let metadata = fs::metadata(path).ok()?;
+    Some(Self { modified: metadata.modified().ok(), len: metadata.len() })
+  }
+}
+
+/// polls a file's metadata to notice changes made by other programs.
+pub struct FileWatcher {
+  path: PathBuf,
+  stamp: Option<Stamp>,
+}
+
+impl FileWatcher {
+  pub fn new(path: &Path) -> Self {
+    Self { path: path.to_path_buf(), stamp: Stamp::of(path) }
+  }
+
+  /// whether the file changed since the last call or `sync`. a deleted file
+  /// does not count as changed.
+  pub fn changed(&mut self) -> bool {
+    let Some(stamp) = Stamp::of(&self.path) else { return false };
+    let changed = self.stamp != Some(stamp);
+    self.stamp = Some(stamp);
+    changed
+  }
+
+  /// accept the file as it is now, e.g. after writing it ourselves.
+  pub fn sync(&mut self) {
+    self.stamp = Stamp::of(&self.path);
+  }
+}
+
+/// saves a dirty document once it has not been edited for `delay`.
+pub struct Autosave {
+  delay: Duration,
+  /// the last version seen, and when it was first seen.
+  seen: (u64, Instant),
+}
+
+impl Autosave {
+  pub fn new(delay: Duration) -> Self {
+    Self { delay, seen: (0, Instant::now()) }
+  }
+
+  /// whether the document should be saved now.
+  pub fn is_due(&mut self, document: &Document) -> bool {
+    let version = document.version();
+    if self.seen.0 != version {
+      self.seen = (version, Instant::now());
+    }
+    document.is_dirty() && document.path().is_some() && self.seen.1.elapsed() >= self.delay
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::location::Location;
+
+  #[test]
+  fn test_watch_and_autosave() {
+    let path = std::env::temp_dir().join(format!("pound-{}-watch", std::process::id()));
+    fs::write(&path, "one\n").unwrap();
+    let mut watcher = FileWatcher::new(&path);
+    assert!(!watcher.changed());
+    fs::write(&path, "one\ntwo\n").unwrap();
+    assert!(watcher.changed());
+    assert!(!watcher.changed());
+    let mut document = Document::open(&path).unwrap();
+    let mut autosave = Autosave::new(Duration::ZERO);
+    assert!(!autosave.is_due(&document));
+    document.insert(Location { ln: 0, col: 0 }, "zero\n").unwrap();
+    assert!(autosave.is_due(&document));
+    fs::remove_file(path).unwrap();
+  }
+}
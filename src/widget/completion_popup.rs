@@ -0,0 +1,60 @@
+use ratatui::{prelude::*, widgets::*};
+
+use crate::state::AppState;
+
+/// at most this many candidates are visible at once.
+const MAX_HEIGHT: u16 = 8;
+const MAX_WIDTH: u16 = 40;
+
+/// the completion candidates, drawn next to the cursor over the edit area.
+#[derive(Default)]
+pub struct CompletionPopup;
+
+impl StatefulWidget for CompletionPopup {
+  type State = AppState;
+  fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+    let Some(completion) = &state.completion else { return };
+    let items = completion.items();
+    if items.is_empty() || area.width == 0 || area.height == 0 {
+      return;
+    }
+    let height = (items.len() as u16).min(MAX_HEIGHT).min(area.height);
+    let width = items.iter()
+      .map(|item| item.label.chars().count() + item.detail.as_ref().map_or(0, |d| d.chars().count() + 1))
+      .max()
+      .unwrap_or(0) as u16 + 2;
+    let width = width.min(MAX_WIDTH).min(area.width);
+    // anchor under the start of the completed word, flipping above the cursor near the bottom
+    let anchor = state.editor.visible_position(completion.start).unwrap_or(state.editor.cursor_position());
+    let x = (area.left() + anchor.x as u16).min(area.right().saturating_sub(width));
+    let below = area.top() + anchor.y as u16 + 1;
+    let y = if below + height <= area.bottom() {
+      below
+    } else {
+      (area.top() + anchor.y as u16).saturating_sub(height).max(area.top())
+    };
+    let popup = Rect::new(x, y, width, height);
+    Clear.render(popup, buf);
+    buf.set_style(popup, Style::default().bg(Color::DarkGray));
+    // scroll so the selected candidate stays visible
+    let first = completion.selected.saturating_sub(height.saturating_sub(1) as usize);
+    for (i, item) in items.iter().skip(first).take(height as usize).enumerate() {
+      let row = popup.top() + i as u16;
+      let style = if first + i == completion.selected {
+        Style::default().bg(Color::Blue)
+      } else {
+        Style::default()
+      };
+      buf.set_style(Rect::new(popup.left(), row, popup.width, 1), style);
+      buf.set_stringn(popup.left() + 1, row, &item.label, popup.width as usize - 1, Style::default());
+      if let Some(detail) = &item.detail {
+        let len = detail.chars().count() as u16;
+        let label_end = popup.left() + 2 + item.label.chars().count() as u16;
+        let detail_x = popup.right().saturating_sub(len + 1).max(label_end);
+        if detail_x < popup.right() {
+          buf.set_stringn(detail_x, row, detail, (popup.right() - detail_x) as usize, Style::default().fg(Color::Gray));
+        }
+      }
+    }
+  }
+}
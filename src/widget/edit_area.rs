@@ -1,6 +1,6 @@
 use ratatui::{prelude::*, widgets::*};
 
-use crate::state::AppState;
+use crate::{state::AppState, settings::LineNumbers, editor::{location::Location, measure::char_width, diagnostic::Severity, fold, whitespace}, git::HunkKind};
 
 #[derive(Default)]
 pub struct EditArea;
@@ -8,10 +8,218 @@ pub struct EditArea;
 impl StatefulWidget for EditArea {
   type State = AppState;
   fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+    let gutter_width = gutter_width(state).min(area.width);
+    let gutter = Rect { width: gutter_width, ..area };
+    let area = Rect { x: area.x + gutter_width, width: area.width - gutter_width, ..area };
+    state.edit_area = area;
     state.editor.set_size(area.width as u32, area.height as u32);
+    let theme = state.settings.theme;
+    buf.set_style(area, theme.text());
+    buf.set_style(gutter, theme.text());
     let visual_area = state.editor.visual_area();
     for (i, line) in visual_area.iter().enumerate() {
       buf.set_string(area.left(), area.top() + i as u16, line, Style::default());
     }
+    let (_, offset_left) = state.editor.offsets();
+    let cursor = state.editor.cursor_location();
+    if let Some(pos) = state.editor.visible_position(cursor) {
+      if state.settings.cursor_line {
+        buf.set_style(Rect::new(area.left(), area.top() + pos.y as u16, area.width, 1), Style::default().bg(theme.cursor_line()));
+      }
+      if state.settings.cursor_column {
+        highlight_column(buf, area, &visual_area, pos.x, Style::default().bg(theme.cursor_line()));
+      }
+    }
+    for ruler in state.editor.rulers() {
+      if let Some(x) = ruler.checked_sub(offset_left).filter(|x| *x < area.width as u32) {
+        highlight_column(buf, area, &visual_area, x, Style::default().bg(theme.guide()));
+      }
+    }
+    if state.show_whitespace {
+      render_whitespace(area, buf, state);
+    }
+    // closed folds say how much they hide after their first line
+    for fold in state.editor.document().folds() {
+      let len = state.editor.document().line(fold.start).map_or(0, |line| line.len()) as u32;
+      let Some(pos) = state.editor.visible_position(Location { ln: fold.start, col: len }) else { continue };
+      let hint = format!(" ⋯ {} lines", fold.hidden_count());
+      let x = area.left() + pos.x as u16;
+      buf.set_stringn(x, area.top() + pos.y as u16, &hint, area.right().saturating_sub(x) as usize, Style::default().fg(theme.faint()));
+    }
+    let mark = |buf: &mut Buffer, location: Location, style: Style| {
+      if let Some(pos) = state.editor.visible_position(location) {
+        buf.get_mut(area.left() + pos.x as u16, area.top() + pos.y as u16).set_style(style);
+      }
+    };
+    let primary = state.editor.cursor_location();
+    for cursor in state.editor.cursors() {
+      if let Some(range) = cursor.selection {
+        for ln in range.start.ln..=range.end.ln {
+          let line = state.editor.document().line(ln).unwrap_or_default();
+          for char in line.iter().filter(|c| c.location >= range.start && c.location < range.end) {
+            mark(buf, char.location, Style::default().bg(theme.selection()));
+          }
+        }
+      }
+      // the terminal cursor only shows the primary one
+      if cursor.location != primary {
+        mark(buf, cursor.location, Style::default().add_modifier(Modifier::REVERSED));
+      }
+    }
+    for diagnostic in state.editor.document().diagnostics() {
+      let style = Style::default()
+        .underline_color(severity_color(diagnostic.severity))
+        .add_modifier(Modifier::UNDERLINED);
+      let range = diagnostic.range;
+      let mut marked = false;
+      for ln in range.start.ln..=range.end.ln {
+        let line = state.editor.document().line(ln).unwrap_or_default();
+        for char in line.iter().filter(|c| c.location >= range.start && c.location < range.end) {
+          mark(buf, char.location, style);
+          marked = true;
+        }
+      }
+      // an empty range still gets one underlined cell
+      if !marked {
+        mark(buf, range.start, style);
+      }
+    }
+    render_gutter(gutter, buf, state);
+    if let Some((bracket, matching)) = state.editor.matching_bracket() {
+      for location in [bracket, matching] {
+        mark(buf, location, Style::default().bg(theme.guide()).add_modifier(Modifier::BOLD));
+      }
+    }
+  }
+}
+
+/// style the cells at display column `x` on every row. a double width
+/// character across the column is styled as a whole.
+fn highlight_column(buf: &mut Buffer, area: Rect, lines: &[String], x: u32, style: Style) {
+  for row in 0..area.height {
+    let line = lines.get(row as usize).map_or("", String::as_str);
+    // past the end of the line the column is where it would be
+    let start = line.chars()
+      .scan(0, |end, char| {
+        let start = *end;
+        *end += char_width(char);
+        Some((start, *end))
+      })
+      .find(|(start, end)| *start <= x && x < *end)
+      .map_or(x, |(start, _)| start);
+    buf.get_mut(area.left() + start as u16, area.top() + row).set_style(style);
+  }
+}
+
+/// spaces, tabs and line breaks as faint glyphs, with trailing whitespace
+/// highlighted.
+fn render_whitespace(area: Rect, buf: &mut Buffer, state: &AppState) {
+  let editor = &state.editor;
+  let faint = Style::default().fg(Color::DarkGray);
+  for ln in editor.visible_lines() {
+    let line = editor.document().line(ln).unwrap_or_default();
+    let trailing_from = line.len() - whitespace::trailing_len(&line);
+    for (i, char) in line.iter().enumerate() {
+      let Some(pos) = editor.visible_position(char.location) else { continue };
+      let cell = buf.get_mut(area.left() + pos.x as u16, area.top() + pos.y as u16);
+      match char.char {
+        ' ' => cell.set_symbol("·").set_style(faint),
+        '\t' => cell.set_symbol("→").set_style(faint),
+        _ => cell,
+      };
+      if i >= trailing_from {
+        cell.set_style(Style::default().bg(Color::Red));
+      }
+    }
+    if editor.is_folded(ln) {
+      continue;
+    }
+    let end = Location { ln, col: line.last().map_or(0, |c| c.location.col + 1) };
+    let symbol = match editor.document().character_at(end).map(|c| c.char) {
+      Some('\r') => "¤",
+      Some('\n') => "¬",
+      _ => continue,
+    };
+    if let Some(pos) = editor.visible_position(end) {
+      buf.get_mut(area.left() + pos.x as u16, area.top() + pos.y as u16).set_symbol(symbol).set_style(faint);
+    }
+  }
+}
+
+/// room for the diagnostic and git columns, the line numbers, the fold
+/// markers and a space.
+fn gutter_width(state: &AppState) -> u16 {
+  if state.settings.line_numbers == LineNumbers::Off {
+    return 4;
+  }
+  let digits = state.editor.document().line_count().max(1).to_string().len().max(3);
+  digits as u16 + 4
+}
+
+/// the line numbers, with a sign for the most severe diagnostic on each line
+/// and a marker for lines changed since HEAD. lines that open a block get a
+/// fold marker.
+fn render_gutter(area: Rect, buf: &mut Buffer, state: &AppState) {
+  if area.width < 4 {
+    return;
+  }
+  let document = state.editor.document();
+  let cursor = state.editor.cursor_location();
+  let faint = Style::default().fg(state.settings.theme.faint());
+  let lines = state.editor.visible_lines();
+  // relative numbers count rows, so folded lines count once
+  let cursor_row = lines.iter().position(|ln| *ln == cursor.ln);
+  for (row, ln) in lines.into_iter().enumerate() {
+    let style = if ln == cursor.ln { Style::default().add_modifier(Modifier::BOLD) } else { faint };
+    let number = match (state.settings.line_numbers, cursor_row) {
+      (LineNumbers::Off, _) => None,
+      (LineNumbers::Relative, Some(cursor_row)) if ln != cursor.ln => Some(row.abs_diff(cursor_row)),
+      _ => Some(ln as usize + 1),
+    };
+    let row = row as u16;
+    if let Some(number) = number {
+      let number = format!("{:>width$}  ", number, width = area.width as usize - 4);
+      buf.set_stringn(area.left() + 2, area.top() + row, &number, area.width as usize - 2, style);
+    }
+    let marker = if state.editor.is_folded(ln) {
+      Some("▸")
+    } else if !document.is_large() && fold::is_foldable(document, ln, state.editor.language()) {
+      Some("▾")
+    } else {
+      None
+    };
+    if let Some(marker) = marker {
+      buf.set_string(area.right() - 2, area.top() + row, marker, faint);
+    }
+    if let Some(hunk) = state.git_gutter.as_ref().and_then(|gutter| gutter.hunk_at(ln)) {
+      let (sign, color) = match hunk.kind() {
+        HunkKind::Added => ("+", Color::Green),
+        HunkKind::Modified => ("~", Color::Yellow),
+        HunkKind::Removed => ("_", Color::Red),
+      };
+      buf.set_string(area.left() + 1, area.top() + row, sign, Style::default().fg(color));
+    }
+    let severity = document.diagnostics().iter()
+      .filter(|d| d.range.start.ln <= ln && ln <= d.range.end.ln)
+      .map(|d| d.severity)
+      .min_by_key(|severity| *severity as u8);
+    if let Some(severity) = severity {
+      let sign = match severity {
+        Severity::Error => "E",
+        Severity::Warning => "W",
+        Severity::Information => "I",
+        Severity::Hint => "H",
+      };
+      buf.set_string(area.left(), area.top() + row, sign, Style::default().fg(severity_color(severity)));
+    }
+  }
+}
+
+fn severity_color(severity: Severity) -> Color {
+  match severity {
+    Severity::Error => Color::Red,
+    Severity::Warning => Color::Yellow,
+    Severity::Information => Color::Blue,
+    Severity::Hint => Color::Cyan,
   }
 }
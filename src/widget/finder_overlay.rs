@@ -0,0 +1,61 @@
+use ratatui::{prelude::*, widgets::*};
+
+use crate::state::AppState;
+
+/// the Ctrl+P file finder: the query and matches on the left, a preview of
+/// the highlighted file on the right.
+#[derive(Default)]
+pub struct FinderOverlay;
+
+impl StatefulWidget for FinderOverlay {
+  type State = AppState;
+  fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+    let Some(finder) = state.finder.as_mut() else { return };
+    finder.update();
+    let width = (area.width as u32 * 8 / 10) as u16;
+    let height = (area.height as u32 * 7 / 10) as u16;
+    let overlay = Rect::new(area.left() + (area.width - width) / 2, area.top() + (area.height - height) / 2, width, height);
+    Clear.render(overlay, buf);
+    let title = if finder.is_indexing() {
+      format!(" Files ({} indexed…) ", finder.indexed())
+    } else {
+      format!(" Files ({}) ", finder.indexed())
+    };
+    let block = Block::default().borders(Borders::ALL).title(title);
+    let inner = block.inner(overlay);
+    block.render(overlay, buf);
+    if inner.height < 2 || inner.width < 4 {
+      return;
+    }
+    let columns = Layout::new()
+      .direction(Direction::Horizontal)
+      .constraints([Constraint::Percentage(45), Constraint::Percentage(55)])
+      .split(inner);
+    let (list, preview) = (columns[0], columns[1]);
+    let prompt = format!("> {}", finder.query);
+    buf.set_stringn(list.left(), list.top(), &prompt, list.width as usize, Style::default().add_modifier(Modifier::BOLD));
+    let rows = list.height.saturating_sub(1) as usize;
+    let first = finder.selected.saturating_sub(rows.saturating_sub(1));
+    for (i, found) in finder.matches().iter().enumerate().skip(first).take(rows) {
+      let y = list.top() + 1 + (i - first) as u16;
+      let selected = i == finder.selected;
+      if selected {
+        buf.set_style(Rect::new(list.left(), y, list.width, 1), Style::default().bg(Color::Blue));
+      }
+      for (col, char) in found.path.chars().enumerate().take(list.width as usize) {
+        let mut style = Style::default();
+        if found.positions.contains(&col) {
+          style = style.fg(Color::Yellow).add_modifier(Modifier::BOLD);
+        }
+        buf.get_mut(list.left() + col as u16, y).set_char(char).set_style(style);
+      }
+    }
+    let preview_block = Block::default().borders(Borders::LEFT);
+    let preview_inner = preview_block.inner(preview);
+    preview_block.render(preview, buf);
+    for (i, line) in finder.preview().iter().take(preview_inner.height as usize).enumerate() {
+      let line = line.replace('\t', "  ");
+      buf.set_stringn(preview_inner.left() + 1, preview_inner.top() + i as u16, &line, preview_inner.width.saturating_sub(1) as usize, Style::default());
+    }
+  }
+}
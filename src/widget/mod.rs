@@ -1,7 +1,21 @@
 mod edit_area;
+mod completion_popup;
 mod status_bar;
 mod terminal;
+mod quit_dialog;
+mod sidebar;
+mod finder_overlay;
+mod search_panel;
+mod prompt_line;
+pub mod scrollbar;
 
 pub use edit_area::EditArea;
+pub use completion_popup::CompletionPopup;
 pub use status_bar::StatusBar;
 pub use terminal::Terminal;
+pub use quit_dialog::QuitDialog;
+pub use sidebar::Sidebar;
+pub use finder_overlay::FinderOverlay;
+pub use search_panel::SearchPanel;
+pub use prompt_line::PromptLine;
+pub use scrollbar::Scrollbar;
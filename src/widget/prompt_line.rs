@@ -0,0 +1,38 @@
+use ratatui::{prelude::*, widgets::*};
+
+use crate::{editor::measure::char_width, state::AppState};
+
+/// the open prompt, drawn over the status bar. the input scrolls sideways to
+/// keep the cursor visible.
+#[derive(Default)]
+pub struct PromptLine;
+
+impl StatefulWidget for PromptLine {
+  type State = AppState;
+  fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+    let Some(prompt) = state.prompt.as_mut() else { return };
+    buf.set_style(area, Style::default().bg(state.settings.theme.bar()));
+    buf.set_stringn(area.left(), area.top(), &prompt.label, area.width as usize, Style::default());
+    let start = (area.left() + prompt.label.chars().count() as u16).min(area.right());
+    // errors about the input, or where Tab is in the completions, go to the right
+    let note = prompt.error.clone().map(|error| (error, Color::Yellow))
+      .or_else(|| prompt.completion_hint().map(|hint| (hint, Color::Gray)));
+    let note_width = note.as_ref().map_or(0, |(note, _)| note.chars().count() as u16 + 1);
+    let width = area.right().saturating_sub(start).saturating_sub(note_width).max(1) as u32;
+    let cursor = prompt.cursor_x();
+    let scroll = (cursor + 1).saturating_sub(width);
+    let mut x = 0;
+    for char in prompt.text().chars() {
+      let char_width = char_width(char);
+      if x >= scroll && x + char_width <= scroll + width {
+        buf.set_string(start + (x - scroll) as u16, area.top(), char.to_string(), Style::default());
+      }
+      x += char_width;
+    }
+    prompt.screen_cursor = (start + (cursor - scroll) as u16).min(area.right().saturating_sub(1));
+    if let Some((note, color)) = note {
+      let x = area.right().saturating_sub(note.chars().count() as u16).max(start);
+      buf.set_stringn(x, area.top(), &note, area.right().saturating_sub(x) as usize, Style::default().fg(color));
+    }
+  }
+}
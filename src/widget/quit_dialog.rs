@@ -0,0 +1,49 @@
+use ratatui::{prelude::*, widgets::*};
+
+use crate::state::{AppState, QuitChoice};
+
+/// the "Save / Discard / Cancel" dialog shown when quitting with unsaved changes.
+#[derive(Default)]
+pub struct QuitDialog;
+
+impl StatefulWidget for QuitDialog {
+  type State = AppState;
+  fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+    let Some(selected) = state.quit_dialog else { return };
+    let name = state.editor.document().path()
+      .and_then(|path| path.file_name())
+      .map(|name| name.to_string_lossy().to_string())
+      .unwrap_or_else(|| "[scratch]".to_string());
+    let text = format!("{} has unsaved changes.", name);
+    let width = (text.chars().count() as u16 + 4).max(36).min(area.width);
+    let height = 6.min(area.height);
+    let dialog = Rect::new(
+      area.left() + (area.width - width) / 2,
+      area.top() + (area.height - height) / 2,
+      width,
+      height,
+    );
+    Clear.render(dialog, buf);
+    let block = Block::default().borders(Borders::ALL).title(" Quit ");
+    let inner = block.inner(dialog);
+    block.render(dialog, buf);
+    if inner.height < 3 {
+      return;
+    }
+    buf.set_stringn(inner.left() + 1, inner.top(), &text, inner.width.saturating_sub(1) as usize, Style::default());
+    let mut x = inner.left() + 1;
+    for choice in [QuitChoice::Save, QuitChoice::Discard, QuitChoice::Cancel] {
+      let label = format!("[{}]", choice.label());
+      let style = if choice == selected {
+        Style::default().bg(Color::Blue).add_modifier(Modifier::BOLD)
+      } else {
+        Style::default()
+      };
+      if x + label.len() as u16 > inner.right() {
+        break;
+      }
+      buf.set_string(x, inner.top() + 2, &label, style);
+      x += label.len() as u16 + 2;
+    }
+  }
+}
@@ -0,0 +1,170 @@
+use ratatui::{prelude::*, widgets::*};
+
+use crate::{state::AppState, editor::{diagnostic::Severity, fold}, git::HunkKind};
+
+/// cells the minimap takes, each two dots wide.
+pub const MINIMAP_WIDTH: u16 = 8;
+/// text columns behind one minimap dot.
+const COLUMNS_PER_DOT: u32 = 5;
+
+/// the scrollbar at the right edge of the edit area, with the minimap to its
+/// left when turned on. the thumb covers the rows on screen.
+#[derive(Default)]
+pub struct Scrollbar;
+
+impl StatefulWidget for Scrollbar {
+  type State = AppState;
+  fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+    state.scrollbar_area = area;
+    if area.width == 0 || area.height == 0 {
+      return;
+    }
+    let theme = state.settings.theme;
+    buf.set_style(area, theme.text());
+    let bar_width = state.settings.scrollbar as u16;
+    if state.settings.minimap && area.width > bar_width {
+      render_minimap(Rect { width: area.width - bar_width, ..area }, buf, state);
+    }
+    if !state.settings.scrollbar {
+      return;
+    }
+    let bar = Rect { x: area.right() - 1, width: 1, ..area };
+    let total = state.editor.row_count();
+    let height = bar.height as u32;
+    let (offset_top, _) = state.editor.offsets();
+    let visible = state.edit_area.height as u32;
+    let thumb_top = track_row(offset_top, total, height);
+    let thumb_bottom = track_row(offset_top + visible, total, height).max(thumb_top + 1).min(height);
+    for y in 0..height {
+      let style = if (thumb_top..thumb_bottom).contains(&y) {
+        Style::default().bg(theme.guide())
+      } else {
+        Style::default().fg(theme.faint())
+      };
+      buf.set_string(bar.left(), bar.top() + y as u16, "│", style);
+    }
+    for (ln, color) in markers(state) {
+      let row = fold::row_of(state.editor.document().folds(), ln);
+      let y = track_row(row, total, height).min(height - 1);
+      buf.get_mut(bar.left(), bar.top() + y as u16).set_symbol("━").set_fg(color);
+    }
+  }
+}
+
+/// the columns the scrollbar and minimap need, taken from the right of
+/// `area`.
+pub fn width(state: &AppState, area: Rect) -> u16 {
+  let minimap = if state.settings.minimap { MINIMAP_WIDTH } else { 0 };
+  let width = minimap + state.settings.scrollbar as u16;
+  // the text keeps most of a narrow window
+  if width * 4 > area.width { 0 } else { width }
+}
+
+/// the visual row under `y` in a scrollbar `height` rows tall.
+pub fn row_at(y: u32, total: u32, height: u32) -> u32 {
+  (y as u64 * total as u64 / height.max(1) as u64) as u32
+}
+
+/// the visual row under `y` in the minimap.
+pub fn minimap_row_at(y: u32, total: u32, height: u32) -> u32 {
+  y * 4 * lines_per_dot(total, height)
+}
+
+/// where visual row `row` is in a scrollbar `height` rows tall.
+fn track_row(row: u32, total: u32, height: u32) -> u32 {
+  (row as u64 * height as u64 / total.max(1) as u64) as u32
+}
+
+/// how many rows each dot row of the minimap stands for, so the whole
+/// document fits.
+fn lines_per_dot(total: u32, height: u32) -> u32 {
+  total.div_ceil(height.max(1) * 4).max(1)
+}
+
+/// lines worth a mark on the scrollbar, least important first so the rest
+/// are drawn over them: git changes, search hits, then diagnostics.
+fn markers(state: &mut AppState) -> Vec<(u32, Color)> {
+  let document = state.editor.document();
+  let mut markers = Vec::new();
+  if let Some(gutter) = &state.git_gutter {
+    for hunk in gutter.hunks() {
+      let color = match hunk.kind() {
+        HunkKind::Added => Color::Green,
+        HunkKind::Modified => Color::Yellow,
+        HunkKind::Removed => Color::Red,
+      };
+      let ln = if hunk.kind() == HunkKind::Removed { hunk.new_start.saturating_sub(1) } else { hunk.new_start };
+      markers.push((ln, color));
+    }
+  }
+  let regex = state.search_panel.as_ref().and_then(|panel| panel.search.as_ref()).map(|search| &search.regex);
+  if let Some(regex) = regex.filter(|_| !document.is_large()) {
+    for &ln in state.search_hits.get(document, regex) {
+      markers.push((ln, Color::Cyan));
+    }
+  }
+  let mut diagnostics: Vec<_> = document.diagnostics().iter().collect();
+  diagnostics.sort_by_key(|diagnostic| std::cmp::Reverse(diagnostic.severity as u8));
+  for diagnostic in diagnostics {
+    let color = match diagnostic.severity {
+      Severity::Error => Color::Red,
+      Severity::Warning => Color::Yellow,
+      Severity::Information => Color::Blue,
+      Severity::Hint => Color::Cyan,
+    };
+    markers.push((diagnostic.range.start.ln, color));
+  }
+  markers
+}
+
+/// every line as braille dots, one dot per `COLUMNS_PER_DOT` columns that
+/// hold anything but whitespace. the rows on screen are shaded.
+fn render_minimap(area: Rect, buf: &mut Buffer, state: &AppState) {
+  let document = state.editor.document();
+  if document.is_large() {
+    return;
+  }
+  let total = state.editor.row_count();
+  let height = area.height as u32;
+  let per_dot = lines_per_dot(total, height);
+  let mut cells = vec![0u8; area.width as usize * area.height as usize];
+  let mut row = 0;
+  for (ln, line) in document.lines().iter().enumerate() {
+    if document.is_hidden(ln as u32) {
+      continue;
+    }
+    let dot_y = row / per_dot;
+    row += 1;
+    let (y, sub_y) = (dot_y / 4, dot_y % 4);
+    if y >= height {
+      break;
+    }
+    for char in line.iter().filter(|c| !c.char.is_whitespace()) {
+      let dot_x = char.position.x / COLUMNS_PER_DOT;
+      let (x, sub_x) = (dot_x / 2, dot_x % 2);
+      if x >= area.width as u32 {
+        break;
+      }
+      cells[(y * area.width as u32 + x) as usize] |= DOTS[sub_x as usize][sub_y as usize];
+    }
+  }
+  let (offset_top, _) = state.editor.offsets();
+  let visible = state.edit_area.height as u32;
+  let shaded = offset_top / per_dot / 4..(offset_top + visible).div_ceil(per_dot * 4);
+  let theme = state.settings.theme;
+  for y in 0..area.height {
+    let style = if shaded.contains(&(y as u32)) {
+      Style::default().fg(theme.faint()).bg(theme.cursor_line())
+    } else {
+      Style::default().fg(theme.faint())
+    };
+    for x in 0..area.width {
+      let bits = cells[y as usize * area.width as usize + x as usize];
+      let glyph = char::from_u32(0x2800 + bits as u32).unwrap_or(' ');
+      buf.set_string(area.left() + x, area.top() + y, glyph.to_string(), style);
+    }
+  }
+}
+
+/// braille dot bits by column, then row.
+const DOTS: [[u8; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
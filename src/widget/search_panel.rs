@@ -0,0 +1,89 @@
+use ratatui::{prelude::*, widgets::*};
+
+use crate::{search::Field, state::AppState};
+
+/// search in files: the query and replacement on top, then the matches
+/// grouped by file. drawn in place of the log.
+#[derive(Default)]
+pub struct SearchPanel;
+
+impl StatefulWidget for SearchPanel {
+  type State = AppState;
+  fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+    let Some(panel) = state.search_panel.as_mut() else { return };
+    if area.height < 3 {
+      return;
+    }
+    if let Some(search) = panel.search.as_mut() {
+      search.poll();
+    }
+    buf.set_style(area, Style::default());
+    let field_style = |field: Field| if panel.field == field {
+      Style::default().add_modifier(Modifier::BOLD)
+    } else {
+      Style::default().fg(Color::Gray)
+    };
+    let mode = if panel.is_regex { "[regex]" } else { "[literal]" };
+    let query = format!("search: {} {}", panel.query, mode);
+    buf.set_stringn(area.left(), area.top(), &query, area.width as usize, field_style(Field::Query));
+    let replace = format!("replace: {}", panel.replacement);
+    buf.set_stringn(area.left(), area.top() + 1, &replace, area.width as usize, field_style(Field::Replace));
+    let status = if let Some(error) = &panel.error {
+      error.clone()
+    } else if panel.confirm.is_some() {
+      "replace this match? (y)es (n)o (a)ll (q)uit".to_string()
+    } else if let Some(search) = &panel.search {
+      let searching = if search.is_done() { "" } else { ", searching…" };
+      format!("{} matches in {} files{}", search.match_count(), search.results().len(), searching)
+    } else {
+      "tab: next field  ctrl+r: regex  enter: search/open  r: replace  esc: close".to_string()
+    };
+    let x = area.left() + (query.chars().count() as u16 + 2).max(area.width / 2);
+    if x < area.right() {
+      buf.set_stringn(x, area.top(), &status, (area.right() - x) as usize, Style::default().fg(Color::Yellow));
+    }
+    let Some(search) = panel.search.as_ref() else { return };
+    // one row per file plus one per match, scrolled to keep the selection visible
+    let mut rows = Vec::new();
+    let mut selected_row = 0;
+    let mut index = 0;
+    for file in search.results() {
+      rows.push((None, file.path.display().to_string()));
+      for found in &file.matches {
+        if index == panel.selected {
+          selected_row = rows.len();
+        }
+        rows.push((Some(found), format!("  {:>4}: ", found.location.ln + 1)));
+        index += 1;
+      }
+    }
+    let height = (area.height - 2) as usize;
+    let first = selected_row.saturating_sub(height.saturating_sub(1));
+    for (i, (found, text)) in rows.iter().enumerate().skip(first).take(height) {
+      let y = area.top() + 2 + (i - first) as u16;
+      let Some(found) = found else {
+        buf.set_stringn(area.left(), y, text, area.width as usize, Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD));
+        continue;
+      };
+      if i == selected_row {
+        let bg = if panel.field == Field::Results { Color::Blue } else { Color::DarkGray };
+        buf.set_style(Rect::new(area.left(), y, area.width, 1), Style::default().bg(bg));
+      }
+      buf.set_stringn(area.left(), y, text, area.width as usize, Style::default().fg(Color::DarkGray));
+      let mut x = area.left() + text.chars().count() as u16;
+      let indent = found.line.chars().take_while(|c| c.is_whitespace()).count();
+      for (col, char) in found.line.chars().enumerate().skip(indent) {
+        if x >= area.right() {
+          break;
+        }
+        let char = if char == '\t' { ' ' } else { char };
+        let mut style = Style::default();
+        if col >= found.location.col as usize && col < found.location.col as usize + found.len {
+          style = style.fg(Color::Yellow).add_modifier(Modifier::BOLD);
+        }
+        buf.get_mut(x, y).set_char(char).set_style(style);
+        x += crate::editor::measure::char_width(char).max(1) as u16;
+      }
+    }
+  }
+}
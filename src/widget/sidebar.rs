@@ -0,0 +1,54 @@
+use ratatui::{prelude::*, widgets::*};
+
+use crate::state::AppState;
+
+/// the file explorer, drawn to the left of the edit area.
+#[derive(Default)]
+pub struct Sidebar;
+
+impl StatefulWidget for Sidebar {
+  type State = AppState;
+  fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+    state.sidebar_area = area;
+    if area.width == 0 {
+      return;
+    }
+    let block = Block::default().borders(Borders::RIGHT);
+    let inner = block.inner(area);
+    block.render(area, buf);
+    let explorer = &mut state.explorer;
+    // the bottom row doubles as the delete confirmation
+    let input = explorer.confirm_delete
+      .then(|| format!("delete {}? (y/n)", explorer.selected_entry().map_or("", |e| e.name.as_str())));
+    let rows = inner.height.saturating_sub(input.is_some() as u16);
+    explorer.scroll_to_selected(rows as usize);
+    let open = state.editor.document().path().map(|path| explorer.entry_path(path));
+    for (i, entry) in explorer.entries().iter().enumerate().skip(explorer.scroll).take(rows as usize) {
+      let y = inner.top() + (i - explorer.scroll) as u16;
+      let icon = match (entry.is_dir, explorer.is_expanded(&entry.path)) {
+        (true, true) => "▾ ",
+        (true, false) => "▸ ",
+        _ => "  ",
+      };
+      let text = format!("{}{}{}", "  ".repeat(entry.depth), icon, entry.name);
+      let mut style = Style::default();
+      if entry.is_dir {
+        style = style.fg(Color::Blue);
+      }
+      if open.as_ref() == Some(&entry.path) {
+        style = style.add_modifier(Modifier::BOLD);
+      }
+      if i == explorer.selected {
+        let bg = if explorer.focused { Color::Blue } else { Color::DarkGray };
+        buf.set_style(Rect::new(inner.left(), y, inner.width, 1), Style::default().bg(bg));
+        style = style.fg(Color::White);
+      }
+      buf.set_stringn(inner.left(), y, &text, inner.width as usize, style);
+    }
+    if let Some(input) = input {
+      let y = inner.top() + rows;
+      buf.set_style(Rect::new(inner.left(), y, inner.width, 1), Style::default().bg(Color::DarkGray));
+      buf.set_stringn(inner.left(), y, &input, inner.width as usize, Style::default());
+    }
+  }
+}
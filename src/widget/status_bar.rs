@@ -1,11 +1,39 @@
 use ratatui::{prelude::*, widgets::*};
 
+use crate::{state::AppState, editor::diagnostic::Severity};
+
 #[derive(Default)]
 pub struct StatusBar;
 
-impl Widget for StatusBar {
-  fn render(self, area: Rect, buf: &mut Buffer) {
-    buf.set_style(area, Style::default().bg(Color::Blue));
-    buf.set_string(area.left(), area.top(), "Status Bar", Style::default());
+impl StatefulWidget for StatusBar {
+  type State = AppState;
+  fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+    buf.set_style(area, Style::default().bg(state.settings.theme.bar()));
+    let name = state.editor.document().path()
+      .and_then(|path| path.file_name())
+      .map(|name| name.to_string_lossy().to_string())
+      .unwrap_or_else(|| "[scratch]".to_string());
+    let cursor = state.editor.cursor_location();
+    let left = format!(" {}  {}:{}", name, cursor.ln + 1, cursor.col + 1);
+    buf.set_string(area.left(), area.top(), &left, Style::default());
+    let diagnostics = state.editor.document().diagnostics();
+    let count = |severity: Severity| diagnostics.iter().filter(|d| d.severity == severity).count();
+    let mut right = String::new();
+    if !diagnostics.is_empty() {
+      right = format!("E:{} W:{} ", count(Severity::Error), count(Severity::Warning));
+    }
+    // an explicit message wins over the diagnostic under the cursor
+    let message = state.message.clone().or_else(|| {
+      state.editor.diagnostic_at_cursor().map(|d| match &d.source {
+        Some(source) => format!("{}: {}", source, d.message.lines().next().unwrap_or_default()),
+        None => d.message.lines().next().unwrap_or_default().to_string(),
+      })
+    });
+    if let Some(message) = message {
+      right = format!("{} {}", message, right);
+    }
+    let width = right.chars().count() as u16;
+    let x = area.right().saturating_sub(width).max(area.left() + left.chars().count() as u16 + 1);
+    buf.set_stringn(x, area.top(), &right, area.right().saturating_sub(x) as usize, Style::default());
   }
 }
@@ -12,7 +12,7 @@ impl Widget for Terminal {
     let offset = 0;
     let display_lines: Vec<String> = if len - offset > area.height as usize {
       lines.into_iter()
-        .skip(len - offset as usize - area.height as usize)
+        .skip(len - offset - area.height as usize)
         .take(area.height as usize)
         .collect()
     } else {